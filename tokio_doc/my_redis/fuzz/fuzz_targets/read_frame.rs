@@ -0,0 +1,32 @@
+#![no_main]
+
+// cargo +nightly fuzz run read_frame -- -malloc_limit_mb=64
+//
+// Arbitrary bytes from a client must never panic the parser, and with the
+// malloc limit, never make it allocate much more than the bytes sent.
+
+use libfuzzer_sys::fuzz_target;
+use my_redis::Connection;
+
+fuzz_target!(|data: &[u8]| {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+
+    rt.block_on(async {
+        let stream = tokio::io::join(data, tokio::io::sink());
+        let mut conn = Connection::new(stream);
+
+        // every frame read must write back out and read the same.
+        while let Ok(Some(frame)) = conn.read_frame().await {
+            let (client, server) = tokio::io::duplex(64);
+            let mut writer = Connection::new(client);
+            let mut reader = Connection::new(server);
+
+            let (written, read) = tokio::join!(writer.write_frame(&frame), reader.read_frame());
+            written.unwrap();
+            let read = read.unwrap().unwrap();
+            assert_eq!(format!("{:?}", read), format!("{:?}", frame));
+        }
+    });
+});
@@ -0,0 +1,409 @@
+use crate::cmd::{self, CommandSpec};
+use crate::{glob, Config};
+
+use bytes::Bytes;
+use mini_redis::Result;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::RwLock;
+
+// ACL categories. Every command belongs to one or more of them, and users
+// are granted commands one by one or a category at a time (`+@read`).
+pub(crate) const READ: u32 = 1 << 0;
+pub(crate) const WRITE: u32 = 1 << 1;
+pub(crate) const KEYSPACE: u32 = 1 << 2;
+pub(crate) const STRING: u32 = 1 << 3;
+pub(crate) const ADMIN: u32 = 1 << 4;
+pub(crate) const DANGEROUS: u32 = 1 << 5;
+pub(crate) const CONNECTION: u32 = 1 << 6;
+pub(crate) const FAST: u32 = 1 << 7;
+pub(crate) const SLOW: u32 = 1 << 8;
+pub(crate) const SCRIPTING: u32 = 1 << 9;
+pub(crate) const PUBSUB: u32 = 1 << 10;
+pub(crate) const STREAM: u32 = 1 << 11;
+pub(crate) const BLOCKING: u32 = 1 << 12;
+pub(crate) const BITMAP: u32 = 1 << 13;
+pub(crate) const HYPERLOGLOG: u32 = 1 << 14;
+pub(crate) const GEO: u32 = 1 << 15;
+
+pub(crate) const CATEGORIES: &[(&str, u32)] = &[
+    ("read", READ),
+    ("write", WRITE),
+    ("keyspace", KEYSPACE),
+    ("string", STRING),
+    ("admin", ADMIN),
+    ("dangerous", DANGEROUS),
+    ("connection", CONNECTION),
+    ("fast", FAST),
+    ("slow", SLOW),
+    ("scripting", SCRIPTING),
+    ("pubsub", PUBSUB),
+    ("stream", STREAM),
+    ("blocking", BLOCKING),
+    ("bitmap", BITMAP),
+    ("hyperloglog", HYPERLOGLOG),
+    ("geo", GEO),
+];
+
+pub(crate) const DEFAULT_USER: &str = "default";
+
+// The users known to a server.
+pub(crate) struct Acl {
+    users: RwLock<BTreeMap<String, User>>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct User {
+    name: String,
+    enabled: bool,
+    nopass: bool,
+    // sha256 of the passwords, hex encoded.
+    passwords: BTreeSet<String>,
+    commands: BTreeSet<&'static str>,
+    // `+`/`-` rules applied since the last reset, to describe the user.
+    command_rules: Vec<String>,
+    key_patterns: Vec<String>,
+}
+
+impl Acl {
+    // The default user gets every permission, with `requirepass` as its
+    // password if set. `user` lines from the config come next.
+    pub(crate) fn new(config: &Config) -> Result<Acl> {
+        let mut default = User::new(DEFAULT_USER);
+        let pass = match &config.requirepass {
+            Some(pass) => format!(">{}", pass),
+            None => "nopass".to_string(),
+        };
+        for rule in ["on", &pass, "~*", "+@all"] {
+            default.apply(rule)?;
+        }
+
+        let acl = Acl {
+            users: RwLock::new(BTreeMap::new()),
+        };
+        acl.users
+            .write()
+            .unwrap()
+            .insert(DEFAULT_USER.to_string(), default);
+
+        for line in &config.users {
+            let mut parts = line.split_whitespace();
+            let name = parts.next().ok_or("empty user line")?;
+            acl.set_user(name, parts)?;
+        }
+
+        Ok(acl)
+    }
+
+    #[cfg(test)]
+    fn get(&self, name: &str) -> Option<User> {
+        self.users.read().unwrap().get(name).cloned()
+    }
+
+    // Check that `user` may run the command `spec` on `keys`.
+    pub(crate) fn check<'a>(
+        &self,
+        user: &str,
+        spec: &CommandSpec,
+        mut keys: impl Iterator<Item = &'a Bytes>,
+    ) -> std::result::Result<(), String> {
+        let users = self.users.read().unwrap();
+        let user = match users.get(user) {
+            Some(user) => user,
+            // the user was deleted since the connection authenticated.
+            None => return Err("NOAUTH Authentication required.".to_string()),
+        };
+
+        if !user.can_run(spec) {
+            return Err(format!(
+                "NOPERM User {} has no permissions to run the '{}' command",
+                user.name, spec.name
+            ));
+        }
+        if keys.any(|key| !user.can_access(key)) {
+            return Err("NOPERM No permissions to access a key".to_string());
+        }
+
+        Ok(())
+    }
+
+    // User a new connection is logged in as, if the default user needs no
+    // password.
+    pub(crate) fn auto_login(&self) -> Option<String> {
+        let users = self.users.read().unwrap();
+        let default = users.get(DEFAULT_USER)?;
+        (default.enabled && default.nopass).then(|| DEFAULT_USER.to_string())
+    }
+
+    pub(crate) fn authenticate(&self, name: &str, password: &str) -> bool {
+        let users = self.users.read().unwrap();
+        match users.get(name) {
+            Some(user) => user.enabled && (user.nopass || user.passwords.contains(&hash(password))),
+            None => false,
+        }
+    }
+
+    // Create or update a user. Rules are applied to a copy, so the user is
+    // left untouched if one of them is invalid.
+    pub(crate) fn set_user<'a>(
+        &self,
+        name: &str,
+        rules: impl Iterator<Item = &'a str>,
+    ) -> Result<()> {
+        let mut users = self.users.write().unwrap();
+
+        let mut user = users.get(name).cloned().unwrap_or_else(|| User::new(name));
+        for rule in rules {
+            user.apply(rule)?;
+        }
+        users.insert(name.to_string(), user);
+
+        Ok(())
+    }
+
+    // Returns whether the user existed.
+    pub(crate) fn del_user(&self, name: &str) -> Result<bool> {
+        if name == DEFAULT_USER {
+            return Err("ERR The 'default' user cannot be removed".into());
+        }
+        Ok(self.users.write().unwrap().remove(name).is_some())
+    }
+
+    pub(crate) fn users(&self) -> Vec<String> {
+        self.users.read().unwrap().keys().cloned().collect()
+    }
+
+    pub(crate) fn describe_all(&self) -> Vec<String> {
+        let users = self.users.read().unwrap();
+        users.values().map(User::describe).collect()
+    }
+}
+
+impl User {
+    fn new(name: &str) -> User {
+        User {
+            name: name.to_string(),
+            enabled: false,
+            nopass: false,
+            passwords: BTreeSet::new(),
+            commands: BTreeSet::new(),
+            command_rules: Vec::new(),
+            key_patterns: Vec::new(),
+        }
+    }
+
+    fn can_run(&self, spec: &CommandSpec) -> bool {
+        self.commands.contains(spec.name)
+    }
+
+    fn can_access(&self, key: &[u8]) -> bool {
+        self.key_patterns
+            .iter()
+            .any(|pattern| glob::matches(pattern.as_bytes(), key))
+    }
+
+    // Apply one rule of the redis ACL syntax, e.g. `on`, `>secret`,
+    // `~cache:*`, `+@read` or `-flushall`.
+    fn apply(&mut self, rule: &str) -> Result<()> {
+        match rule.to_ascii_lowercase().as_str() {
+            "on" => self.enabled = true,
+            "off" => self.enabled = false,
+            "nopass" => {
+                self.nopass = true;
+                self.passwords.clear();
+            }
+            "resetpass" => {
+                self.nopass = false;
+                self.passwords.clear();
+            }
+            "allkeys" => self.apply("~*")?,
+            "resetkeys" => self.key_patterns.clear(),
+            "allcommands" => self.apply("+@all")?,
+            "nocommands" => self.apply("-@all")?,
+            "reset" => {
+                *self = User::new(&self.name);
+                self.apply("resetpass")?;
+            }
+            _ => {
+                if let Some(pass) = rule.strip_prefix('>') {
+                    self.nopass = false;
+                    self.passwords.insert(hash(pass));
+                } else if let Some(pass) = rule.strip_prefix('<') {
+                    self.passwords.remove(&hash(pass));
+                } else if let Some(hashed) = rule.strip_prefix('#') {
+                    if hashed.len() != 64 || !hashed.bytes().all(|b| b.is_ascii_hexdigit()) {
+                        return Err("ERR The password hash must be exactly 64 characters and contain only lowercase hexadecimal characters".into());
+                    }
+                    self.nopass = false;
+                    self.passwords.insert(hashed.to_ascii_lowercase());
+                } else if let Some(pattern) = rule.strip_prefix('~') {
+                    self.key_patterns.push(pattern.to_string());
+                } else if let Some(command) = rule.strip_prefix('+') {
+                    self.apply_command_rule(command, true)?;
+                } else if let Some(command) = rule.strip_prefix('-') {
+                    self.apply_command_rule(command, false)?;
+                } else {
+                    return Err(format!(
+                        "ERR Error in ACL SETUSER modifier '{}': Syntax error",
+                        rule
+                    )
+                    .into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply_command_rule(&mut self, rule: &str, allow: bool) -> Result<()> {
+        let rule = rule.to_ascii_lowercase();
+
+        let specs: Vec<&CommandSpec> = if rule == "@all" {
+            cmd::table().iter().collect()
+        } else if let Some(category) = rule.strip_prefix('@') {
+            let bit = category_bit(category).ok_or_else(|| {
+                format!("ERR Error in ACL SETUSER modifier '{}': Unknown command or category name in ACL", rule)
+            })?;
+            cmd::table()
+                .iter()
+                .filter(|spec| spec.acl & bit != 0)
+                .collect()
+        } else {
+            let spec = cmd::lookup(&rule).ok_or_else(|| {
+                format!("ERR Error in ACL SETUSER modifier '{}': Unknown command or category name in ACL", rule)
+            })?;
+            vec![spec]
+        };
+
+        for spec in specs {
+            if allow {
+                self.commands.insert(spec.name);
+            } else {
+                self.commands.remove(spec.name);
+            }
+        }
+
+        // `+@all`/`-@all` override every earlier rule.
+        if rule == "@all" {
+            self.command_rules.clear();
+        }
+        self.command_rules
+            .push(format!("{}{}", if allow { '+' } else { '-' }, rule));
+
+        Ok(())
+    }
+
+    // The user in ACL LIST format.
+    fn describe(&self) -> String {
+        let mut out = format!(
+            "user {} {}",
+            self.name,
+            if self.enabled { "on" } else { "off" }
+        );
+        if self.nopass {
+            out.push_str(" nopass");
+        }
+        for hashed in &self.passwords {
+            out.push_str(" #");
+            out.push_str(hashed);
+        }
+        for pattern in &self.key_patterns {
+            out.push_str(" ~");
+            out.push_str(pattern);
+        }
+        if self.command_rules.is_empty() {
+            out.push_str(" -@all");
+        }
+        for rule in &self.command_rules {
+            out.push(' ');
+            out.push_str(rule);
+        }
+        out
+    }
+}
+
+pub(crate) fn category_bit(name: &str) -> Option<u32> {
+    CATEGORIES
+        .iter()
+        .find(|(category, _)| *category == name)
+        .map(|(_, bit)| *bit)
+}
+
+fn hash(password: &str) -> String {
+    Sha256::digest(password.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn acl_with(users: &[&str]) -> Acl {
+        let mut config = Config::default();
+        for user in users {
+            config.set("user", user).unwrap();
+        }
+        Acl::new(&config).unwrap()
+    }
+
+    #[test]
+    fn default_user_without_requirepass() {
+        let acl = acl_with(&[]);
+        assert_eq!(acl.auto_login().as_deref(), Some(DEFAULT_USER));
+
+        let default = acl.get(DEFAULT_USER).unwrap();
+        assert!(default.can_run(cmd::lookup("set").unwrap()));
+        assert!(default.can_access(b"any key"));
+    }
+
+    #[test]
+    fn requirepass_disables_auto_login() {
+        let mut config = Config::default();
+        config.set("requirepass", "secret").unwrap();
+        let acl = Acl::new(&config).unwrap();
+
+        assert!(acl.auto_login().is_none());
+        assert!(acl.authenticate(DEFAULT_USER, "secret"));
+        assert!(!acl.authenticate(DEFAULT_USER, "wrong"));
+    }
+
+    #[test]
+    fn categories_and_key_patterns() {
+        let acl = acl_with(&["reader on >pw ~cache:* +@read -info"]);
+        let reader = acl.get("reader").unwrap();
+
+        assert!(reader.can_run(cmd::lookup("get").unwrap()));
+        assert!(!reader.can_run(cmd::lookup("set").unwrap()));
+        assert!(!reader.can_run(cmd::lookup("info").unwrap()));
+        assert!(reader.can_access(b"cache:user:1"));
+        assert!(!reader.can_access(b"session:1"));
+        assert!(acl.authenticate("reader", "pw"));
+    }
+
+    #[test]
+    fn disabled_users_cannot_authenticate() {
+        let acl = acl_with(&["bob off >pw +@all"]);
+        assert!(!acl.authenticate("bob", "pw"));
+
+        acl.set_user("bob", ["on"].into_iter()).unwrap();
+        assert!(acl.authenticate("bob", "pw"));
+    }
+
+    #[test]
+    fn invalid_rules_leave_the_user_untouched() {
+        let acl = acl_with(&["bob on >pw"]);
+        assert!(acl.set_user("bob", ["off", "+nope"].into_iter()).is_err());
+        assert!(acl.authenticate("bob", "pw"));
+    }
+
+    #[test]
+    fn describe_user() {
+        let acl = acl_with(&["bob on nopass ~a:* +@all -set"]);
+        assert_eq!(
+            acl.get("bob").unwrap().describe(),
+            "user bob on nopass ~a:* +@all -set"
+        );
+    }
+}
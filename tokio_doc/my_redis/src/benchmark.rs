@@ -0,0 +1,321 @@
+use crate::Connection;
+
+use bytes::Bytes;
+use mini_redis::{Frame, Result};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+
+// A load test: `clients` connections sending `requests` commands in all,
+// `pipeline` at a time, picked from `mix` on random keys of the key space.
+#[derive(Clone, Debug)]
+pub struct Benchmark {
+    pub addr: String,
+    pub clients: usize,
+    pub requests: u64,
+    pub pipeline: usize,
+    // keys are "key:0" to "key:<keyspace - 1>".
+    pub keyspace: u64,
+    // bytes of the values SET writes.
+    pub value_size: usize,
+    // commands with their weights, e.g. 9 GETs for 1 SET.
+    pub mix: Vec<(Op, u32)>,
+    // makes the commands sent reproducible.
+    pub seed: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    Get,
+    Set,
+    Del,
+    Ping,
+}
+
+// The outcome of a run. Latencies are from sending a pipeline to reading
+// the reply of each of its commands.
+#[derive(Debug)]
+pub struct Report {
+    pub requests: u64,
+    pub errors: u64,
+    pub elapsed: Duration,
+    // sorted.
+    latencies: Vec<Duration>,
+}
+
+impl Default for Benchmark {
+    fn default() -> Benchmark {
+        Benchmark {
+            addr: format!("127.0.0.1:{}", crate::DEFAULT_PORT),
+            clients: 50,
+            requests: 100_000,
+            pipeline: 1,
+            keyspace: 10_000,
+            value_size: 3,
+            mix: vec![(Op::Set, 1), (Op::Get, 1)],
+            seed: None,
+        }
+    }
+}
+
+impl Benchmark {
+    // Build a benchmark from `--name value` pairs, like `Config::from_args`.
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Benchmark> {
+        let mut benchmark = Benchmark::default();
+        while let Some(arg) = args.next() {
+            let name = arg
+                .strip_prefix("--")
+                .ok_or_else(|| format!("unexpected argument '{}'", arg))?;
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for '{}'", arg))?;
+            benchmark.set(name, &value)?;
+        }
+        Ok(benchmark)
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        match name {
+            "addr" => self.addr = value.to_string(),
+            "clients" => self.clients = parse(name, value)?,
+            "requests" => self.requests = parse(name, value)?,
+            "pipeline" => self.pipeline = parse(name, value)?,
+            "keyspace" => self.keyspace = parse(name, value)?,
+            "value-size" => self.value_size = parse(name, value)?,
+            "mix" => self.mix = parse_mix(value)?,
+            "seed" => self.seed = Some(parse(name, value)?),
+            _ => return Err(format!("unknown option '{}'", name).into()),
+        }
+
+        if self.clients == 0 || self.pipeline == 0 || self.keyspace == 0 {
+            return Err(format!("'{}' must be at least 1", name).into());
+        }
+        Ok(())
+    }
+
+    pub async fn run(&self) -> Result<Report> {
+        // requests are claimed a pipeline at a time by the clients, until
+        // there are none left.
+        let claimed = Arc::new(AtomicU64::new(0));
+        let value = Bytes::from(vec![b'x'; self.value_size]);
+
+        let mut conns = Vec::with_capacity(self.clients);
+        for _ in 0..self.clients {
+            let socket = TcpStream::connect(&self.addr).await?;
+            // pipelined commands go out as soon as they're written.
+            socket.set_nodelay(true)?;
+            conns.push(Connection::new(socket));
+        }
+
+        let start = Instant::now();
+        let mut tasks = Vec::with_capacity(self.clients);
+        for (i, conn) in conns.into_iter().enumerate() {
+            let rng = match self.seed {
+                Some(seed) => StdRng::seed_from_u64(seed.wrapping_add(i as u64)),
+                None => StdRng::from_entropy(),
+            };
+            let client = BenchClient {
+                conn,
+                rng,
+                benchmark: self.clone(),
+                value: value.clone(),
+                claimed: claimed.clone(),
+            };
+            tasks.push(tokio::spawn(client.run()));
+        }
+
+        let mut latencies = Vec::with_capacity(self.requests as usize);
+        let mut errors = 0;
+        for task in tasks {
+            let (client_latencies, client_errors) = task.await??;
+            latencies.extend(client_latencies);
+            errors += client_errors;
+        }
+        let elapsed = start.elapsed();
+        latencies.sort();
+
+        Ok(Report {
+            requests: latencies.len() as u64,
+            errors,
+            elapsed,
+            latencies,
+        })
+    }
+}
+
+struct BenchClient {
+    conn: Connection,
+    rng: StdRng,
+    benchmark: Benchmark,
+    value: Bytes,
+    claimed: Arc<AtomicU64>,
+}
+
+impl BenchClient {
+    // Returns the latency of every request sent, and the number of error
+    // replies.
+    async fn run(mut self) -> Result<(Vec<Duration>, u64)> {
+        let total = self.benchmark.requests;
+        let pipeline = self.benchmark.pipeline as u64;
+        let mut latencies = Vec::new();
+        let mut errors = 0;
+
+        loop {
+            let first = self.claimed.fetch_add(pipeline, Ordering::Relaxed);
+            if first >= total {
+                break;
+            }
+            let count = pipeline.min(total - first);
+
+            let sent = Instant::now();
+            for _ in 0..count {
+                let frame = self.next_command();
+                self.conn.write_frame(&frame).await?;
+            }
+            for _ in 0..count {
+                match self.conn.read_frame().await? {
+                    Some(Frame::Error(_)) => errors += 1,
+                    Some(_) => {}
+                    None => return Err("connection closed by server".into()),
+                }
+                latencies.push(sent.elapsed());
+            }
+        }
+
+        Ok((latencies, errors))
+    }
+
+    fn next_command(&mut self) -> Frame {
+        let op = pick(&self.benchmark.mix, &mut self.rng);
+        let key = format!("key:{}", self.rng.gen_range(0..self.benchmark.keyspace));
+
+        let args = match op {
+            Op::Get => vec![Bytes::from_static(b"GET"), Bytes::from(key)],
+            Op::Set => vec![
+                Bytes::from_static(b"SET"),
+                Bytes::from(key),
+                self.value.clone(),
+            ],
+            Op::Del => vec![Bytes::from_static(b"DEL"), Bytes::from(key)],
+            Op::Ping => vec![Bytes::from_static(b"PING")],
+        };
+        Frame::Array(args.into_iter().map(Frame::Bulk).collect())
+    }
+}
+
+// A weighted random pick.
+fn pick(mix: &[(Op, u32)], rng: &mut impl Rng) -> Op {
+    let total: u32 = mix.iter().map(|(_, weight)| weight).sum();
+    let mut n = rng.gen_range(0..total);
+    for (op, weight) in mix {
+        if n < *weight {
+            return *op;
+        }
+        n -= weight;
+    }
+    unreachable!()
+}
+
+impl Report {
+    pub fn throughput(&self) -> f64 {
+        self.requests as f64 / self.elapsed.as_secs_f64()
+    }
+
+    // The latency `p` of the requests were faster than, `p` in [0, 1].
+    pub fn percentile(&self, p: f64) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        let rank = (p * self.latencies.len() as f64).ceil() as usize;
+        self.latencies[rank.clamp(1, self.latencies.len()) - 1]
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+
+        writeln!(
+            f,
+            "{} requests in {:.2}s, {} errors",
+            self.requests,
+            self.elapsed.as_secs_f64(),
+            self.errors
+        )?;
+        writeln!(f, "throughput: {:.0} requests/s", self.throughput())?;
+        write!(
+            f,
+            "latency (ms): p50 {:.3}, p99 {:.3}, p999 {:.3}, max {:.3}",
+            ms(self.percentile(0.5)),
+            ms(self.percentile(0.99)),
+            ms(self.percentile(0.999)),
+            ms(self.percentile(1.0))
+        )
+    }
+}
+
+fn parse<T: std::str::FromStr>(name: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for '{}'", value, name).into())
+}
+
+// "get:9,set:1", a weight of 1 if left out.
+fn parse_mix(value: &str) -> Result<Vec<(Op, u32)>> {
+    let mut mix = Vec::new();
+    for part in value.split(',') {
+        let (name, weight) = part.split_once(':').unwrap_or((part, "1"));
+        let op = match name.to_ascii_lowercase().as_str() {
+            "get" => Op::Get,
+            "set" => Op::Set,
+            "del" => Op::Del,
+            "ping" => Op::Ping,
+            _ => return Err(format!("unknown command '{}' in mix", name).into()),
+        };
+        mix.push((op, parse("mix", weight)?));
+    }
+
+    if mix.iter().all(|(_, weight)| *weight == 0) {
+        return Err("the mix needs a command with a weight".into());
+    }
+    Ok(mix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mix_format() {
+        assert_eq!(
+            parse_mix("get:9,set").unwrap(),
+            [(Op::Get, 9), (Op::Set, 1)]
+        );
+        assert!(parse_mix("get:0").is_err());
+        assert!(parse_mix("hset:1").is_err());
+        assert!(parse_mix("get:x").is_err());
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let mix = [(Op::Get, 0), (Op::Ping, 3)];
+        assert!((0..100).all(|_| pick(&mix, &mut rng) == Op::Ping));
+    }
+
+    #[test]
+    fn percentiles() {
+        let report = Report {
+            requests: 1000,
+            errors: 0,
+            elapsed: Duration::from_secs(2),
+            latencies: (1..=1000).map(Duration::from_micros).collect(),
+        };
+        assert_eq!(report.throughput(), 500.0);
+        assert_eq!(report.percentile(0.5), Duration::from_micros(500));
+        assert_eq!(report.percentile(0.99), Duration::from_micros(990));
+        assert_eq!(report.percentile(0.999), Duration::from_micros(999));
+        assert_eq!(report.percentile(0.0), Duration::from_micros(1));
+    }
+}
@@ -1,12 +1,32 @@
-use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+use my_redis::faults::Faults;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpListener;
 
+// e.g. echo_server --listen 127.0.0.1:6142 --seed 7 --corrupt 0.01
+// with the options of `Faults` to test how clients cope with a bad server.
 #[tokio::main]
-async fn main() -> io::Result<()> {
-    let listener = TcpListener::bind("127.0.0.1::6142").await?;
+async fn main() -> mini_redis::Result<()> {
+    let mut listen = "127.0.0.1:6142".to_string();
+    let mut faults = Faults::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let name = arg
+            .strip_prefix("--")
+            .ok_or_else(|| format!("unexpected argument '{}'", arg))?;
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for '{}'", arg))?;
+        match name {
+            "listen" => listen = value,
+            _ => faults.set(name, &value)?,
+        }
+    }
+
+    let listener = TcpListener::bind(&listen).await?;
 
-    loop {
-        let (mut socket, _) = listener.accept().await?;
+    for n in 0.. {
+        let (socket, _) = listener.accept().await?;
+        let mut socket = faults.wrap(socket, n);
 
         tokio::spawn(async move {
             let mut buf = vec![0; 1024];
@@ -24,4 +44,6 @@ async fn main() -> io::Result<()> {
             }
         });
     }
+
+    Ok(())
 }
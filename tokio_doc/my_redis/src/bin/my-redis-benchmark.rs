@@ -0,0 +1,21 @@
+use my_redis::benchmark::Benchmark;
+
+// e.g. my-redis-benchmark --clients 50 --pipeline 16 --mix get:9,set:1
+#[tokio::main]
+async fn main() -> mini_redis::Result<()> {
+    let benchmark = Benchmark::from_args(std::env::args().skip(1))?;
+
+    println!(
+        "{} clients, {} requests, pipeline {}, {} keys, {} byte values against {}",
+        benchmark.clients,
+        benchmark.requests,
+        benchmark.pipeline,
+        benchmark.keyspace,
+        benchmark.value_size,
+        benchmark.addr
+    );
+    let report = benchmark.run().await?;
+    println!("{}", report);
+
+    Ok(())
+}
@@ -0,0 +1,15 @@
+use my_redis::logging::{self, LogFormat};
+use my_redis::proxy::Proxy;
+
+// e.g. tcp-proxy --listen 127.0.0.1:6380 --upstream 127.0.0.1:6379
+// with the options of `Faults`, e.g. --delay-ms 20 --reset 0.01 --seed 7
+#[tokio::main]
+async fn main() -> mini_redis::Result<()> {
+    logging::init(LogFormat::Text)?;
+    let proxy = Proxy::from_args(std::env::args().skip(1))?;
+
+    let running = proxy.start().await?;
+    tokio::signal::ctrl_c().await?;
+    running.shutdown().await;
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use bytes::BytesMut;
+
+// Bit 0 of a string is the most significant bit of its first byte, as in
+// redis.
+
+// Highest offset SETBIT takes, strings being limited to 512MB.
+pub(crate) const MAX_OFFSET: u64 = (512 << 23) - 1;
+
+pub(crate) fn get_bit(bytes: &[u8], offset: u64) -> bool {
+    let byte = (offset / 8) as usize;
+    bytes
+        .get(byte)
+        .is_some_and(|b| b & (0x80 >> (offset % 8)) != 0)
+}
+
+// Set the bit at `offset`, growing the string with zeros to reach it.
+// Returns the previous bit.
+pub(crate) fn set_bit(bytes: &mut BytesMut, offset: u64, on: bool) -> bool {
+    let byte = (offset / 8) as usize;
+    if byte >= bytes.len() {
+        bytes.resize(byte + 1, 0);
+    }
+
+    let mask = 0x80 >> (offset % 8);
+    let previous = bytes[byte] & mask != 0;
+    if on {
+        bytes[byte] |= mask;
+    } else {
+        bytes[byte] &= !mask;
+    }
+    previous
+}
+
+pub(crate) fn count(bytes: &[u8]) -> u64 {
+    bytes.iter().map(|b| b.count_ones() as u64).sum()
+}
+
+// Set bits from bit `start` to bit `end` included, both within `bytes`.
+pub(crate) fn count_bits(bytes: &[u8], start: u64, end: u64) -> u64 {
+    let (first, last) = ((start / 8) as usize, (end / 8) as usize);
+    let mut n = count(&bytes[first..=last]);
+    // the bits of the first and last bytes out of the range.
+    n -= (bytes[first] & !(0xff >> (start % 8))).count_ones() as u64;
+    n -= (bytes[last] & 0xffu8.checked_shr(end as u32 % 8 + 1).unwrap_or(0)).count_ones() as u64;
+    n
+}
+
+// `start` and `end` of a BITCOUNT range for a string of `len` units (bytes
+// or bits), negative ones counting from the end. `None` for an empty range.
+pub(crate) fn range(len: u64, start: i64, end: i64) -> Option<(u64, u64)> {
+    let len = len as i64;
+    let resolve = |i: i64| if i < 0 { (len + i).max(0) } else { i };
+    let (start, end) = (resolve(start), resolve(end).min(len - 1));
+    (start <= end).then_some((start as u64, end as u64))
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum BitOp {
+    And,
+    Or,
+    Xor,
+    Not,
+}
+
+// The result of BITOP over `sources`, the shorter ones padded with zeros.
+// NOT takes a single source.
+pub(crate) fn bitop(op: BitOp, sources: &[&[u8]]) -> Vec<u8> {
+    let len = sources.iter().map(|s| s.len()).max().unwrap_or(0);
+    let byte = |source: &[u8], i: usize| source.get(i).copied().unwrap_or(0);
+
+    (0..len)
+        .map(|i| {
+            let mut bytes = sources.iter().map(|source| byte(source, i));
+            let first = bytes.next().unwrap_or(0);
+            match op {
+                BitOp::And => bytes.fold(first, |acc, b| acc & b),
+                BitOp::Or => bytes.fold(first, |acc, b| acc | b),
+                BitOp::Xor => bytes.fold(first, |acc, b| acc ^ b),
+                BitOp::Not => !first,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bits_and_counts() {
+        let mut bytes = BytesMut::new();
+        assert!(!set_bit(&mut bytes, 7, true));
+        assert!(set_bit(&mut bytes, 7, true));
+        set_bit(&mut bytes, 18, true);
+        assert_eq!(&bytes[..], &[0x01, 0x00, 0x20]);
+        assert!(get_bit(&bytes, 18));
+        assert!(!get_bit(&bytes, 17));
+        assert!(!get_bit(&bytes, 1000));
+
+        let bytes = [0xff, 0xf0, 0x0f];
+        for start in 0..24 {
+            for end in start..24 {
+                let exact = (start..=end).filter(|&i| get_bit(&bytes, i)).count();
+                assert_eq!(count_bits(&bytes, start, end), exact as u64);
+            }
+        }
+
+        assert_eq!(range(3, 0, -1), Some((0, 2)));
+        assert_eq!(range(3, -2, 100), Some((1, 2)));
+        assert_eq!(range(3, -100, -3), Some((0, 0)));
+        assert_eq!(range(3, 2, 1), None);
+        assert_eq!(range(0, 0, -1), None);
+    }
+
+    #[test]
+    fn bit_operations_pad_shorter_sources() {
+        let (a, b): (&[u8], &[u8]) = (&[0b1100, 0xff], &[0b1010]);
+        assert_eq!(bitop(BitOp::And, &[a, b]), [0b1000, 0]);
+        assert_eq!(bitop(BitOp::Or, &[a, b]), [0b1110, 0xff]);
+        assert_eq!(bitop(BitOp::Xor, &[a, b]), [0b0110, 0xff]);
+        assert_eq!(bitop(BitOp::Not, &[b]), [!0b1010u8]);
+    }
+}
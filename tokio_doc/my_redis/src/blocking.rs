@@ -0,0 +1,59 @@
+use crate::cmd::Command;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+use tokio::sync::Notify;
+
+// Wakes up the clients blocked in commands like XREAD BLOCK when data is
+// added. Every write bumps a version, so that a client can tell whether
+// something changed since it looked.
+#[derive(Default)]
+pub(crate) struct Waker {
+    version: AtomicU64,
+    notify: Notify,
+}
+
+// Set on the session by a command that found nothing to reply with yet.
+// The connection waits for a write, then runs `retry` in its place.
+#[derive(Clone)]
+pub(crate) struct Blocked {
+    pub(crate) retry: Command,
+    // version of the waker before the command looked for data.
+    pub(crate) version: u64,
+    // `None` to block forever.
+    pub(crate) deadline: Option<Instant>,
+}
+
+impl Waker {
+    pub(crate) fn version(&self) -> u64 {
+        self.version.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn wake(&self) {
+        self.version.fetch_add(1, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    // Wait for a write after `version`. Returns false if `deadline` came
+    // first.
+    pub(crate) async fn wait(&self, version: u64, deadline: Option<Instant>) -> bool {
+        let notified = self.notify.notified();
+        tokio::pin!(notified);
+        // register before checking the version, so that a wake up in
+        // between is not lost.
+        notified.as_mut().enable();
+        if self.version() != version {
+            return true;
+        }
+
+        match deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline.into(), notified)
+                .await
+                .is_ok(),
+            None => {
+                notified.await;
+                true
+            }
+        }
+    }
+}
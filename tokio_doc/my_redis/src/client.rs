@@ -0,0 +1,169 @@
+use crate::connection::{Connection, Stream};
+
+use bytes::Bytes;
+use mini_redis::{Frame, Result};
+use rustls::pki_types::ServerName;
+use rustls::ClientConfig;
+use std::sync::Arc;
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio_rustls::TlsConnector;
+
+// Redirects followed for one command before giving up, e.g. while slots
+// move back and forth.
+const MAX_REDIRECTS: usize = 5;
+
+// A connection to a my_redis server, over plain TCP or TLS.
+//
+// In cluster mode, the client follows the MOVED and ASK redirects of the
+// nodes: a MOVED one moves the client over to the node named, an ASK one
+// sends that single command there.
+pub struct Client {
+    conn: Connection<Box<dyn Stream>>,
+    // how to connect to the nodes the client is redirected to.
+    tls: Option<(String, Arc<ClientConfig>)>,
+}
+
+impl Client {
+    pub async fn connect(addr: impl ToSocketAddrs) -> Result<Client> {
+        let socket = TcpStream::connect(addr).await?;
+
+        Ok(Client::from_stream(Box::new(socket)))
+    }
+
+    // Connect over TLS. `domain` is the name the server certificate is
+    // checked against.
+    pub async fn connect_tls(
+        addr: impl ToSocketAddrs,
+        domain: &str,
+        config: Arc<ClientConfig>,
+    ) -> Result<Client> {
+        let tls = (domain.to_string(), config);
+        let conn = open(addr, Some(&tls)).await?;
+
+        Ok(Client {
+            conn,
+            tls: Some(tls),
+        })
+    }
+
+    #[cfg(unix)]
+    pub async fn connect_unix(path: impl AsRef<std::path::Path>) -> Result<Client> {
+        let socket = tokio::net::UnixStream::connect(path).await?;
+
+        Ok(Client::from_stream(Box::new(socket)))
+    }
+
+    pub(crate) fn from_stream(stream: Box<dyn Stream>) -> Client {
+        Client {
+            conn: Connection::new(stream),
+            tls: None,
+        }
+    }
+
+    pub async fn get(&mut self, key: &str) -> Result<Option<Bytes>> {
+        match self.command(&[b"GET", key.as_bytes()]).await? {
+            Frame::Bulk(value) => Ok(Some(value)),
+            Frame::Null => Ok(None),
+            frame => Err(unexpected(frame)),
+        }
+    }
+
+    pub async fn set(&mut self, key: &str, value: Bytes) -> Result<()> {
+        let frame = Frame::Array(vec![
+            Frame::Bulk(Bytes::from_static(b"SET")),
+            Frame::Bulk(Bytes::copy_from_slice(key.as_bytes())),
+            Frame::Bulk(value),
+        ]);
+
+        match self.request(frame).await? {
+            Frame::Simple(_) => Ok(()),
+            frame => Err(unexpected(frame)),
+        }
+    }
+
+    // Send any command. Error replies are turned into `Err`.
+    pub async fn command(&mut self, args: &[&[u8]]) -> Result<Frame> {
+        let frame = Frame::Array(
+            args.iter()
+                .map(|arg| Frame::Bulk(Bytes::copy_from_slice(arg)))
+                .collect(),
+        );
+
+        self.request(frame).await
+    }
+
+    async fn request(&mut self, frame: Frame) -> Result<Frame> {
+        for _ in 0..MAX_REDIRECTS {
+            let reply = roundtrip(&mut self.conn, &frame).await?;
+            let msg = match &reply {
+                Frame::Error(msg) => msg,
+                _ => return Ok(reply),
+            };
+
+            match redirect(msg) {
+                Some(("MOVED", addr)) => {
+                    self.conn = open(addr, self.tls.as_ref()).await?;
+                }
+                Some(("ASK", addr)) => {
+                    let mut conn = open(addr, self.tls.as_ref()).await?;
+                    let asking = Frame::Array(vec![Frame::Bulk(Bytes::from_static(b"ASKING"))]);
+                    roundtrip(&mut conn, &asking).await?;
+                    return reply_or_error(roundtrip(&mut conn, &frame).await?);
+                }
+                _ => return reply_or_error(reply),
+            }
+        }
+
+        Err("too many cluster redirects".into())
+    }
+}
+
+async fn open(
+    addr: impl ToSocketAddrs,
+    tls: Option<&(String, Arc<ClientConfig>)>,
+) -> Result<Connection<Box<dyn Stream>>> {
+    let socket = TcpStream::connect(addr).await?;
+    let stream: Box<dyn Stream> = match tls {
+        Some((domain, config)) => {
+            let domain = ServerName::try_from(domain.clone())?;
+            Box::new(
+                TlsConnector::from(config.clone())
+                    .connect(domain, socket)
+                    .await?,
+            )
+        }
+        None => Box::new(socket),
+    };
+    Ok(Connection::new(stream))
+}
+
+async fn roundtrip(conn: &mut Connection<Box<dyn Stream>>, frame: &Frame) -> Result<Frame> {
+    conn.write_frame(frame).await?;
+
+    match conn.read_frame().await? {
+        Some(frame) => Ok(frame),
+        None => Err("connection closed by server".into()),
+    }
+}
+
+// Error replies are turned into `Err`.
+fn reply_or_error(frame: Frame) -> Result<Frame> {
+    match frame {
+        Frame::Error(msg) => Err(msg.into()),
+        frame => Ok(frame),
+    }
+}
+
+// The kind and node address of a "MOVED slot addr" or "ASK slot addr"
+// error.
+fn redirect(msg: &str) -> Option<(&str, &str)> {
+    let mut parts = msg.split(' ');
+    match (parts.next()?, parts.next()?, parts.next()?) {
+        (kind @ ("MOVED" | "ASK"), _slot, addr) => Some((kind, addr)),
+        _ => None,
+    }
+}
+
+fn unexpected(frame: Frame) -> mini_redis::Error {
+    format!("unexpected frame: {:?}", frame).into()
+}
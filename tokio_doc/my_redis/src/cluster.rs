@@ -0,0 +1,395 @@
+use bytes::Bytes;
+use mini_redis::Result;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::RwLock;
+
+pub(crate) const SLOTS: usize = 16384;
+
+// The slot of a key: CRC16 of the key, or of its hash tag, the part
+// between the first `{` and the next `}` if not empty. Keys sharing a tag,
+// like `{user:1}:name` and `{user:1}:mail`, land on the same node.
+pub(crate) fn key_slot(key: &[u8]) -> u16 {
+    let hashed = match key.iter().position(|&b| b == b'{') {
+        Some(open) => match key[open + 1..].iter().position(|&b| b == b'}') {
+            Some(0) | None => key,
+            Some(len) => &key[open + 1..open + 1 + len],
+        },
+        None => key,
+    };
+    crc16(hashed) % SLOTS as u16
+}
+
+// CRC16-CCITT (XMODEM), as used by redis cluster.
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+// What a node knows of the cluster. The nodes and their slots come from
+// the `cluster-node` config lines, every node of a cluster is given the
+// same ones. Slots then move with CLUSTER SETSLOT.
+pub(crate) struct Cluster {
+    myself: String,
+    state: RwLock<State>,
+}
+
+struct State {
+    // node id -> address, "host:port".
+    nodes: BTreeMap<String, String>,
+    // the owner of each slot, `None` while unassigned.
+    slots: Vec<Option<String>>,
+    // slots being moved away to, or in from, another node.
+    migrating: HashMap<u16, String>,
+    importing: HashMap<u16, String>,
+}
+
+// Why a command can't run on this node.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Redirect {
+    // the slot is served by another node, for good.
+    Moved(u16, String),
+    // the slot is being migrated and the keys aren't here anymore: ask the
+    // node importing them, for this command only.
+    Ask(u16, String),
+    CrossSlot,
+    Down(u16),
+}
+
+impl std::fmt::Display for Redirect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Redirect::Moved(slot, addr) => write!(f, "MOVED {} {}", slot, addr),
+            Redirect::Ask(slot, addr) => write!(f, "ASK {} {}", slot, addr),
+            Redirect::CrossSlot => {
+                f.write_str("CROSSSLOT Keys in request don't hash to the same slot")
+            }
+            Redirect::Down(slot) => write!(f, "CLUSTERDOWN Hash slot {} not served", slot),
+        }
+    }
+}
+
+impl Cluster {
+    // `nodes` are `id host:port [slot|start-end ...]` lines, `myself` the
+    // id of this node among them.
+    pub(crate) fn new(myself: &str, nodes: &[String]) -> Result<Cluster> {
+        let mut state = State {
+            nodes: BTreeMap::new(),
+            slots: vec![None; SLOTS],
+            migrating: HashMap::new(),
+            importing: HashMap::new(),
+        };
+
+        for line in nodes {
+            let mut parts = line.split_whitespace();
+            let (id, addr) = match (parts.next(), parts.next()) {
+                (Some(id), Some(addr)) => (id.to_string(), addr.to_string()),
+                _ => return Err(format!("invalid cluster node '{}'", line).into()),
+            };
+            for range in parts {
+                let (start, end) = parse_range(range)?;
+                for slot in &mut state.slots[start as usize..=end as usize] {
+                    if let Some(owner) = slot {
+                        return Err(format!(
+                            "slot {} assigned to both {} and {}",
+                            start, owner, id
+                        )
+                        .into());
+                    }
+                    *slot = Some(id.clone());
+                }
+            }
+            state.nodes.insert(id, addr);
+        }
+
+        if !state.nodes.contains_key(myself) {
+            return Err(format!("cluster-node-id '{}' is not a cluster node", myself).into());
+        }
+
+        Ok(Cluster {
+            myself: myself.to_string(),
+            state: RwLock::new(state),
+        })
+    }
+
+    pub(crate) fn myself(&self) -> &str {
+        &self.myself
+    }
+
+    // Check that the keys of a command can be served here. `exists` tells
+    // whether a key is in the local db, `asking` whether the client sent
+    // ASKING just before.
+    pub(crate) fn route<'a>(
+        &self,
+        keys: impl Iterator<Item = &'a Bytes>,
+        exists: impl Fn(&Bytes) -> bool,
+        asking: bool,
+    ) -> std::result::Result<(), Redirect> {
+        let mut slot = None;
+        let mut missing = false;
+        for key in keys {
+            let key_slot = key_slot(key);
+            if slot.is_some_and(|slot| slot != key_slot) {
+                return Err(Redirect::CrossSlot);
+            }
+            slot = Some(key_slot);
+            missing |= !exists(key);
+        }
+        let slot = match slot {
+            Some(slot) => slot,
+            // commands without keys run anywhere.
+            None => return Ok(()),
+        };
+
+        let state = self.state.read().unwrap();
+        match &state.slots[slot as usize] {
+            Some(owner) if *owner == self.myself => match state.migrating.get(&slot) {
+                Some(target) if missing => Err(Redirect::Ask(slot, state.nodes[target].clone())),
+                _ => Ok(()),
+            },
+            _ if asking && state.importing.contains_key(&slot) => Ok(()),
+            Some(owner) => Err(Redirect::Moved(slot, state.nodes[owner].clone())),
+            None => Err(Redirect::Down(slot)),
+        }
+    }
+
+    // CLUSTER SETSLOT slot MIGRATING|IMPORTING|NODE node-id, or STABLE.
+    pub(crate) fn set_slot(&self, slot: u16, action: &str, node: Option<&str>) -> Result<()> {
+        let mut state = self.state.write().unwrap();
+        let node = match node {
+            Some(node) if !state.nodes.contains_key(node) => {
+                return Err(format!("ERR I don't know about node {}", node).into())
+            }
+            node => node.map(str::to_string),
+        };
+        let owner = state.slots[slot as usize].clone();
+        let mine = owner.as_deref() == Some(self.myself.as_str());
+
+        match (action, node) {
+            ("migrating", Some(node)) => {
+                if !mine {
+                    return Err(format!("ERR I'm not the owner of hash slot {}", slot).into());
+                }
+                state.migrating.insert(slot, node);
+            }
+            ("importing", Some(node)) => {
+                if mine {
+                    return Err(format!("ERR I'm already the owner of hash slot {}", slot).into());
+                }
+                state.importing.insert(slot, node);
+            }
+            ("node", Some(node)) => {
+                state.migrating.remove(&slot);
+                state.importing.remove(&slot);
+                state.slots[slot as usize] = Some(node);
+            }
+            ("stable", None) => {
+                state.migrating.remove(&slot);
+                state.importing.remove(&slot);
+            }
+            _ => return Err("ERR Invalid CLUSTER SETSLOT action or number of arguments".into()),
+        }
+        Ok(())
+    }
+
+    // Contiguous runs of slots with the same owner: (start, end, node id).
+    pub(crate) fn ranges(&self) -> Vec<(u16, u16, String)> {
+        let state = self.state.read().unwrap();
+        let mut ranges: Vec<(u16, u16, String)> = Vec::new();
+        for (slot, owner) in state.slots.iter().enumerate() {
+            let owner = match owner {
+                Some(owner) => owner,
+                None => continue,
+            };
+            match ranges.last_mut() {
+                Some((_, end, id)) if *end as usize + 1 == slot && id == owner => {
+                    *end = slot as u16
+                }
+                _ => ranges.push((slot as u16, slot as u16, owner.clone())),
+            }
+        }
+        ranges
+    }
+
+    pub(crate) fn address(&self, id: &str) -> Option<String> {
+        self.state.read().unwrap().nodes.get(id).cloned()
+    }
+
+    // CLUSTER NODES, one line per node.
+    pub(crate) fn describe(&self) -> String {
+        let ranges = self.ranges();
+        let state = self.state.read().unwrap();
+
+        let mut out = String::new();
+        for (id, addr) in &state.nodes {
+            let flags = if *id == self.myself {
+                "myself,master"
+            } else {
+                "master"
+            };
+            let port: u32 = addr
+                .rsplit(':')
+                .next()
+                .and_then(|p| p.parse().ok())
+                .unwrap_or(0);
+            let _ = write!(
+                out,
+                "{} {}@{} {} - 0 0 0 connected",
+                id,
+                addr,
+                port + 10000,
+                flags
+            );
+            for (start, end, _) in ranges.iter().filter(|(_, _, owner)| owner == id) {
+                if start == end {
+                    let _ = write!(out, " {}", start);
+                } else {
+                    let _ = write!(out, " {}-{}", start, end);
+                }
+            }
+            if *id == self.myself {
+                let mut moving: Vec<_> = state
+                    .migrating
+                    .iter()
+                    .map(|(slot, to)| format!(" [{}->-{}]", slot, to))
+                    .collect();
+                moving.extend(
+                    state
+                        .importing
+                        .iter()
+                        .map(|(slot, from)| format!(" [{}-<-{}]", slot, from)),
+                );
+                moving.sort();
+                out.extend(moving);
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    // Number of slots with an owner, for CLUSTER INFO.
+    pub(crate) fn assigned_slots(&self) -> usize {
+        let state = self.state.read().unwrap();
+        state.slots.iter().filter(|owner| owner.is_some()).count()
+    }
+
+    pub(crate) fn known_nodes(&self) -> usize {
+        self.state.read().unwrap().nodes.len()
+    }
+}
+
+fn parse_range(range: &str) -> Result<(u16, u16)> {
+    let (start, end) = range.split_once('-').unwrap_or((range, range));
+    let parse = |slot: &str| match slot.parse::<u16>() {
+        Ok(slot) if (slot as usize) < SLOTS => Ok(slot),
+        _ => Err(format!("invalid slot range '{}'", range)),
+    };
+    let (start, end) = (parse(start)?, parse(end)?);
+    if start > end {
+        return Err(format!("invalid slot range '{}'", range).into());
+    }
+    Ok((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cluster(myself: &str) -> Cluster {
+        let nodes = [
+            "a 127.0.0.1:7000 0-8191".to_string(),
+            "b 127.0.0.1:7001 8192-16383".to_string(),
+        ];
+        Cluster::new(myself, &nodes).unwrap()
+    }
+
+    #[test]
+    fn key_slots() {
+        // values from the redis cluster specification and CLUSTER KEYSLOT.
+        assert_eq!(crc16(b"123456789"), 0x31c3);
+        assert_eq!(key_slot(b"foo"), 12182);
+        assert_eq!(key_slot(b"bar"), 5061);
+        assert_eq!(key_slot(b"{user1000}.following"), key_slot(b"user1000"));
+        assert_eq!(key_slot(b"{user1000}.followers"), key_slot(b"user1000"));
+        // an empty tag hashes the whole key.
+        assert_eq!(key_slot(b"foo{}{bar}"), crc16(b"foo{}{bar}") % 16384);
+        assert_eq!(key_slot(b"foo{{bar}}zap"), key_slot(b"{bar"));
+    }
+
+    #[test]
+    fn routing() {
+        let a = cluster("a");
+        let keys = |keys: &[&'static str]| {
+            keys.iter()
+                .map(|k| Bytes::from_static(k.as_bytes()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(a.route(keys(&["bar"]).iter(), |_| true, false), Ok(()));
+        assert_eq!(
+            a.route(keys(&["foo"]).iter(), |_| true, false),
+            Err(Redirect::Moved(12182, "127.0.0.1:7001".to_string()))
+        );
+        assert_eq!(
+            a.route(keys(&["bar", "foo"]).iter(), |_| true, false),
+            Err(Redirect::CrossSlot)
+        );
+        assert_eq!(
+            a.route(keys(&["{bar}1", "{bar}2"]).iter(), |_| true, false),
+            Ok(())
+        );
+
+        // bar's slot moving to b: keys still here are served, others asked
+        // for on b.
+        a.set_slot(5061, "migrating", Some("b")).unwrap();
+        assert_eq!(a.route(keys(&["bar"]).iter(), |_| true, false), Ok(()));
+        assert_eq!(
+            a.route(keys(&["bar"]).iter(), |_| false, false),
+            Err(Redirect::Ask(5061, "127.0.0.1:7001".to_string()))
+        );
+
+        let b = cluster("b");
+        b.set_slot(5061, "importing", Some("a")).unwrap();
+        assert!(matches!(
+            b.route(keys(&["bar"]).iter(), |_| false, false),
+            Err(Redirect::Moved(..))
+        ));
+        assert_eq!(b.route(keys(&["bar"]).iter(), |_| false, true), Ok(()));
+
+        b.set_slot(5061, "node", Some("b")).unwrap();
+        assert_eq!(b.route(keys(&["bar"]).iter(), |_| false, false), Ok(()));
+    }
+
+    #[test]
+    fn slot_ranges() {
+        let a = cluster("a");
+        a.set_slot(100, "node", Some("b")).unwrap();
+        assert_eq!(
+            a.ranges(),
+            [
+                (0, 99, "a".to_string()),
+                (100, 100, "b".to_string()),
+                (101, 8191, "a".to_string()),
+                (8192, 16383, "b".to_string()),
+            ]
+        );
+
+        let nodes = a.describe();
+        assert!(nodes
+            .contains("a 127.0.0.1:7000@17000 myself,master - 0 0 0 connected 0-99 101-8191\n"));
+        assert!(nodes.contains("b 127.0.0.1:7001@17001 master - 0 0 0 connected 100 8192-16383\n"));
+
+        assert!(Cluster::new("c", &["a 127.0.0.1:7000 0-10".to_string()]).is_err());
+        assert!(Cluster::new("a", &["a 127.0.0.1:7000 10-0".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,65 @@
+use super::Context;
+use crate::acl::{self, CATEGORIES};
+use crate::parse::Parse;
+
+use bytes::Bytes;
+use mini_redis::{Frame, Result};
+
+// ACL WHOAMI | USERS | LIST | CAT [category] | SETUSER user [rule ...] |
+// DELUSER user [user ...]
+pub(super) fn acl(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let acl = &ctx.shared.acl;
+
+    match parse.next_option()?.as_str() {
+        "whoami" => match &ctx.session.user {
+            Some(user) => Ok(Frame::Bulk(Bytes::from(user.clone()))),
+            None => Ok(Frame::Null),
+        },
+        "users" => Ok(bulk_array(acl.users())),
+        "list" => Ok(bulk_array(acl.describe_all())),
+        "cat" => {
+            if parse.remaining() == 0 {
+                let names = CATEGORIES.iter().map(|(name, _)| name.to_string());
+                return Ok(bulk_array(names.collect()));
+            }
+
+            let category = parse.next_option()?;
+            let bit = acl::category_bit(&category)
+                .ok_or_else(|| format!("ERR Unknown category '{}'", category))?;
+            let names = super::table()
+                .iter()
+                .filter(|spec| spec.acl & bit != 0)
+                .map(|spec| spec.name.to_string());
+            Ok(bulk_array(names.collect()))
+        }
+        "setuser" => {
+            let name = parse.next_string()?;
+            let mut rules = Vec::new();
+            while parse.remaining() > 0 {
+                rules.push(parse.next_string()?);
+            }
+
+            acl.set_user(&name, rules.iter().map(|rule| rule.as_str()))?;
+            Ok(Frame::Simple("OK".to_string()))
+        }
+        "deluser" => {
+            let mut deleted = 0;
+            while parse.remaining() > 0 {
+                if acl.del_user(&parse.next_string()?)? {
+                    deleted += 1;
+                }
+            }
+            Ok(Frame::Integer(deleted))
+        }
+        sub => Err(format!("ERR unknown subcommand '{}'", sub).into()),
+    }
+}
+
+fn bulk_array(values: Vec<String>) -> Frame {
+    Frame::Array(
+        values
+            .into_iter()
+            .map(|value| Frame::Bulk(Bytes::from(value)))
+            .collect(),
+    )
+}
@@ -0,0 +1,118 @@
+use super::Context;
+use crate::bitmap::{self, BitOp};
+use crate::parse::Parse;
+use crate::pubsub::KeyspaceEvents;
+
+use bytes::Bytes;
+use mini_redis::{Frame, Result};
+
+// SETBIT key offset value
+pub(super) fn setbit(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let key = parse.next_string()?;
+    let offset = match parse.next_string()?.parse::<u64>() {
+        Ok(offset) if offset <= bitmap::MAX_OFFSET => offset,
+        _ => return Err("ERR bit offset is not an integer or out of range".into()),
+    };
+    let on = match &parse.next_bytes()?[..] {
+        b"0" => false,
+        b"1" => true,
+        _ => return Err("ERR bit is not an integer or out of range".into()),
+    };
+
+    let previous = ctx
+        .db()
+        .string_mut(&key, true, |bytes| bitmap::set_bit(bytes, offset, on))?
+        .unwrap_or_default();
+
+    ctx.notify(KeyspaceEvents::STRING, "setbit", &key);
+    Ok(Frame::Integer(previous as u64))
+}
+
+// GETBIT key offset
+pub(super) fn getbit(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let key = parse.next_string()?;
+    let offset = parse
+        .next_string()?
+        .parse::<u64>()
+        .map_err(|_| "ERR bit offset is not an integer or out of range")?;
+
+    let value = ctx.db().get(&key)?.unwrap_or_default();
+    Ok(Frame::Integer(bitmap::get_bit(&value, offset) as u64))
+}
+
+// BITCOUNT key [start end [BYTE | BIT]]
+pub(super) fn bitcount(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let key = parse.next_string()?;
+    let range = match parse.remaining() {
+        0 => None,
+        2 | 3 => {
+            let (start, end) = (parse_index(parse)?, parse_index(parse)?);
+            let bits = match parse.remaining() {
+                0 => false,
+                _ => match parse.next_option()?.as_str() {
+                    "byte" => false,
+                    "bit" => true,
+                    _ => return Err("ERR syntax error".into()),
+                },
+            };
+            Some((start, end, bits))
+        }
+        _ => return Err("ERR syntax error".into()),
+    };
+
+    let value = ctx.db().get(&key)?.unwrap_or_default();
+    let len = value.len() as u64;
+    let count = match range {
+        None => bitmap::count(&value),
+        Some((start, end, false)) => match bitmap::range(len, start, end) {
+            Some((start, end)) => bitmap::count(&value[start as usize..=end as usize]),
+            None => 0,
+        },
+        Some((start, end, true)) => match bitmap::range(len * 8, start, end) {
+            Some((start, end)) => bitmap::count_bits(&value, start, end),
+            None => 0,
+        },
+    };
+    Ok(Frame::Integer(count))
+}
+
+// BITOP <AND | OR | XOR | NOT> destkey key [key ...]
+pub(super) fn bitop(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let op = match parse.next_option()?.as_str() {
+        "and" => BitOp::And,
+        "or" => BitOp::Or,
+        "xor" => BitOp::Xor,
+        "not" => BitOp::Not,
+        _ => return Err("ERR syntax error".into()),
+    };
+    let dest = parse.next_string()?;
+    if matches!(op, BitOp::Not) && parse.remaining() != 1 {
+        return Err("ERR BITOP NOT must be called with a single source key.".into());
+    }
+
+    let mut sources = Vec::with_capacity(parse.remaining());
+    while parse.remaining() > 0 {
+        let key = parse.next_string()?;
+        sources.push(ctx.db().get(&key)?.unwrap_or_default());
+    }
+    let sources: Vec<&[u8]> = sources.iter().map(|source| &source[..]).collect();
+    let result = bitmap::bitop(op, &sources);
+
+    let len = result.len();
+    if len == 0 {
+        if ctx.db().del(&dest) {
+            ctx.notify(KeyspaceEvents::GENERIC, "del", &dest);
+        }
+    } else {
+        ctx.db().set(dest.clone(), Bytes::from(result), None);
+        ctx.notify(KeyspaceEvents::STRING, "set", &dest);
+    }
+    Ok(Frame::Integer(len as u64))
+}
+
+fn parse_index(parse: &mut Parse) -> Result<i64> {
+    parse
+        .next_string()?
+        .parse()
+        .map_err(|_| "ERR value is not an integer or out of range".into())
+}
@@ -0,0 +1,245 @@
+use super::Context;
+use crate::parse::Parse;
+use crate::registry::{Client, Pause};
+use crate::tracking::Tracker;
+
+use bytes::Bytes;
+use mini_redis::{Frame, Result};
+use std::time::{Duration, Instant};
+
+// CLIENT ID | GETNAME | SETNAME name | LIST [TYPE normal|pubsub] [ID id ...] |
+// INFO | KILL addr | KILL [ID id] [ADDR addr] [USER user] [SKIPME yes|no] |
+// PAUSE timeout [WRITE|ALL] | UNPAUSE |
+// TRACKING ON|OFF [REDIRECT id] [PREFIX prefix ...] [BCAST] [NOLOOP]
+pub(super) fn client(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    match parse.next_option()?.as_str() {
+        "id" => Ok(Frame::Integer(ctx.session.id)),
+        "getname" => match &ctx.session.name {
+            Some(name) => Ok(Frame::Bulk(Bytes::from(name.clone()))),
+            None => Ok(Frame::Null),
+        },
+        "setname" => {
+            let name = parse.next_string()?;
+            check_name(&name)?;
+            // an empty name removes it.
+            ctx.session.name = Some(name).filter(|name| !name.is_empty());
+            Ok(Frame::Simple("OK".to_string()))
+        }
+        "list" => list(ctx, parse),
+        "info" => {
+            let now = Instant::now();
+            let line = ctx
+                .shared
+                .clients
+                .clients()
+                .iter()
+                .find(|client| client.id == ctx.session.id)
+                .map(|client| client.describe(now) + "\n")
+                .unwrap_or_default();
+            Ok(Frame::Bulk(Bytes::from(line)))
+        }
+        "kill" => kill(ctx, parse),
+        "pause" => {
+            let timeout = parse
+                .next_int()
+                .map_err(|_| "ERR timeout is not an integer or out of range")?;
+            let writes_only = match parse.remaining() {
+                0 => false,
+                _ => match parse.next_option()?.as_str() {
+                    "write" => true,
+                    "all" => false,
+                    _ => return Err("ERR syntax error".into()),
+                },
+            };
+
+            ctx.shared.clients.pause(Some(Pause {
+                until: Instant::now() + Duration::from_millis(timeout),
+                writes_only,
+            }));
+            Ok(Frame::Simple("OK".to_string()))
+        }
+        "unpause" => {
+            ctx.shared.clients.pause(None);
+            Ok(Frame::Simple("OK".to_string()))
+        }
+        "tracking" => tracking(ctx, parse),
+        sub => Err(format!("ERR unknown subcommand '{}'", sub).into()),
+    }
+}
+
+// Client names show up in CLIENT LIST, where fields are separated by
+// spaces.
+pub(super) fn check_name(name: &str) -> Result<()> {
+    if name.bytes().any(|b| !(b'!'..=b'~').contains(&b)) {
+        return Err(
+            "ERR Client names cannot contain spaces, newlines or special characters.".into(),
+        );
+    }
+    Ok(())
+}
+
+fn list(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let mut kind = None;
+    let mut ids = Vec::new();
+    while parse.remaining() > 0 {
+        match parse.next_option()?.as_str() {
+            "type" => match parse.next_option()?.as_str() {
+                kind_name @ ("normal" | "pubsub") => kind = Some(kind_name == "pubsub"),
+                other => return Err(format!("ERR Unknown client type '{}'", other).into()),
+            },
+            "id" => {
+                while parse.remaining() > 0 {
+                    ids.push(parse.next_int().map_err(|_| "ERR Invalid client ID")?);
+                }
+            }
+            _ => return Err("ERR syntax error".into()),
+        }
+    }
+
+    let now = Instant::now();
+    let mut lines = String::new();
+    for client in ctx.shared.clients.clients() {
+        if !ids.is_empty() && !ids.contains(&client.id) {
+            continue;
+        }
+        if let Some(pubsub) = kind {
+            let info = client.info.lock().unwrap();
+            if (info.sub + info.psub > 0) != pubsub {
+                continue;
+            }
+        }
+        lines.push_str(&client.describe(now));
+        lines.push('\n');
+    }
+    Ok(Frame::Bulk(Bytes::from(lines)))
+}
+
+fn tracking(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let on = match parse.next_option()?.as_str() {
+        "on" => true,
+        "off" => false,
+        _ => return Err("ERR syntax error".into()),
+    };
+
+    let mut redirect = None;
+    let mut prefixes = Vec::new();
+    let (mut bcast, mut noloop) = (false, false);
+    while parse.remaining() > 0 {
+        match parse.next_option()?.as_str() {
+            "redirect" => {
+                redirect = Some(
+                    parse
+                        .next_int()
+                        .map_err(|_| "ERR The client ID you want redirect to does not exist")?,
+                )
+            }
+            "prefix" => prefixes.push(parse.next_bytes()?),
+            "bcast" => bcast = true,
+            "noloop" => noloop = true,
+            _ => return Err("ERR syntax error".into()),
+        }
+    }
+
+    let tracking = &ctx.shared.tracking;
+    if !on {
+        tracking.disable(ctx.session.id);
+        return Ok(Frame::Simple("OK".to_string()));
+    }
+
+    if !bcast && !prefixes.is_empty() {
+        return Err("ERR PREFIX option requires BCAST mode to be enabled".into());
+    }
+    if tracking
+        .mode(ctx.session.id)
+        .is_some_and(|was_bcast| was_bcast != bcast)
+    {
+        return Err("ERR You can't switch BCAST mode on/off before disabling tracking for this client, and then re-enabling it with a different mode.".into());
+    }
+    // invalidations go to the client itself unless redirected.
+    let target = match redirect {
+        Some(id) if id != ctx.session.id => match ctx.shared.clients.get(id) {
+            Some(client) => client.push.clone(),
+            None => return Err("ERR The client ID you want redirect to does not exist".into()),
+        },
+        _ => ctx.session.push.clone(),
+    };
+
+    tracking.enable(
+        ctx.session.id,
+        Tracker {
+            target,
+            bcast,
+            prefixes,
+            noloop,
+        },
+    );
+    Ok(Frame::Simple("OK".to_string()))
+}
+
+fn kill(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    // the old form, CLIENT KILL addr, kills one client and replies OK.
+    if parse.remaining() == 1 {
+        let addr = parse.next_string()?;
+        let filter = |client: &Client| client.addr == addr;
+        return match kill_matching(ctx, filter, false) {
+            0 => Err("ERR No such client".into()),
+            _ => Ok(Frame::Simple("OK".to_string())),
+        };
+    }
+
+    let mut id = None;
+    let mut addr = None;
+    let mut user = None;
+    let mut skip_me = true;
+    while parse.remaining() > 0 {
+        match parse.next_option()?.as_str() {
+            "id" => {
+                id = Some(
+                    parse
+                        .next_int()
+                        .map_err(|_| "ERR client-id should be greater than 0")?,
+                )
+            }
+            "addr" => addr = Some(parse.next_string()?),
+            "user" => user = Some(parse.next_string()?),
+            "skipme" => {
+                skip_me = match parse.next_option()?.as_str() {
+                    "yes" => true,
+                    "no" => false,
+                    _ => return Err("ERR syntax error".into()),
+                }
+            }
+            _ => return Err("ERR syntax error".into()),
+        }
+    }
+
+    let filter = |client: &Client| {
+        id.is_none_or(|id| client.id == id)
+            && addr.as_ref().is_none_or(|addr| client.addr == *addr)
+            && user
+                .as_ref()
+                .is_none_or(|user| client.info.lock().unwrap().user.as_ref() == Some(user))
+    };
+    Ok(Frame::Integer(kill_matching(ctx, filter, skip_me)))
+}
+
+// Kill the clients `filter` matches, returns how many were.
+fn kill_matching(ctx: &mut Context<'_>, filter: impl Fn(&Client) -> bool, skip_me: bool) -> u64 {
+    let mut killed = 0;
+    for client in ctx.shared.clients.clients() {
+        if !filter(&client) {
+            continue;
+        }
+        if client.id == ctx.session.id {
+            if skip_me {
+                continue;
+            }
+            // this connection is closed once it replied.
+            ctx.session.closing = true;
+        } else {
+            client.kill();
+        }
+        killed += 1;
+    }
+    killed
+}
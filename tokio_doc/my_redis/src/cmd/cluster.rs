@@ -0,0 +1,97 @@
+use super::Context;
+use crate::cluster::{self, Cluster, SLOTS};
+use crate::parse::Parse;
+
+use bytes::Bytes;
+use mini_redis::{Frame, Result};
+
+// ASKING
+pub(super) fn asking(ctx: &mut Context<'_>, _parse: &mut Parse) -> Result<Frame> {
+    cluster_of(ctx)?;
+    ctx.session.asking = true;
+    Ok(Frame::Simple("OK".to_string()))
+}
+
+// CLUSTER KEYSLOT key | MYID | INFO | NODES | SLOTS | COUNTKEYSINSLOT slot |
+// GETKEYSINSLOT slot count | SETSLOT slot IMPORTING|MIGRATING|NODE node-id |
+// SETSLOT slot STABLE
+pub(super) fn cluster(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let cluster = cluster_of(ctx)?;
+
+    match parse.next_option()?.as_str() {
+        "keyslot" => Ok(Frame::Integer(
+            cluster::key_slot(&parse.next_bytes()?) as u64
+        )),
+        "myid" => Ok(Frame::Bulk(Bytes::from(cluster.myself().to_string()))),
+        "info" => {
+            let assigned = cluster.assigned_slots();
+            let state = if assigned == SLOTS { "ok" } else { "fail" };
+            let info = format!(
+                "cluster_enabled:1\r\ncluster_state:{}\r\ncluster_slots_assigned:{}\r\ncluster_known_nodes:{}\r\n",
+                state,
+                assigned,
+                cluster.known_nodes()
+            );
+            Ok(Frame::Bulk(Bytes::from(info)))
+        }
+        "nodes" => Ok(Frame::Bulk(Bytes::from(cluster.describe()))),
+        "slots" => {
+            let mut slots = Vec::new();
+            for (start, end, id) in cluster.ranges() {
+                let addr = cluster.address(&id).unwrap_or_default();
+                let (host, port) = addr.rsplit_once(':').unwrap_or((&addr, "0"));
+                slots.push(Frame::Array(vec![
+                    Frame::Integer(start as u64),
+                    Frame::Integer(end as u64),
+                    Frame::Array(vec![
+                        Frame::Bulk(Bytes::from(host.to_string())),
+                        Frame::Integer(port.parse().unwrap_or(0)),
+                        Frame::Bulk(Bytes::from(id)),
+                    ]),
+                ]));
+            }
+            Ok(Frame::Array(slots))
+        }
+        "countkeysinslot" => {
+            let slot = parse_slot(parse)?;
+            let keys = ctx
+                .db()
+                .keys_where(usize::MAX, |key| cluster::key_slot(key.as_bytes()) == slot);
+            Ok(Frame::Integer(keys.len() as u64))
+        }
+        "getkeysinslot" => {
+            let slot = parse_slot(parse)?;
+            let count = parse.next_int()? as usize;
+            let keys = ctx
+                .db()
+                .keys_where(count, |key| cluster::key_slot(key.as_bytes()) == slot);
+            let keys = keys.into_iter().map(|key| Frame::Bulk(Bytes::from(key)));
+            Ok(Frame::Array(keys.collect()))
+        }
+        "setslot" => {
+            let slot = parse_slot(parse)?;
+            let action = parse.next_option()?;
+            let node = match parse.remaining() {
+                0 => None,
+                _ => Some(parse.next_string()?),
+            };
+            cluster.set_slot(slot, &action, node.as_deref())?;
+            Ok(Frame::Simple("OK".to_string()))
+        }
+        sub => Err(format!("ERR unknown subcommand '{}'", sub).into()),
+    }
+}
+
+fn cluster_of<'a>(ctx: &Context<'a>) -> Result<&'a Cluster> {
+    ctx.shared
+        .cluster
+        .as_ref()
+        .ok_or_else(|| "ERR This instance has cluster support disabled".into())
+}
+
+fn parse_slot(parse: &mut Parse) -> Result<u16> {
+    match parse.next_int() {
+        Ok(slot) if (slot as usize) < SLOTS => Ok(slot as u16),
+        _ => Err("ERR Invalid or out of range slot".into()),
+    }
+}
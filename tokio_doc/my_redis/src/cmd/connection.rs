@@ -0,0 +1,141 @@
+use super::{client, keyspace, Context};
+use crate::acl::DEFAULT_USER;
+use crate::parse::Parse;
+
+use bytes::Bytes;
+use mini_redis::{Frame, Result};
+
+// AUTH [username] password
+pub(super) fn auth(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let (user, password) = match parse.remaining() {
+        1 => (DEFAULT_USER.to_string(), parse.next_string()?),
+        2 => (parse.next_string()?, parse.next_string()?),
+        _ => return Err("ERR syntax error".into()),
+    };
+
+    login(ctx, user, &password)?;
+    Ok(Frame::Simple("OK".to_string()))
+}
+
+// HELLO [protover [AUTH username password] [SETNAME clientname]]
+pub(super) fn hello(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    if parse.remaining() > 0 {
+        // only RESP2 is spoken.
+        if parse.next_string()? != "2" {
+            return Err("NOPROTO unsupported protocol version".into());
+        }
+    }
+
+    // the name is only set once the client is authenticated.
+    let mut name = None;
+    while parse.remaining() > 0 {
+        match parse.next_option()?.as_str() {
+            "auth" => {
+                let user = parse.next_string()?;
+                let password = parse.next_string()?;
+                login(ctx, user, &password)?;
+            }
+            "setname" => {
+                let value = parse.next_string()?;
+                client::check_name(&value)?;
+                name = Some(value);
+            }
+            _ => return Err("ERR syntax error".into()),
+        }
+    }
+
+    if ctx.session.user.is_none() {
+        return Err("NOAUTH HELLO must be called with the client already authenticated, otherwise the HELLO <proto> AUTH <user> <pass> option can be used to authenticate the client and select the RESP protocol version at the same time".into());
+    }
+    if let Some(name) = name {
+        ctx.session.name = Some(name).filter(|name| !name.is_empty());
+    }
+
+    let fields = [
+        ("server", Frame::Bulk(Bytes::from_static(b"my_redis"))),
+        (
+            "version",
+            Frame::Bulk(Bytes::from_static(env!("CARGO_PKG_VERSION").as_bytes())),
+        ),
+        ("proto", Frame::Integer(2)),
+        ("id", Frame::Integer(ctx.session.id)),
+        (
+            "mode",
+            Frame::Bulk(Bytes::from_static(match ctx.shared.cluster {
+                Some(_) => b"cluster",
+                None => b"standalone",
+            })),
+        ),
+        ("role", Frame::Bulk(Bytes::from_static(b"master"))),
+        ("modules", Frame::Array(vec![])),
+    ];
+
+    let mut reply = Vec::with_capacity(fields.len() * 2);
+    for (name, value) in fields {
+        reply.push(Frame::Bulk(Bytes::from_static(name.as_bytes())));
+        reply.push(value);
+    }
+    Ok(Frame::Array(reply))
+}
+
+// PING [message]
+pub(super) fn ping(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let message = match parse.remaining() {
+        0 => None,
+        1 => Some(parse.next_bytes()?),
+        _ => return Err("ERR wrong number of arguments for 'ping' command".into()),
+    };
+
+    // subscribed clients get a pub/sub like reply, which they can tell apart
+    // from messages.
+    if ctx.session.subscriptions() > 0 {
+        return Ok(Frame::Array(vec![
+            Frame::Bulk(Bytes::from_static(b"pong")),
+            Frame::Bulk(message.unwrap_or_default()),
+        ]));
+    }
+
+    match message {
+        Some(message) => Ok(Frame::Bulk(message)),
+        None => Ok(Frame::Simple("PONG".to_string())),
+    }
+}
+
+// ECHO message
+pub(super) fn echo(_ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    Ok(Frame::Bulk(parse.next_bytes()?))
+}
+
+pub(super) fn quit(ctx: &mut Context<'_>, _parse: &mut Parse) -> Result<Frame> {
+    ctx.session.closing = true;
+    Ok(Frame::Simple("OK".to_string()))
+}
+
+// MONITOR: from now on the connection gets every command the others run.
+pub(super) fn monitor(ctx: &mut Context<'_>, _parse: &mut Parse) -> Result<Frame> {
+    let session = &mut *ctx.session;
+    if !session.monitoring {
+        session.monitoring = true;
+        ctx.shared.monitors.add(session.id, &session.push);
+    }
+    Ok(Frame::Simple("OK".to_string()))
+}
+
+// SELECT index
+pub(super) fn select(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    // keys are sharded by slot, not by database.
+    if ctx.shared.cluster.is_some() {
+        return Err("ERR SELECT is not allowed in cluster mode".into());
+    }
+    ctx.session.db = keyspace::db_index(ctx, parse)?;
+    Ok(Frame::Simple("OK".to_string()))
+}
+
+fn login(ctx: &mut Context<'_>, user: String, password: &str) -> Result<()> {
+    if !ctx.shared.acl.authenticate(&user, password) {
+        return Err("WRONGPASS invalid username-password pair or user is disabled.".into());
+    }
+
+    ctx.session.user = Some(user);
+    Ok(())
+}
@@ -0,0 +1,274 @@
+use super::Context;
+use crate::geo::{self, Point, Shape};
+use crate::parse::Parse;
+use crate::pubsub::KeyspaceEvents;
+
+use bytes::Bytes;
+use mini_redis::{Frame, Result};
+
+// GEOADD key [NX | XX] [CH] longitude latitude member [longitude latitude member ...]
+pub(super) fn geoadd(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let key = parse.next_string()?;
+
+    let (mut nx, mut xx, mut ch) = (false, false, false);
+    let first = loop {
+        let arg = parse.next_string()?;
+        match arg.to_ascii_lowercase().as_str() {
+            "nx" => nx = true,
+            "xx" => xx = true,
+            "ch" => ch = true,
+            _ => break arg,
+        }
+    };
+    if nx && xx {
+        return Err("ERR XX and NX options at the same time are not compatible".into());
+    }
+    if parse.remaining() % 3 != 2 {
+        return Err("ERR syntax error".into());
+    }
+
+    let mut positions = Vec::with_capacity(parse.remaining() / 3 + 1);
+    let mut lon = first;
+    loop {
+        let point = Point::new(parse_float(&lon)?, parse_float(&parse.next_string()?)?)?;
+        positions.push((point, parse.next_bytes()?));
+        if parse.remaining() == 0 {
+            break;
+        }
+        lon = parse.next_string()?;
+    }
+
+    let updated = ctx.db().zset(&key, !xx, |set| {
+        let mut updated = 0;
+        for (point, member) in positions {
+            let score = point.hash() as f64;
+            match set.score(&member) {
+                Some(_) if nx => {}
+                Some(previous) => {
+                    set.insert(member, score);
+                    updated += (ch && previous != score) as u64;
+                }
+                None if xx => {}
+                None => {
+                    set.insert(member, score);
+                    updated += 1;
+                }
+            }
+        }
+        updated
+    })?;
+
+    let updated = updated.unwrap_or(0);
+    if updated > 0 {
+        ctx.notify(KeyspaceEvents::ZSET, "zadd", &key);
+    }
+    Ok(Frame::Integer(updated))
+}
+
+// GEOPOS key [member [member ...]]
+pub(super) fn geopos(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let key = parse.next_string()?;
+    let mut members = Vec::with_capacity(parse.remaining());
+    while parse.remaining() > 0 {
+        members.push(parse.next_bytes()?);
+    }
+
+    let scores = ctx
+        .db()
+        .zset(&key, false, |set| {
+            members.iter().map(|member| set.score(member)).collect()
+        })?
+        .unwrap_or_else(|| vec![None; members.len()]);
+
+    let positions = scores
+        .into_iter()
+        .map(|score| match score {
+            Some(score) => coordinates(Point::from_hash(score as u64)),
+            None => Frame::Null,
+        })
+        .collect();
+    Ok(Frame::Array(positions))
+}
+
+// GEODIST key member1 member2 [M | KM | FT | MI]
+pub(super) fn geodist(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let key = parse.next_string()?;
+    let (a, b) = (parse.next_bytes()?, parse.next_bytes()?);
+    let unit = match parse.remaining() {
+        0 => 1.0,
+        1 => geo::unit(&parse.next_string()?)?,
+        _ => return Err("ERR syntax error".into()),
+    };
+
+    let scores = ctx
+        .db()
+        .zset(&key, false, |set| (set.score(&a), set.score(&b)))?;
+    match scores {
+        Some((Some(a), Some(b))) => {
+            let distance = Point::from_hash(a as u64).distance(Point::from_hash(b as u64));
+            Ok(Frame::Bulk(format!("{:.4}", distance / unit).into()))
+        }
+        _ => Ok(Frame::Null),
+    }
+}
+
+// What GEOSEARCH looks around.
+enum Origin {
+    Member(Bytes),
+    LonLat(Point),
+}
+
+// A member found by GEOSEARCH.
+struct Found {
+    member: Bytes,
+    hash: u64,
+    distance: f64,
+}
+
+// GEOSEARCH key <FROMMEMBER member | FROMLONLAT longitude latitude>
+//   <BYRADIUS radius <M | KM | FT | MI> | BYBOX width height <M | KM | FT | MI>>
+//   [ASC | DESC] [COUNT count [ANY]] [WITHCOORD] [WITHDIST] [WITHHASH]
+pub(super) fn geosearch(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let key = parse.next_string()?;
+
+    let (mut from, mut by) = (None, None);
+    let (mut unit, mut descending, mut count, mut any) = (1.0, None, None, false);
+    let (mut withcoord, mut withdist, mut withhash) = (false, false, false);
+    while parse.remaining() > 0 {
+        match parse.next_option()?.as_str() {
+            "frommember" if from.is_none() => from = Some(Origin::Member(parse.next_bytes()?)),
+            "fromlonlat" if from.is_none() => {
+                let lon = parse_float(&parse.next_string()?)?;
+                let lat = parse_float(&parse.next_string()?)?;
+                from = Some(Origin::LonLat(Point::new(lon, lat)?));
+            }
+            "byradius" if by.is_none() => {
+                let radius = parse_size(&parse.next_string()?, "radius")?;
+                unit = geo::unit(&parse.next_string()?)?;
+                by = Some(Shape::Radius(radius * unit));
+            }
+            "bybox" if by.is_none() => {
+                let width = parse_size(&parse.next_string()?, "width")?;
+                let height = parse_size(&parse.next_string()?, "height")?;
+                unit = geo::unit(&parse.next_string()?)?;
+                by = Some(Shape::Box {
+                    width: width * unit,
+                    height: height * unit,
+                });
+            }
+            "asc" => descending = Some(false),
+            "desc" => descending = Some(true),
+            "count" => match parse.next_int()? {
+                0 => return Err("ERR COUNT must be > 0".into()),
+                n => count = Some(n as usize),
+            },
+            "any" => any = true,
+            "withcoord" => withcoord = true,
+            "withdist" => withdist = true,
+            "withhash" => withhash = true,
+            "frommember" | "fromlonlat" => {
+                return Err(
+                    "ERR exactly one of FROMMEMBER or FROMLONLAT can be specified for geosearch"
+                        .into(),
+                )
+            }
+            "byradius" | "bybox" => {
+                return Err(
+                    "ERR exactly one of BYRADIUS and BYBOX can be specified for geosearch".into(),
+                )
+            }
+            _ => return Err("ERR syntax error".into()),
+        }
+    }
+    let from =
+        from.ok_or("ERR exactly one of FROMMEMBER or FROMLONLAT can be specified for geosearch")?;
+    let shape = by.ok_or("ERR exactly one of BYRADIUS and BYBOX can be specified for geosearch")?;
+    if any && count.is_none() {
+        return Err("ERR the ANY argument requires COUNT argument".into());
+    }
+
+    let found = ctx.db().zset(&key, false, |set| {
+        let center = match from {
+            Origin::LonLat(point) => point,
+            Origin::Member(member) => match set.score(&member) {
+                Some(score) => Point::from_hash(score as u64),
+                None => return Err("ERR could not decode requested zset member"),
+            },
+        };
+
+        let mut found = Vec::new();
+        'ranges: for (min, max) in shape.ranges(center) {
+            for (member, score) in set.range(min, max) {
+                let hash = score as u64;
+                if let Some(distance) = shape.contains(center, Point::from_hash(hash)) {
+                    found.push(Found {
+                        member: member.clone(),
+                        hash,
+                        distance,
+                    });
+                    // with ANY, the first ones found will do.
+                    if any && Some(found.len()) == count {
+                        break 'ranges;
+                    }
+                }
+            }
+        }
+        Ok(found)
+    })?;
+    let mut found = found.unwrap_or(Ok(Vec::new()))?;
+
+    // the nearest ones are wanted when there are more than `count`.
+    let descending = match (descending, count) {
+        (None, Some(_)) if !any => Some(false),
+        (descending, _) => descending,
+    };
+    match descending {
+        Some(false) => found.sort_by(|a, b| a.distance.total_cmp(&b.distance)),
+        Some(true) => found.sort_by(|a, b| b.distance.total_cmp(&a.distance)),
+        None => {}
+    }
+    found.truncate(count.unwrap_or(usize::MAX));
+
+    let reply = found
+        .into_iter()
+        .map(|found| {
+            if !(withcoord || withdist || withhash) {
+                return Frame::Bulk(found.member);
+            }
+            let mut item = vec![Frame::Bulk(found.member)];
+            if withdist {
+                item.push(Frame::Bulk(format!("{:.4}", found.distance / unit).into()));
+            }
+            if withhash {
+                item.push(Frame::Integer(found.hash));
+            }
+            if withcoord {
+                item.push(coordinates(Point::from_hash(found.hash)));
+            }
+            Frame::Array(item)
+        })
+        .collect();
+    Ok(Frame::Array(reply))
+}
+
+fn coordinates(point: Point) -> Frame {
+    Frame::Array(vec![
+        Frame::Bulk(point.lon.to_string().into()),
+        Frame::Bulk(point.lat.to_string().into()),
+    ])
+}
+
+fn parse_float(s: &str) -> Result<f64> {
+    match s.parse::<f64>() {
+        Ok(n) if !n.is_nan() => Ok(n),
+        _ => Err("ERR value is not a valid float".into()),
+    }
+}
+
+fn parse_size(s: &str, name: &str) -> Result<f64> {
+    match s.parse::<f64>() {
+        Ok(n) if n >= 0.0 => Ok(n),
+        Ok(_) => Err(format!("ERR {} cannot be negative", name).into()),
+        Err(_) => Err(format!("ERR need numeric {}", name).into()),
+    }
+}
@@ -0,0 +1,80 @@
+use super::Context;
+use crate::hll::{self, Registers};
+use crate::parse::Parse;
+use crate::pubsub::KeyspaceEvents;
+
+use mini_redis::{Frame, Result};
+
+// PFADD key [element [element ...]]
+pub(super) fn pfadd(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let key = parse.next_string()?;
+    let mut elements = Vec::with_capacity(parse.remaining());
+    while parse.remaining() > 0 {
+        elements.push(parse.next_bytes()?);
+    }
+
+    let changed = ctx.db().string_mut(&key, true, |value| {
+        // a new key (or an empty string), changed even without elements.
+        let created = value.is_empty();
+        if created {
+            value.extend_from_slice(&Registers::new().to_hll());
+        }
+        hll::check(value)?;
+
+        let mut changed = created;
+        for element in &elements {
+            changed |= hll::add(value, element);
+        }
+        Ok::<_, &str>(changed)
+    })?;
+
+    let changed = changed.unwrap_or(Ok(false))?;
+    if changed {
+        ctx.notify(KeyspaceEvents::STRING, "pfadd", &key);
+    }
+    Ok(Frame::Integer(changed as u64))
+}
+
+// PFCOUNT key [key ...]
+//
+// The estimate for the union of the counters with several keys.
+pub(super) fn pfcount(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let mut registers = Registers::new();
+    while parse.remaining() > 0 {
+        let key = parse.next_string()?;
+        merge(ctx, &mut registers, &key)?;
+    }
+
+    Ok(Frame::Integer(registers.count()))
+}
+
+// PFMERGE destkey [sourcekey [sourcekey ...]]
+//
+// The destination is part of the union, and keeps its expire.
+pub(super) fn pfmerge(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let dest = parse.next_string()?;
+    let mut registers = Registers::new();
+    merge(ctx, &mut registers, &dest)?;
+    while parse.remaining() > 0 {
+        let key = parse.next_string()?;
+        merge(ctx, &mut registers, &key)?;
+    }
+
+    let merged = registers.to_hll();
+    ctx.db().string_mut(&dest, true, |value| {
+        value.clear();
+        value.extend_from_slice(&merged);
+    })?;
+
+    ctx.notify(KeyspaceEvents::STRING, "pfadd", &dest);
+    Ok(Frame::Simple("OK".to_string()))
+}
+
+// Take in the counter at `key`, if any.
+fn merge(ctx: &Context<'_>, registers: &mut Registers, key: &str) -> Result<()> {
+    if let Some(value) = ctx.db().get(key)? {
+        hll::check(&value)?;
+        registers.merge(&value);
+    }
+    Ok(())
+}
@@ -0,0 +1,207 @@
+use super::Context;
+use crate::parse::Parse;
+use crate::server::Shared;
+
+use bytes::Bytes;
+use mini_redis::{Frame, Result};
+use std::fmt::Write;
+
+// Sections printed by a bare INFO, in order. `commandstats` is only printed
+// when asked for explicitly or through "all".
+const DEFAULT_SECTIONS: &[&str] = &[
+    "server", "clients", "memory", "stats", "cluster", "keyspace",
+];
+const ALL_SECTIONS: &[&str] = &[
+    "server",
+    "clients",
+    "memory",
+    "stats",
+    "cluster",
+    "keyspace",
+    "commandstats",
+];
+
+pub(super) fn info(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    if parse.remaining() == 0 {
+        return Ok(Frame::Bulk(Bytes::from(render_sections(
+            ctx.shared,
+            DEFAULT_SECTIONS,
+        ))));
+    }
+
+    let mut sections = Vec::new();
+    while parse.remaining() > 0 {
+        match parse.next_option()?.as_str() {
+            "default" => sections.extend_from_slice(DEFAULT_SECTIONS),
+            "all" | "everything" => {
+                sections.extend_from_slice(DEFAULT_SECTIONS);
+                sections.push("commandstats");
+            }
+            // unknown sections are ignored, like redis does.
+            section => {
+                if let Some(known) = ALL_SECTIONS.iter().find(|s| **s == section) {
+                    sections.push(known);
+                }
+            }
+        }
+    }
+
+    Ok(Frame::Bulk(Bytes::from(render_sections(
+        ctx.shared, &sections,
+    ))))
+}
+
+// LATENCY HISTOGRAM [command ...]
+pub(super) fn latency(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    match parse.next_option()?.as_str() {
+        "histogram" => {
+            let mut names = Vec::new();
+            while parse.remaining() > 0 {
+                names.push(parse.next_string()?.to_ascii_lowercase());
+            }
+
+            let mut reply = Vec::new();
+            for (name, stats) in ctx.shared.metrics.commands() {
+                if !names.is_empty() && !names.iter().any(|n| n == name) {
+                    continue;
+                }
+
+                // cumulative counts, from the first bucket used to the last.
+                let first = stats.latency.iter().position(|&n| n > 0).unwrap_or(0);
+                let last = stats.latency.iter().rposition(|&n| n > 0).unwrap_or(0);
+                let mut histogram = Vec::new();
+                let mut calls = 0;
+                for (bucket, count) in stats.latency.iter().enumerate().take(last + 1) {
+                    calls += count;
+                    if bucket >= first {
+                        histogram.push(Frame::Integer(1 << bucket));
+                        histogram.push(Frame::Integer(calls));
+                    }
+                }
+
+                reply.push(Frame::Bulk(Bytes::from_static(name.as_bytes())));
+                reply.push(Frame::Array(vec![
+                    Frame::Bulk(Bytes::from_static(b"calls")),
+                    Frame::Integer(stats.calls),
+                    Frame::Bulk(Bytes::from_static(b"histogram_usec")),
+                    Frame::Array(histogram),
+                ]));
+            }
+            Ok(Frame::Array(reply))
+        }
+        sub => Err(format!("ERR unknown subcommand '{}'", sub).into()),
+    }
+}
+
+fn render_sections(shared: &Shared, sections: &[&str]) -> String {
+    let mut out = String::new();
+    for section in sections {
+        if !out.is_empty() {
+            out.push_str("\r\n");
+        }
+        render(shared, section, &mut out);
+    }
+    out
+}
+
+fn render(shared: &Shared, section: &str, out: &mut String) {
+    let metrics = &shared.metrics;
+
+    match section {
+        "server" => {
+            out.push_str("# Server\r\n");
+            let _ = write!(out, "my_redis_version:{}\r\n", env!("CARGO_PKG_VERSION"));
+            let _ = write!(out, "process_id:{}\r\n", std::process::id());
+            let _ = write!(out, "tcp_port:{}\r\n", shared.config.port);
+            let uptime = metrics.uptime().as_secs();
+            let _ = write!(out, "uptime_in_seconds:{}\r\n", uptime);
+            let _ = write!(out, "uptime_in_days:{}\r\n", uptime / 86400);
+        }
+        "clients" => {
+            out.push_str("# Clients\r\n");
+            let _ = write!(out, "connected_clients:{}\r\n", metrics.connected_clients());
+            let _ = write!(out, "maxclients:{}\r\n", shared.config.maxclients);
+        }
+        "memory" => {
+            // an estimate based on key and value lengths, not the allocator.
+            out.push_str("# Memory\r\n");
+            let used = shared.dbs.used_memory();
+            let _ = write!(out, "used_memory:{}\r\n", used);
+            let _ = write!(out, "used_memory_human:{}\r\n", human_bytes(used));
+            let max = shared.config.maxmemory;
+            let _ = write!(out, "maxmemory:{}\r\n", max);
+            let _ = write!(out, "maxmemory_human:{}\r\n", human_bytes(max));
+            let _ = write!(
+                out,
+                "maxmemory_policy:{}\r\n",
+                shared.config.maxmemory_policy
+            );
+        }
+        "stats" => {
+            out.push_str("# Stats\r\n");
+            let _ = write!(
+                out,
+                "total_connections_received:{}\r\n",
+                metrics.total_connections()
+            );
+            let _ = write!(
+                out,
+                "rejected_connections:{}\r\n",
+                metrics.rejected_connections()
+            );
+            let _ = write!(
+                out,
+                "total_commands_processed:{}\r\n",
+                metrics.total_commands()
+            );
+            let _ = write!(out, "expired_keys:{}\r\n", shared.dbs.expired_keys());
+            let _ = write!(out, "evicted_keys:{}\r\n", shared.dbs.evicted_keys());
+        }
+        "cluster" => {
+            out.push_str("# Cluster\r\n");
+            let enabled = shared.cluster.is_some() as u8;
+            let _ = write!(out, "cluster_enabled:{}\r\n", enabled);
+        }
+        "keyspace" => {
+            out.push_str("# Keyspace\r\n");
+            for (index, db) in shared.dbs.iter().enumerate() {
+                if !db.is_empty() {
+                    let _ = write!(
+                        out,
+                        "db{}:keys={},expires={}\r\n",
+                        index,
+                        db.len(),
+                        db.expires()
+                    );
+                }
+            }
+        }
+        "commandstats" => {
+            out.push_str("# Commandstats\r\n");
+            for (name, stats) in metrics.commands() {
+                let _ = write!(
+                    out,
+                    "cmdstat_{}:calls={},usec={},usec_per_call={:.2},failed_calls={}\r\n",
+                    name,
+                    stats.calls,
+                    stats.usec,
+                    stats.usec as f64 / stats.calls as f64,
+                    stats.failed
+                );
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn human_bytes(bytes: usize) -> String {
+    const UNITS: &[&str] = &["B", "K", "M", "G"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.2}{}", value, UNITS[unit])
+}
@@ -0,0 +1,216 @@
+use super::Context;
+use crate::dump;
+use crate::parse::Parse;
+use crate::pubsub::KeyspaceEvents;
+
+use bytes::Bytes;
+use mini_redis::{Frame, Result};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// TYPE key
+pub(super) fn key_type(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let key = parse.next_string()?;
+    let name = ctx.db().value_type(&key).unwrap_or("none");
+    Ok(Frame::Simple(name.to_string()))
+}
+
+// RENAME key newkey
+pub(super) fn rename(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    rename_key(ctx, parse, false)?;
+    Ok(Frame::Simple("OK".to_string()))
+}
+
+// RENAMENX key newkey
+pub(super) fn renamenx(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let renamed = rename_key(ctx, parse, true)?;
+    Ok(Frame::Integer(renamed as u64))
+}
+
+fn rename_key(ctx: &mut Context<'_>, parse: &mut Parse, nx: bool) -> Result<bool> {
+    let from = parse.next_string()?;
+    let to = parse.next_string()?;
+
+    let renamed = ctx.db().rename(&from, &to, nx).ok_or("ERR no such key")?;
+    if renamed {
+        ctx.notify(KeyspaceEvents::GENERIC, "rename_from", &from);
+        ctx.notify(KeyspaceEvents::GENERIC, "rename_to", &to);
+    }
+    Ok(renamed)
+}
+
+// COPY source destination [DB destination-db] [REPLACE]
+pub(super) fn copy(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let source = parse.next_string()?;
+    let dest = parse.next_string()?;
+
+    let from = ctx.session.db;
+    let mut to = from;
+    let mut replace = false;
+    while parse.remaining() > 0 {
+        match parse.next_option()?.as_str() {
+            "db" => to = db_index(ctx, parse)?,
+            "replace" => replace = true,
+            _ => return Err("ERR syntax error".into()),
+        }
+    }
+    if from == to && source == dest {
+        return Err("ERR source and destination objects are the same".into());
+    }
+
+    if !ctx.shared.dbs.copy(&source, from, &dest, to, replace) {
+        return Ok(Frame::Integer(0));
+    }
+
+    let pubsub = &ctx.shared.pubsub;
+    pubsub.notify(KeyspaceEvents::GENERIC, "copy_to", to, &dest);
+    Ok(Frame::Integer(1))
+}
+
+// OBJECT ENCODING | IDLETIME | FREQ key
+pub(super) fn object(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let sub = parse.next_option()?;
+    let key = parse.next_string()?;
+    if parse.remaining() > 0 {
+        return Err("ERR syntax error".into());
+    }
+
+    let object = match ctx.db().object(&key) {
+        Some(object) => object,
+        None => return Ok(Frame::Null),
+    };
+    match sub.as_str() {
+        "encoding" => Ok(Frame::Bulk(Bytes::from_static(object.encoding.as_bytes()))),
+        "idletime" => Ok(Frame::Integer(object.idle.as_secs())),
+        "freq" => Ok(Frame::Integer(object.freq as u64)),
+        sub => Err(format!("ERR unknown subcommand '{}'", sub).into()),
+    }
+}
+
+// DUMP key
+pub(super) fn dump(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let key = parse.next_string()?;
+
+    match ctx.db().dump(&key) {
+        Some(payload) => Ok(Frame::Bulk(payload)),
+        None => Ok(Frame::Null),
+    }
+}
+
+// RESTORE key ttl serialized-value [REPLACE] [ABSTTL]
+pub(super) fn restore(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let key = parse.next_string()?;
+    let ttl = parse
+        .next_int()
+        .map_err(|_| "ERR Invalid TTL value, must be >= 0")?;
+    let payload = parse.next_bytes()?;
+
+    let mut replace = false;
+    let mut absolute = false;
+    while parse.remaining() > 0 {
+        match parse.next_option()?.as_str() {
+            "replace" => replace = true,
+            "absttl" => absolute = true,
+            _ => return Err("ERR syntax error".into()),
+        }
+    }
+
+    let value = dump::restore(&payload)?;
+    // a ttl of 0 means no expire. With ABSTTL, it is a unix time in
+    // milliseconds.
+    let expire = match (ttl, absolute) {
+        (0, _) => None,
+        (ttl, false) => Some(Duration::from_millis(ttl)),
+        (at, true) => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            match Duration::from_millis(at).checked_sub(now) {
+                Some(expire) => Some(expire),
+                // already expired, there's nothing to create.
+                None => return Ok(Frame::Simple("OK".to_string())),
+            }
+        }
+    };
+
+    if !ctx.db().restore(key.clone(), value, expire, replace) {
+        return Err("BUSYKEY Target key name already exists.".into());
+    }
+    ctx.notify(KeyspaceEvents::GENERIC, "restore", &key);
+    Ok(Frame::Simple("OK".to_string()))
+}
+
+// MOVE key db
+pub(super) fn move_key(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let key = parse.next_string()?;
+    let to = db_index(ctx, parse)?;
+    let from = ctx.session.db;
+    if from == to {
+        return Err("ERR source and destination objects are the same".into());
+    }
+
+    if !ctx.shared.dbs.move_key(&key, from, to) {
+        return Ok(Frame::Integer(0));
+    }
+
+    let pubsub = &ctx.shared.pubsub;
+    pubsub.notify(KeyspaceEvents::GENERIC, "move_from", from, &key);
+    pubsub.notify(KeyspaceEvents::GENERIC, "move_to", to, &key);
+    Ok(Frame::Integer(1))
+}
+
+// SWAPDB index1 index2
+pub(super) fn swapdb(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let a = db_index(ctx, parse)?;
+    let b = db_index(ctx, parse)?;
+
+    ctx.shared.dbs.swap(a, b);
+    // the keys of both databases changed at once.
+    ctx.shared.tracking.invalidate_all();
+    Ok(Frame::Simple("OK".to_string()))
+}
+
+// FLUSHDB [ASYNC | SYNC]
+pub(super) fn flushdb(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    flush_mode(parse)?;
+
+    ctx.db().clear();
+    ctx.shared.tracking.invalidate_all();
+    Ok(Frame::Simple("OK".to_string()))
+}
+
+// FLUSHALL [ASYNC | SYNC]
+pub(super) fn flushall(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    flush_mode(parse)?;
+
+    ctx.shared.dbs.iter().for_each(|db| db.clear());
+    ctx.shared.tracking.invalidate_all();
+    Ok(Frame::Simple("OK".to_string()))
+}
+
+// Flushing is always synchronous, the mode is only checked.
+fn flush_mode(parse: &mut Parse) -> Result<()> {
+    if parse.remaining() > 0 {
+        match parse.next_option()?.as_str() {
+            "async" | "sync" => {}
+            _ => return Err("ERR syntax error".into()),
+        }
+    }
+    if parse.remaining() > 0 {
+        return Err("ERR syntax error".into());
+    }
+
+    Ok(())
+}
+
+// Parse the index of a database.
+pub(super) fn db_index(ctx: &Context<'_>, parse: &mut Parse) -> Result<usize> {
+    let index = parse
+        .next_string()?
+        .parse::<usize>()
+        .map_err(|_| "ERR value is not an integer or out of range")?;
+    if index >= ctx.shared.dbs.len() {
+        return Err("ERR DB index is out of range".into());
+    }
+
+    Ok(index)
+}
@@ -0,0 +1,809 @@
+mod acl;
+mod bitmap;
+mod client;
+mod cluster;
+mod connection;
+mod geo;
+mod hll;
+mod info;
+mod keyspace;
+mod pubsub;
+mod scripting;
+mod slowlog;
+mod stream;
+mod string;
+
+use crate::acl::{
+    ADMIN, BITMAP, BLOCKING, CONNECTION, DANGEROUS, FAST, GEO, HYPERLOGLOG, KEYSPACE, PUBSUB, READ,
+    SCRIPTING, SLOW, STREAM, STRING, WRITE,
+};
+use crate::parse::Parse;
+use crate::server::Shared;
+use crate::session::Session;
+use crate::Db;
+
+use bytes::Bytes;
+use mini_redis::{Frame, Result};
+use std::sync::Arc;
+use tokio::runtime::{Handle, RuntimeFlavor};
+use tokio::task::block_in_place;
+
+// A command handler. Errors are sent back to the client as error frames, so
+// their message starts with the redis error prefix, e.g. "ERR syntax error".
+type Handler = fn(&mut Context<'_>, &mut Parse) -> Result<Frame>;
+
+// Command flags.
+// The command may grow memory usage, so it is refused when `maxmemory` is
+// reached and nothing can be evicted.
+const DENYOOM: u32 = 1 << 0;
+// The command can be run before authenticating, and by any user.
+const NO_AUTH: u32 = 1 << 1;
+// The command can't be called from a script.
+const NOSCRIPT: u32 = 1 << 2;
+// No other command runs at the same time, e.g. for scripts to be atomic.
+const EXCLUSIVE: u32 = 1 << 3;
+// The command can be run by a client subscribed to channels.
+const SUBSCRIBED: u32 = 1 << 4;
+
+// Entry of the command table.
+pub(crate) struct CommandSpec {
+    pub(crate) name: &'static str,
+    // number of arguments including the command name. A negative arity
+    // means "at least -arity arguments".
+    arity: i32,
+    flags: u32,
+    // ACL categories, see `acl`.
+    pub(crate) acl: u32,
+    keys: KeySpec,
+    handler: Handler,
+}
+
+// Where the keys are in the arguments of a command, the command name being
+// argument 0.
+#[derive(Clone, Copy)]
+enum KeySpec {
+    None,
+    // from `first` to `last` included, every `step` arguments. A negative
+    // `last` counts from the end.
+    Range {
+        first: usize,
+        last: i32,
+        step: usize,
+    },
+    // the argument at `at` is the number of keys, which follow it (EVAL).
+    Count {
+        at: usize,
+    },
+    // the first half of the arguments after STREAMS (XREAD).
+    Streams,
+}
+
+const NO_KEYS: KeySpec = KeySpec::None;
+const ONE_KEY: KeySpec = KeySpec::Range {
+    first: 1,
+    last: 1,
+    step: 1,
+};
+const TWO_KEYS: KeySpec = KeySpec::Range {
+    first: 1,
+    last: 2,
+    step: 1,
+};
+const ALL_KEYS: KeySpec = KeySpec::Range {
+    first: 1,
+    last: -1,
+    step: 1,
+};
+
+static COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "get",
+        arity: 2,
+        flags: 0,
+        acl: READ | STRING | FAST,
+        keys: ONE_KEY,
+        handler: string::get,
+    },
+    CommandSpec {
+        name: "set",
+        arity: -3,
+        flags: DENYOOM,
+        acl: WRITE | STRING | SLOW,
+        keys: ONE_KEY,
+        handler: string::set,
+    },
+    CommandSpec {
+        name: "del",
+        arity: -2,
+        flags: 0,
+        acl: WRITE | KEYSPACE | SLOW,
+        keys: ALL_KEYS,
+        handler: string::del,
+    },
+    CommandSpec {
+        name: "setbit",
+        arity: 4,
+        flags: DENYOOM,
+        acl: WRITE | BITMAP | SLOW,
+        keys: ONE_KEY,
+        handler: bitmap::setbit,
+    },
+    CommandSpec {
+        name: "getbit",
+        arity: 3,
+        flags: 0,
+        acl: READ | BITMAP | FAST,
+        keys: ONE_KEY,
+        handler: bitmap::getbit,
+    },
+    CommandSpec {
+        name: "bitcount",
+        arity: -2,
+        flags: 0,
+        acl: READ | BITMAP | SLOW,
+        keys: ONE_KEY,
+        handler: bitmap::bitcount,
+    },
+    CommandSpec {
+        name: "bitop",
+        arity: -4,
+        flags: DENYOOM,
+        acl: WRITE | BITMAP | SLOW,
+        keys: KeySpec::Range {
+            first: 2,
+            last: -1,
+            step: 1,
+        },
+        handler: bitmap::bitop,
+    },
+    CommandSpec {
+        name: "pfadd",
+        arity: -2,
+        flags: DENYOOM,
+        acl: WRITE | HYPERLOGLOG | FAST,
+        keys: ONE_KEY,
+        handler: hll::pfadd,
+    },
+    CommandSpec {
+        name: "pfcount",
+        arity: -2,
+        flags: 0,
+        acl: READ | HYPERLOGLOG | SLOW,
+        keys: ALL_KEYS,
+        handler: hll::pfcount,
+    },
+    CommandSpec {
+        name: "pfmerge",
+        arity: -2,
+        flags: DENYOOM,
+        acl: WRITE | HYPERLOGLOG | SLOW,
+        keys: ALL_KEYS,
+        handler: hll::pfmerge,
+    },
+    CommandSpec {
+        name: "geoadd",
+        arity: -5,
+        flags: DENYOOM,
+        acl: WRITE | GEO | SLOW,
+        keys: ONE_KEY,
+        handler: geo::geoadd,
+    },
+    CommandSpec {
+        name: "geopos",
+        arity: -2,
+        flags: 0,
+        acl: READ | GEO | SLOW,
+        keys: ONE_KEY,
+        handler: geo::geopos,
+    },
+    CommandSpec {
+        name: "geodist",
+        arity: -4,
+        flags: 0,
+        acl: READ | GEO | SLOW,
+        keys: ONE_KEY,
+        handler: geo::geodist,
+    },
+    CommandSpec {
+        name: "geosearch",
+        arity: -7,
+        flags: 0,
+        acl: READ | GEO | SLOW,
+        keys: ONE_KEY,
+        handler: geo::geosearch,
+    },
+    CommandSpec {
+        name: "type",
+        arity: 2,
+        flags: 0,
+        acl: READ | KEYSPACE | FAST,
+        keys: ONE_KEY,
+        handler: keyspace::key_type,
+    },
+    CommandSpec {
+        name: "rename",
+        arity: 3,
+        flags: 0,
+        acl: WRITE | KEYSPACE | SLOW,
+        keys: TWO_KEYS,
+        handler: keyspace::rename,
+    },
+    CommandSpec {
+        name: "renamenx",
+        arity: 3,
+        flags: 0,
+        acl: WRITE | KEYSPACE | FAST,
+        keys: TWO_KEYS,
+        handler: keyspace::renamenx,
+    },
+    CommandSpec {
+        name: "copy",
+        arity: -3,
+        flags: DENYOOM,
+        acl: WRITE | KEYSPACE | SLOW,
+        keys: TWO_KEYS,
+        handler: keyspace::copy,
+    },
+    CommandSpec {
+        name: "object",
+        arity: -2,
+        flags: 0,
+        acl: READ | KEYSPACE | SLOW,
+        keys: KeySpec::Range {
+            first: 2,
+            last: 2,
+            step: 1,
+        },
+        handler: keyspace::object,
+    },
+    CommandSpec {
+        name: "dump",
+        arity: 2,
+        flags: 0,
+        acl: READ | KEYSPACE | SLOW,
+        keys: ONE_KEY,
+        handler: keyspace::dump,
+    },
+    CommandSpec {
+        name: "restore",
+        arity: -4,
+        flags: DENYOOM,
+        acl: WRITE | KEYSPACE | SLOW | DANGEROUS,
+        keys: ONE_KEY,
+        handler: keyspace::restore,
+    },
+    CommandSpec {
+        name: "move",
+        arity: 3,
+        flags: 0,
+        acl: WRITE | KEYSPACE | FAST,
+        keys: ONE_KEY,
+        handler: keyspace::move_key,
+    },
+    CommandSpec {
+        name: "swapdb",
+        arity: 3,
+        flags: 0,
+        acl: WRITE | KEYSPACE | FAST | DANGEROUS,
+        keys: NO_KEYS,
+        handler: keyspace::swapdb,
+    },
+    CommandSpec {
+        name: "flushdb",
+        arity: -1,
+        flags: 0,
+        acl: WRITE | KEYSPACE | SLOW | DANGEROUS,
+        keys: NO_KEYS,
+        handler: keyspace::flushdb,
+    },
+    CommandSpec {
+        name: "flushall",
+        arity: -1,
+        flags: 0,
+        acl: WRITE | KEYSPACE | SLOW | DANGEROUS,
+        keys: NO_KEYS,
+        handler: keyspace::flushall,
+    },
+    CommandSpec {
+        name: "xadd",
+        arity: -5,
+        flags: DENYOOM,
+        acl: WRITE | STREAM | FAST,
+        keys: ONE_KEY,
+        handler: stream::xadd,
+    },
+    CommandSpec {
+        name: "xlen",
+        arity: 2,
+        flags: 0,
+        acl: READ | STREAM | FAST,
+        keys: ONE_KEY,
+        handler: stream::xlen,
+    },
+    CommandSpec {
+        name: "xrange",
+        arity: -4,
+        flags: 0,
+        acl: READ | STREAM | SLOW,
+        keys: ONE_KEY,
+        handler: stream::xrange,
+    },
+    CommandSpec {
+        name: "xrevrange",
+        arity: -4,
+        flags: 0,
+        acl: READ | STREAM | SLOW,
+        keys: ONE_KEY,
+        handler: stream::xrevrange,
+    },
+    CommandSpec {
+        name: "xread",
+        arity: -4,
+        flags: 0,
+        acl: READ | STREAM | SLOW | BLOCKING,
+        keys: KeySpec::Streams,
+        handler: stream::xread,
+    },
+    CommandSpec {
+        name: "xreadgroup",
+        arity: -7,
+        flags: 0,
+        acl: WRITE | STREAM | SLOW | BLOCKING,
+        keys: KeySpec::Streams,
+        handler: stream::xreadgroup,
+    },
+    CommandSpec {
+        name: "xgroup",
+        arity: -4,
+        flags: 0,
+        acl: WRITE | STREAM | SLOW,
+        keys: KeySpec::Range {
+            first: 2,
+            last: 2,
+            step: 1,
+        },
+        handler: stream::xgroup,
+    },
+    CommandSpec {
+        name: "xack",
+        arity: -4,
+        flags: 0,
+        acl: WRITE | STREAM | FAST,
+        keys: ONE_KEY,
+        handler: stream::xack,
+    },
+    CommandSpec {
+        name: "xpending",
+        arity: -3,
+        flags: 0,
+        acl: READ | STREAM | SLOW,
+        keys: ONE_KEY,
+        handler: stream::xpending,
+    },
+    CommandSpec {
+        name: "info",
+        arity: -1,
+        flags: 0,
+        acl: SLOW | DANGEROUS,
+        keys: NO_KEYS,
+        handler: info::info,
+    },
+    CommandSpec {
+        name: "slowlog",
+        arity: -2,
+        flags: 0,
+        acl: ADMIN | SLOW | DANGEROUS,
+        keys: NO_KEYS,
+        handler: slowlog::slowlog,
+    },
+    CommandSpec {
+        name: "latency",
+        arity: -2,
+        flags: 0,
+        acl: ADMIN | SLOW | DANGEROUS,
+        keys: NO_KEYS,
+        handler: info::latency,
+    },
+    CommandSpec {
+        name: "auth",
+        arity: -2,
+        flags: NO_AUTH | NOSCRIPT,
+        acl: FAST | CONNECTION,
+        keys: NO_KEYS,
+        handler: connection::auth,
+    },
+    CommandSpec {
+        name: "hello",
+        arity: -1,
+        flags: NO_AUTH | NOSCRIPT,
+        acl: FAST | CONNECTION,
+        keys: NO_KEYS,
+        handler: connection::hello,
+    },
+    CommandSpec {
+        name: "quit",
+        arity: -1,
+        flags: NO_AUTH | NOSCRIPT | SUBSCRIBED,
+        acl: FAST | CONNECTION,
+        keys: NO_KEYS,
+        handler: connection::quit,
+    },
+    CommandSpec {
+        name: "ping",
+        arity: -1,
+        flags: SUBSCRIBED,
+        acl: FAST | CONNECTION,
+        keys: NO_KEYS,
+        handler: connection::ping,
+    },
+    CommandSpec {
+        name: "echo",
+        arity: 2,
+        flags: 0,
+        acl: FAST | CONNECTION,
+        keys: NO_KEYS,
+        handler: connection::echo,
+    },
+    CommandSpec {
+        name: "monitor",
+        arity: 1,
+        flags: NOSCRIPT,
+        acl: ADMIN | SLOW | DANGEROUS,
+        keys: NO_KEYS,
+        handler: connection::monitor,
+    },
+    CommandSpec {
+        name: "client",
+        arity: -2,
+        flags: NOSCRIPT,
+        acl: ADMIN | SLOW | DANGEROUS | CONNECTION,
+        keys: NO_KEYS,
+        handler: client::client,
+    },
+    CommandSpec {
+        name: "asking",
+        arity: 1,
+        flags: 0,
+        acl: FAST | CONNECTION,
+        keys: NO_KEYS,
+        handler: cluster::asking,
+    },
+    CommandSpec {
+        name: "cluster",
+        arity: -2,
+        flags: 0,
+        acl: SLOW | ADMIN,
+        keys: NO_KEYS,
+        handler: cluster::cluster,
+    },
+    CommandSpec {
+        name: "select",
+        arity: 2,
+        flags: 0,
+        acl: FAST | CONNECTION,
+        keys: NO_KEYS,
+        handler: connection::select,
+    },
+    CommandSpec {
+        name: "acl",
+        arity: -2,
+        flags: 0,
+        acl: ADMIN | SLOW | DANGEROUS,
+        keys: NO_KEYS,
+        handler: acl::acl,
+    },
+    CommandSpec {
+        name: "eval",
+        arity: -3,
+        flags: NOSCRIPT | EXCLUSIVE,
+        acl: SLOW | SCRIPTING,
+        keys: KeySpec::Count { at: 2 },
+        handler: scripting::eval,
+    },
+    CommandSpec {
+        name: "evalsha",
+        arity: -3,
+        flags: NOSCRIPT | EXCLUSIVE,
+        acl: SLOW | SCRIPTING,
+        keys: KeySpec::Count { at: 2 },
+        handler: scripting::evalsha,
+    },
+    CommandSpec {
+        name: "script",
+        arity: -2,
+        flags: NOSCRIPT,
+        acl: SLOW | SCRIPTING,
+        keys: NO_KEYS,
+        handler: scripting::script,
+    },
+    CommandSpec {
+        name: "subscribe",
+        arity: -2,
+        flags: NOSCRIPT | SUBSCRIBED,
+        acl: PUBSUB | SLOW,
+        keys: NO_KEYS,
+        handler: pubsub::subscribe,
+    },
+    CommandSpec {
+        name: "unsubscribe",
+        arity: -1,
+        flags: NOSCRIPT | SUBSCRIBED,
+        acl: PUBSUB | SLOW,
+        keys: NO_KEYS,
+        handler: pubsub::unsubscribe,
+    },
+    CommandSpec {
+        name: "psubscribe",
+        arity: -2,
+        flags: NOSCRIPT | SUBSCRIBED,
+        acl: PUBSUB | SLOW,
+        keys: NO_KEYS,
+        handler: pubsub::psubscribe,
+    },
+    CommandSpec {
+        name: "punsubscribe",
+        arity: -1,
+        flags: NOSCRIPT | SUBSCRIBED,
+        acl: PUBSUB | SLOW,
+        keys: NO_KEYS,
+        handler: pubsub::punsubscribe,
+    },
+    CommandSpec {
+        name: "publish",
+        arity: 3,
+        flags: 0,
+        acl: PUBSUB | FAST,
+        keys: NO_KEYS,
+        handler: pubsub::publish,
+    },
+];
+
+pub(crate) fn table() -> &'static [CommandSpec] {
+    COMMANDS
+}
+
+pub(crate) fn lookup(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|spec| spec.name == name)
+}
+
+// State a command handler can reach.
+pub(crate) struct Context<'a> {
+    pub(crate) shared: &'a Arc<Shared>,
+    pub(crate) session: &'a mut Session,
+}
+
+impl Context<'_> {
+    // The database selected by the connection.
+    pub(crate) fn db(&self) -> &Db {
+        self.shared.dbs.get(self.session.db)
+    }
+
+    // Publish a keyspace event for `key` of the selected database.
+    pub(crate) fn notify(&self, class: u32, event: &str, key: &str) {
+        self.shared
+            .pubsub
+            .notify(class, event, self.session.db, key);
+    }
+}
+
+// A command received from a client, resolved against the command table.
+#[derive(Clone)]
+pub struct Command {
+    spec: &'static CommandSpec,
+    args: Vec<Bytes>,
+}
+
+impl Command {
+    // Parse a command from an array frame of bulk strings.
+    pub fn from_frame(frame: Frame) -> Result<Command> {
+        let parts = match frame {
+            Frame::Array(parts) => parts,
+            frame => {
+                return Err(format!("ERR protocol error; expected array, got {:?}", frame).into())
+            }
+        };
+
+        let mut args = Vec::with_capacity(parts.len());
+        for part in parts {
+            match part {
+                Frame::Bulk(bytes) => args.push(bytes),
+                Frame::Simple(s) => args.push(Bytes::from(s)),
+                frame => {
+                    return Err(format!(
+                        "ERR protocol error; expected bulk string, got {:?}",
+                        frame
+                    )
+                    .into())
+                }
+            }
+        }
+
+        Command::from_args(args)
+    }
+
+    // Resolve a command from its name and arguments.
+    pub(crate) fn from_args(mut args: Vec<Bytes>) -> Result<Command> {
+        if args.is_empty() {
+            return Err("ERR empty command".into());
+        }
+        let name = String::from_utf8_lossy(&args[0]).to_ascii_lowercase();
+        let spec = lookup(&name).ok_or_else(|| format!("ERR unknown command '{}'", name))?;
+
+        let argc = args.len() as i32;
+        if (spec.arity > 0 && argc != spec.arity) || (spec.arity < 0 && argc < -spec.arity) {
+            return Err(format!("ERR wrong number of arguments for '{}' command", name).into());
+        }
+
+        args.remove(0);
+        Ok(Command { spec, args })
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.spec.name
+    }
+
+    // The command name followed by its arguments.
+    pub(crate) fn argv(&self) -> Vec<Bytes> {
+        let mut argv = Vec::with_capacity(self.args.len() + 1);
+        argv.push(Bytes::from_static(self.spec.name.as_bytes()));
+        argv.extend(self.args.iter().cloned());
+        argv
+    }
+
+    // The keys the command operates on.
+    pub(crate) fn keys(&self) -> impl Iterator<Item = &Bytes> {
+        // `args` doesn't include the command name, so argument `i` is
+        // `args[i - 1]`.
+        let argc = self.args.len() as i32 + 1;
+        let (first, last, step) = match self.spec.keys {
+            KeySpec::None => (1, 0, 1),
+            KeySpec::Range { first, last, step } => {
+                let last = if last < 0 { argc + last } else { last };
+                (first as i32, last, step)
+            }
+            KeySpec::Streams => {
+                let streams = self
+                    .args
+                    .iter()
+                    .position(|arg| arg.eq_ignore_ascii_case(b"streams"))
+                    .map_or(argc, |i| i as i32 + 2);
+                let count = (argc - streams) / 2;
+                (streams, streams + count - 1, 1)
+            }
+            KeySpec::Count { at } => {
+                let count = self
+                    .args
+                    .get(at - 1)
+                    .and_then(|count| std::str::from_utf8(count).ok())
+                    .and_then(|count| count.parse::<usize>().ok())
+                    .unwrap_or(0)
+                    // checked by the command itself, e.g. EVAL, the keys
+                    // are only the arguments there are.
+                    .min(self.args.len()) as i32;
+                (at as i32 + 1, at as i32 + count, 1)
+            }
+        };
+
+        (first..=last.min(argc - 1))
+            .step_by(step)
+            .map(move |i| &self.args[i as usize - 1])
+    }
+
+    // Whether the command may modify data, it waits during CLIENT PAUSE
+    // WRITE.
+    pub(crate) fn is_write(&self) -> bool {
+        self.spec.acl & (WRITE | SCRIPTING) != 0
+    }
+
+    pub(crate) fn allowed_in_scripts(&self) -> bool {
+        self.spec.flags & NOSCRIPT == 0
+    }
+
+    pub(crate) async fn execute(self, ctx: &mut Context<'_>) -> Frame {
+        // commands share the lock, exclusive ones like scripts have it for
+        // themselves. Clients waiting for it yield rather than block their
+        // thread.
+        let shared = ctx.shared.clone();
+        if self.spec.flags & EXCLUSIVE == 0 {
+            let _guard = shared.exec_lock.read().await;
+            return self.run(ctx);
+        }
+
+        let _guard = shared.exec_lock.write().await;
+        // a script may run until its time limit, the other tasks of this
+        // thread are moved to another one meanwhile.
+        match Handle::current().runtime_flavor() {
+            RuntimeFlavor::MultiThread => block_in_place(|| self.run(ctx)),
+            _ => self.run(ctx),
+        }
+    }
+
+    // Same as `execute`, for callers already holding the lock, e.g. scripts.
+    pub(crate) fn run(self, ctx: &mut Context<'_>) -> Frame {
+        if let Err(e) = self.check_permissions(ctx) {
+            return Frame::Error(e);
+        }
+
+        // ASKING only holds for the command right after it.
+        let asking = std::mem::take(&mut ctx.session.asking);
+        if let Some(cluster) = &ctx.shared.cluster {
+            let db = ctx.db();
+            let exists = |key: &Bytes| std::str::from_utf8(key).is_ok_and(|key| db.contains(key));
+            if let Err(redirect) = cluster.route(self.keys(), exists, asking) {
+                return Frame::Error(redirect.to_string());
+            }
+        }
+
+        if ctx.session.subscriptions() > 0 && self.spec.flags & SUBSCRIBED == 0 {
+            return Frame::Error(format!(
+                "ERR Can't execute '{}': only (P)SUBSCRIBE / (P)UNSUBSCRIBE / QUIT are allowed in this context",
+                self.spec.name
+            ));
+        }
+
+        if ctx.session.monitoring && self.spec.name != "quit" {
+            return Frame::Error(format!(
+                "ERR Can't execute '{}': only QUIT is allowed while monitoring",
+                self.spec.name
+            ));
+        }
+
+        let config = &ctx.shared.config;
+        if self.spec.flags & DENYOOM != 0
+            && config.maxmemory > 0
+            && ctx
+                .shared
+                .dbs
+                .make_room(config.maxmemory, config.maxmemory_policy)
+                .is_err()
+        {
+            return Frame::Error(
+                "OOM command not allowed when used memory > 'maxmemory'".to_string(),
+            );
+        }
+
+        // only the commands that get to run are shown, like redis. AUTH
+        // and HELLO carry passwords.
+        let monitors = &ctx.shared.monitors;
+        if monitors.active()
+            && !ctx.session.retrying
+            && !matches!(self.spec.name, "auth" | "hello" | "monitor")
+        {
+            let session = &ctx.session;
+            monitors.feed(session.id, session.db, &session.addr, &self.argv());
+        }
+
+        // kept for the clients tracking keys, the arguments are consumed.
+        let tracking = ctx.shared.tracking.clone();
+        let keys: Option<Vec<Bytes>> = tracking.active().then(|| self.keys().cloned().collect());
+        let write = self.is_write();
+        // keys are read while others may write them: recorded before, a
+        // write in between is invalidated after the reply rather than missed.
+        if let (Some(keys), false) = (&keys, write) {
+            tracking.read(ctx.session.id, keys);
+        }
+
+        let mut parse = Parse::new(self.args);
+
+        let frame = match (self.spec.handler)(ctx, &mut parse) {
+            Ok(frame) => frame,
+            Err(e) => return Frame::Error(e.to_string()),
+        };
+
+        if let (Some(keys), true) = (&keys, write) {
+            tracking.invalidate(keys, Some(ctx.session.id));
+        }
+        frame
+    }
+
+    fn check_permissions(&self, ctx: &Context<'_>) -> std::result::Result<(), String> {
+        if self.spec.flags & NO_AUTH != 0 {
+            return Ok(());
+        }
+
+        match &ctx.session.user {
+            Some(user) => ctx.shared.acl.check(user, self.spec, self.keys()),
+            None => Err("NOAUTH Authentication required.".to_string()),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use super::Context;
+use crate::parse::Parse;
+
+use bytes::Bytes;
+use mini_redis::{Frame, Result};
+
+// SUBSCRIBE channel [channel ...]
+pub(super) fn subscribe(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    while parse.remaining() > 0 {
+        let channel = parse.next_bytes()?;
+        let session = &mut *ctx.session;
+        if session.channels.insert(channel.clone()) {
+            ctx.shared
+                .pubsub
+                .subscribe(channel.clone(), session.id, &session.push);
+        }
+        confirm(ctx, "subscribe", Some(&channel));
+    }
+
+    Ok(last_reply(ctx))
+}
+
+// UNSUBSCRIBE [channel ...], every channel if none is given.
+pub(super) fn unsubscribe(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let mut channels = Vec::new();
+    while parse.remaining() > 0 {
+        channels.push(parse.next_bytes()?);
+    }
+    if channels.is_empty() {
+        channels = ctx.session.channels.iter().cloned().collect();
+    }
+    if channels.is_empty() {
+        confirm(ctx, "unsubscribe", None);
+    }
+
+    for channel in channels {
+        if ctx.session.channels.remove(&channel) {
+            ctx.shared.pubsub.unsubscribe(&channel, ctx.session.id);
+        }
+        confirm(ctx, "unsubscribe", Some(&channel));
+    }
+
+    Ok(last_reply(ctx))
+}
+
+// PSUBSCRIBE pattern [pattern ...]
+pub(super) fn psubscribe(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    while parse.remaining() > 0 {
+        let pattern = parse.next_bytes()?;
+        let session = &mut *ctx.session;
+        if session.patterns.insert(pattern.clone()) {
+            ctx.shared
+                .pubsub
+                .psubscribe(pattern.clone(), session.id, &session.push);
+        }
+        confirm(ctx, "psubscribe", Some(&pattern));
+    }
+
+    Ok(last_reply(ctx))
+}
+
+// PUNSUBSCRIBE [pattern ...], every pattern if none is given.
+pub(super) fn punsubscribe(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let mut patterns = Vec::new();
+    while parse.remaining() > 0 {
+        patterns.push(parse.next_bytes()?);
+    }
+    if patterns.is_empty() {
+        patterns = ctx.session.patterns.iter().cloned().collect();
+    }
+    if patterns.is_empty() {
+        confirm(ctx, "punsubscribe", None);
+    }
+
+    for pattern in patterns {
+        if ctx.session.patterns.remove(&pattern) {
+            ctx.shared.pubsub.punsubscribe(&pattern, ctx.session.id);
+        }
+        confirm(ctx, "punsubscribe", Some(&pattern));
+    }
+
+    Ok(last_reply(ctx))
+}
+
+// PUBLISH channel message
+pub(super) fn publish(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let channel = parse.next_bytes()?;
+    let message = parse.next_bytes()?;
+
+    let received = ctx.shared.pubsub.publish(&channel, &message);
+    Ok(Frame::Integer(received as u64))
+}
+
+// Queue a `[kind, name, count]` confirmation, one per channel or pattern.
+fn confirm(ctx: &mut Context<'_>, kind: &'static str, name: Option<&Bytes>) {
+    let name = match name {
+        Some(name) => Frame::Bulk(name.clone()),
+        None => Frame::Null,
+    };
+    let count = ctx.session.subscriptions() as u64;
+
+    ctx.session.replies.push(Frame::Array(vec![
+        Frame::Bulk(Bytes::from_static(kind.as_bytes())),
+        name,
+        Frame::Integer(count),
+    ]));
+}
+
+// The last confirmation is the command reply, the others are written
+// before it.
+fn last_reply(ctx: &mut Context<'_>) -> Frame {
+    ctx.session.replies.pop().unwrap_or(Frame::Null)
+}
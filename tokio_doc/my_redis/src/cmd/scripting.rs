@@ -0,0 +1,69 @@
+use super::Context;
+use crate::parse::Parse;
+
+use bytes::Bytes;
+use mini_redis::{Frame, Result};
+use rhai::AST;
+
+// EVAL script numkeys [key ...] [arg ...]
+pub(super) fn eval(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let source = parse.next_string()?;
+    let (_, ast) = ctx.shared.scripts.load(&source)?;
+
+    run(ctx, &ast, parse)
+}
+
+// EVALSHA sha1 numkeys [key ...] [arg ...]
+pub(super) fn evalsha(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let sha = parse.next_string()?;
+    let ast = ctx
+        .shared
+        .scripts
+        .get(&sha)
+        .ok_or("NOSCRIPT No matching script. Please use EVAL.")?;
+
+    run(ctx, &ast, parse)
+}
+
+// SCRIPT LOAD script | EXISTS sha1 [sha1 ...] | FLUSH
+pub(super) fn script(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let scripts = &ctx.shared.scripts;
+
+    match parse.next_option()?.as_str() {
+        "load" => {
+            let (sha, _) = scripts.load(&parse.next_string()?)?;
+            Ok(Frame::Bulk(Bytes::from(sha)))
+        }
+        "exists" => {
+            let mut exists = Vec::new();
+            while parse.remaining() > 0 {
+                let found = scripts.exists(&parse.next_string()?);
+                exists.push(Frame::Integer(found as u64));
+            }
+            Ok(Frame::Array(exists))
+        }
+        "flush" => {
+            scripts.flush();
+            Ok(Frame::Simple("OK".to_string()))
+        }
+        sub => Err(format!("ERR unknown subcommand '{}'", sub).into()),
+    }
+}
+
+fn run(ctx: &mut Context<'_>, ast: &AST, parse: &mut Parse) -> Result<Frame> {
+    let numkeys = parse.next_int()? as usize;
+    if numkeys > parse.remaining() {
+        return Err("ERR Number of keys can't be greater than number of args".into());
+    }
+
+    let mut keys = Vec::with_capacity(numkeys);
+    for _ in 0..numkeys {
+        keys.push(parse.next_bytes()?);
+    }
+    let mut argv = Vec::with_capacity(parse.remaining());
+    while parse.remaining() > 0 {
+        argv.push(parse.next_bytes()?);
+    }
+
+    ctx.shared.scripts.run(ast, ctx, keys, argv)
+}
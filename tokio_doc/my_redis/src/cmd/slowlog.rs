@@ -0,0 +1,42 @@
+use super::Context;
+use crate::parse::Parse;
+
+use bytes::Bytes;
+use mini_redis::{Frame, Result};
+
+// SLOWLOG GET [count] | LEN | RESET
+pub(super) fn slowlog(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let slowlog = &ctx.shared.slowlog;
+
+    match parse.next_option()?.as_str() {
+        "get" => {
+            // 10 entries by default, -1 for all of them.
+            let count = match parse.remaining() {
+                0 => 10,
+                _ => match parse.next_string()?.parse::<i64>() {
+                    Ok(-1) => usize::MAX,
+                    Ok(count) if count >= 0 => count as usize,
+                    _ => return Err("ERR count should be greater than or equal to -1".into()),
+                },
+            };
+
+            let entries = slowlog.get(count).into_iter().map(|entry| {
+                Frame::Array(vec![
+                    Frame::Integer(entry.id),
+                    Frame::Integer(entry.timestamp),
+                    Frame::Integer(entry.duration.as_micros() as u64),
+                    Frame::Array(entry.args.into_iter().map(Frame::Bulk).collect()),
+                    Frame::Bulk(Bytes::from(entry.addr)),
+                    Frame::Bulk(Bytes::from(entry.name)),
+                ])
+            });
+            Ok(Frame::Array(entries.collect()))
+        }
+        "len" => Ok(Frame::Integer(slowlog.len() as u64)),
+        "reset" => {
+            slowlog.reset();
+            Ok(Frame::Simple("OK".to_string()))
+        }
+        sub => Err(format!("ERR unknown subcommand '{}'", sub).into()),
+    }
+}
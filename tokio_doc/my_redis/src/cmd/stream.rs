@@ -0,0 +1,506 @@
+use super::{Command, Context};
+use crate::blocking::Blocked;
+use crate::parse::Parse;
+use crate::pubsub::KeyspaceEvents;
+use crate::stream::{Fields, NewId, StreamId};
+
+use bytes::Bytes;
+use mini_redis::{Frame, Result};
+use std::time::{Duration, Instant};
+
+// XADD key [NOMKSTREAM] [MAXLEN [=|~] count] <* | id> field value [field value ...]
+pub(super) fn xadd(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let key = parse.next_string()?;
+
+    let mut create = true;
+    let mut maxlen = None;
+    let id = loop {
+        let arg = parse.next_string()?;
+        match arg.to_ascii_lowercase().as_str() {
+            "nomkstream" => create = false,
+            "maxlen" => {
+                let mut count = parse.next_string()?;
+                // trimming is always exact.
+                if count == "=" || count == "~" {
+                    count = parse.next_string()?;
+                }
+                maxlen = Some(parse_count(&count)?);
+            }
+            _ => break NewId::parse(&arg)?,
+        }
+    };
+    // checked before a stream is created for nothing.
+    if let NewId::Explicit(StreamId::MIN) = id {
+        return Err("ERR The ID specified in XADD must be greater than 0-0".into());
+    }
+
+    if parse.remaining() == 0 || !parse.remaining().is_multiple_of(2) {
+        return Err("ERR wrong number of arguments for 'xadd' command".into());
+    }
+    let mut fields = Vec::with_capacity(parse.remaining() / 2);
+    while parse.remaining() > 0 {
+        fields.push((parse.next_bytes()?, parse.next_bytes()?));
+    }
+
+    let added = ctx.db().stream(&key, create, |stream| {
+        let id = stream.add(id, fields)?;
+        let trimmed = maxlen.map_or(0, |maxlen| stream.trim(maxlen));
+        Ok::<_, String>((id, trimmed))
+    })?;
+    let (id, trimmed) = match added {
+        Some(added) => added?,
+        None => return Ok(Frame::Null),
+    };
+
+    ctx.notify(KeyspaceEvents::STREAM, "xadd", &key);
+    if trimmed > 0 {
+        ctx.notify(KeyspaceEvents::STREAM, "xtrim", &key);
+    }
+    ctx.shared.waker.wake();
+
+    Ok(Frame::Bulk(Bytes::from(id.to_string())))
+}
+
+// XLEN key
+pub(super) fn xlen(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let key = parse.next_string()?;
+
+    let len = ctx.db().stream(&key, false, |stream| stream.len())?;
+    Ok(Frame::Integer(len.unwrap_or(0) as u64))
+}
+
+// XRANGE key start end [COUNT count]
+pub(super) fn xrange(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    range(ctx, parse, false)
+}
+
+// XREVRANGE key end start [COUNT count]
+pub(super) fn xrevrange(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    range(ctx, parse, true)
+}
+
+fn range(ctx: &mut Context<'_>, parse: &mut Parse, rev: bool) -> Result<Frame> {
+    let key = parse.next_string()?;
+    let (first, second) = (parse.next_string()?, parse.next_string()?);
+    let (start, end) = if rev {
+        (second, first)
+    } else {
+        (first, second)
+    };
+    let start = range_bound(&start, true)?;
+    let end = range_bound(&end, false)?;
+
+    let mut count = None;
+    if parse.remaining() > 0 {
+        if parse.next_option()? != "count" || parse.remaining() != 1 {
+            return Err("ERR syntax error".into());
+        }
+        count = Some(parse.next_int()? as usize);
+    }
+
+    let entries = match (start, end) {
+        (Some(start), Some(end)) => ctx
+            .db()
+            .stream(&key, false, |stream| stream.range(start, end, count, rev))?
+            .unwrap_or_default(),
+        // an exclusive bound past the first or last possible ID.
+        _ => Vec::new(),
+    };
+
+    Ok(entries_frame(
+        entries.into_iter().map(|(id, f)| (id, Some(f))),
+    ))
+}
+
+// `-`, `+`, an ID or an exclusive `(id`. `None` if nothing can be in range.
+fn range_bound(s: &str, start: bool) -> Result<Option<StreamId>> {
+    match s {
+        "-" => return Ok(Some(StreamId::MIN)),
+        "+" => return Ok(Some(StreamId::MAX)),
+        _ => {}
+    }
+
+    let seq = if start { 0 } else { u64::MAX };
+    match s.strip_prefix('(') {
+        Some(s) => {
+            let id = StreamId::parse(s, seq)?;
+            Ok(if start { id.next() } else { id.prev() })
+        }
+        None => Ok(Some(StreamId::parse(s, seq)?)),
+    }
+}
+
+// XREAD [COUNT count] [BLOCK milliseconds] STREAMS key [key ...] id [id ...]
+pub(super) fn xread(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let mut count = None;
+    let mut block = None;
+    loop {
+        match parse.next_option()?.as_str() {
+            "count" => count = Some(parse.next_int()? as usize),
+            "block" => block = Some(parse.next_int()?),
+            "streams" => break,
+            _ => return Err("ERR syntax error".into()),
+        }
+    }
+    let (keys, ids) = streams(parse, "xread")?;
+
+    // read before looking for entries, so that an entry added meanwhile
+    // wakes this client up.
+    let version = ctx.shared.waker.version();
+
+    let mut resolved = Vec::with_capacity(ids.len());
+    for (key, id) in keys.iter().zip(&ids) {
+        let id = match id.as_str() {
+            // only the entries added from now on.
+            "$" => ctx
+                .db()
+                .stream(key, false, |stream| stream.last_id())?
+                .unwrap_or(StreamId::MIN),
+            id => StreamId::parse(id, 0)?,
+        };
+        resolved.push(id);
+    }
+
+    let mut reply = Vec::new();
+    for (key, id) in keys.iter().zip(&resolved) {
+        let entries = ctx
+            .db()
+            .stream(key, false, |stream| stream.after(*id, count))?
+            .unwrap_or_default();
+        if !entries.is_empty() {
+            reply.push(stream_frame(
+                key,
+                entries.into_iter().map(|(id, f)| (id, Some(f))),
+            ));
+        }
+    }
+    if !reply.is_empty() {
+        return Ok(Frame::Array(reply));
+    }
+
+    if let Some(block) = block {
+        // `$` means the IDs seen now, not when retrying.
+        let mut args = vec!["xread".to_string()];
+        if let Some(count) = count {
+            args.extend(["count".to_string(), count.to_string()]);
+        }
+        args.extend([
+            "block".to_string(),
+            block.to_string(),
+            "streams".to_string(),
+        ]);
+        args.extend(keys);
+        args.extend(resolved.iter().map(|id| id.to_string()));
+        ctx.session.blocked = Some(blocked(args, version, block)?);
+    }
+
+    Ok(Frame::Null)
+}
+
+// XREADGROUP GROUP group consumer [COUNT count] [BLOCK milliseconds] [NOACK]
+//   STREAMS key [key ...] id [id ...]
+pub(super) fn xreadgroup(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    if parse.next_option()? != "group" {
+        return Err("ERR syntax error".into());
+    }
+    let group = parse.next_string()?;
+    let consumer = parse.next_string()?;
+
+    let mut count = None;
+    let mut block = None;
+    let mut noack = false;
+    loop {
+        match parse.next_option()?.as_str() {
+            "count" => count = Some(parse.next_int()? as usize),
+            "block" => block = Some(parse.next_int()?),
+            "noack" => noack = true,
+            "streams" => break,
+            _ => return Err("ERR syntax error".into()),
+        }
+    }
+    let (keys, ids) = streams(parse, "xreadgroup")?;
+
+    let version = ctx.shared.waker.version();
+
+    let mut reply = Vec::new();
+    for (key, id) in keys.iter().zip(&ids) {
+        // `>` for new entries, an ID for the consumer's pending ones.
+        let id = match id.as_str() {
+            ">" => None,
+            id => Some(StreamId::parse(id, 0)?),
+        };
+
+        let read = ctx.db().stream(key, false, |stream| {
+            stream.read_group(&group, &consumer, id, count, noack)
+        })?;
+        let entries = read.ok_or_else(|| {
+            format!(
+                "NOGROUP No such key '{}' or consumer group '{}' in XREADGROUP with GROUP option",
+                key, group
+            )
+        })??;
+
+        // the history of a consumer is replied even when empty.
+        if !entries.is_empty() || id.is_some() {
+            reply.push(stream_frame(key, entries.into_iter()));
+        }
+    }
+    if !reply.is_empty() {
+        return Ok(Frame::Array(reply));
+    }
+
+    if let Some(block) = block {
+        let mut args = vec![
+            "xreadgroup".to_string(),
+            "group".to_string(),
+            group,
+            consumer,
+        ];
+        if let Some(count) = count {
+            args.extend(["count".to_string(), count.to_string()]);
+        }
+        if noack {
+            args.push("noack".to_string());
+        }
+        args.extend([
+            "block".to_string(),
+            block.to_string(),
+            "streams".to_string(),
+        ]);
+        args.extend(keys);
+        args.extend(ids);
+        ctx.session.blocked = Some(blocked(args, version, block)?);
+    }
+
+    Ok(Frame::Null)
+}
+
+// XGROUP CREATE key group <id | $> [MKSTREAM]
+//      | DESTROY key group
+//      | CREATECONSUMER key group consumer
+//      | DELCONSUMER key group consumer
+pub(super) fn xgroup(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let sub = parse.next_option()?;
+    let key = parse.next_string()?;
+    let group = parse.next_string()?;
+
+    let missing = || {
+        "ERR The XGROUP subcommand requires the key to exist. Note that for CREATE you may \
+         want to use the MKSTREAM option to create an empty stream automatically."
+    };
+
+    match sub.as_str() {
+        "create" => {
+            let id = parse.next_string()?;
+            let mkstream = match parse.remaining() {
+                0 => false,
+                1 if parse.next_option()? == "mkstream" => true,
+                _ => return Err("ERR syntax error".into()),
+            };
+            let id = match id.as_str() {
+                "$" => None,
+                id => Some(StreamId::parse(id, 0)?),
+            };
+
+            ctx.db()
+                .stream(&key, mkstream, |stream| {
+                    let id = id.unwrap_or(stream.last_id());
+                    stream.create_group(&group, id)
+                })?
+                .ok_or_else(missing)??;
+            ctx.notify(KeyspaceEvents::STREAM, "xgroup-create", &key);
+            Ok(Frame::Simple("OK".to_string()))
+        }
+        "destroy" => {
+            let destroyed = ctx
+                .db()
+                .stream(&key, false, |stream| stream.destroy_group(&group))?
+                .ok_or_else(missing)?;
+            if destroyed {
+                ctx.notify(KeyspaceEvents::STREAM, "xgroup-destroy", &key);
+            }
+            Ok(Frame::Integer(destroyed as u64))
+        }
+        "createconsumer" => {
+            let consumer = parse.next_string()?;
+            let created = ctx
+                .db()
+                .stream(&key, false, |stream| {
+                    stream.create_consumer(&group, &consumer)
+                })?
+                .ok_or_else(missing)??;
+            Ok(Frame::Integer(created as u64))
+        }
+        "delconsumer" => {
+            let consumer = parse.next_string()?;
+            let pending = ctx
+                .db()
+                .stream(&key, false, |stream| {
+                    stream.delete_consumer(&group, &consumer)
+                })?
+                .ok_or_else(missing)??;
+            Ok(Frame::Integer(pending as u64))
+        }
+        sub => Err(format!("ERR unknown subcommand '{}'", sub).into()),
+    }
+}
+
+// XACK key group id [id ...]
+pub(super) fn xack(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let key = parse.next_string()?;
+    let group = parse.next_string()?;
+    let mut ids = Vec::new();
+    while parse.remaining() > 0 {
+        ids.push(StreamId::parse(&parse.next_string()?, 0)?);
+    }
+
+    let acked = ctx
+        .db()
+        .stream(&key, false, |stream| stream.ack(&group, &ids))?
+        .transpose()?;
+    Ok(Frame::Integer(acked.unwrap_or(0) as u64))
+}
+
+// XPENDING key group [start end count [consumer]]
+pub(super) fn xpending(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let key = parse.next_string()?;
+    let group = parse.next_string()?;
+    let no_group = || {
+        format!(
+            "NOGROUP No such key '{}' or consumer group '{}'",
+            key, group
+        )
+    };
+
+    if parse.remaining() == 0 {
+        let summary = ctx
+            .db()
+            .stream(&key, false, |stream| stream.pending_summary(&group))?
+            .ok_or_else(no_group)??;
+
+        let id = |id: Option<StreamId>| match id {
+            Some(id) => Frame::Bulk(Bytes::from(id.to_string())),
+            None => Frame::Null,
+        };
+        let consumers = if summary.consumers.is_empty() {
+            Frame::Null
+        } else {
+            Frame::Array(
+                summary
+                    .consumers
+                    .into_iter()
+                    .map(|(name, count)| {
+                        Frame::Array(vec![
+                            Frame::Bulk(Bytes::from(name)),
+                            Frame::Bulk(Bytes::from(count.to_string())),
+                        ])
+                    })
+                    .collect(),
+            )
+        };
+        return Ok(Frame::Array(vec![
+            Frame::Integer(summary.count as u64),
+            id(summary.first),
+            id(summary.last),
+            consumers,
+        ]));
+    }
+
+    let start = range_bound(&parse.next_string()?, true)?;
+    let end = range_bound(&parse.next_string()?, false)?;
+    let count = parse.next_int()? as usize;
+    let consumer = match parse.remaining() {
+        0 => None,
+        1 => Some(parse.next_string()?),
+        _ => return Err("ERR syntax error".into()),
+    };
+
+    let (start, end) = match (start, end) {
+        (Some(start), Some(end)) => (start, end),
+        _ => (StreamId::MAX, StreamId::MIN),
+    };
+    let pending = ctx
+        .db()
+        .stream(&key, false, |stream| {
+            stream.pending_range(&group, start, end, count, consumer.as_deref())
+        })?
+        .ok_or_else(no_group)??;
+
+    Ok(Frame::Array(
+        pending
+            .into_iter()
+            .map(|(id, consumer, idle, deliveries)| {
+                Frame::Array(vec![
+                    Frame::Bulk(Bytes::from(id.to_string())),
+                    Frame::Bulk(Bytes::from(consumer)),
+                    Frame::Integer(idle.as_millis() as u64),
+                    Frame::Integer(deliveries),
+                ])
+            })
+            .collect(),
+    ))
+}
+
+// The keys and IDs following STREAMS, as many of each.
+fn streams(parse: &mut Parse, command: &str) -> Result<(Vec<String>, Vec<String>)> {
+    let mut args = Vec::with_capacity(parse.remaining());
+    while parse.remaining() > 0 {
+        args.push(parse.next_string()?);
+    }
+    if args.is_empty() || !args.len().is_multiple_of(2) {
+        return Err(format!(
+            "ERR Unbalanced '{}' list of streams: for each stream key an ID or '$' must be specified.",
+            command
+        )
+        .into());
+    }
+
+    let ids = args.split_off(args.len() / 2);
+    Ok((args, ids))
+}
+
+fn blocked(args: Vec<String>, version: u64, block: u64) -> Result<Blocked> {
+    let retry = Command::from_args(args.into_iter().map(Bytes::from).collect())?;
+    // BLOCK 0 waits forever.
+    let deadline = (block > 0).then(|| Instant::now() + Duration::from_millis(block));
+
+    Ok(Blocked {
+        retry,
+        version,
+        deadline,
+    })
+}
+
+fn parse_count(s: &str) -> Result<usize> {
+    s.parse()
+        .map_err(|_| "ERR value is not an integer or out of range".into())
+}
+
+// `[key, entries]`, one stream of an XREAD reply.
+fn stream_frame(key: &str, entries: impl Iterator<Item = (StreamId, Option<Fields>)>) -> Frame {
+    Frame::Array(vec![
+        Frame::Bulk(Bytes::copy_from_slice(key.as_bytes())),
+        entries_frame(entries),
+    ])
+}
+
+// `[[id, [field, value, ...]], ...]`, with a nil in place of the fields of
+// entries trimmed since they were delivered.
+fn entries_frame(entries: impl Iterator<Item = (StreamId, Option<Fields>)>) -> Frame {
+    Frame::Array(
+        entries
+            .map(|(id, fields)| {
+                let fields = match fields {
+                    Some(fields) => Frame::Array(
+                        fields
+                            .into_iter()
+                            .flat_map(|(f, v)| [Frame::Bulk(f), Frame::Bulk(v)])
+                            .collect(),
+                    ),
+                    None => Frame::Null,
+                };
+                Frame::Array(vec![Frame::Bulk(Bytes::from(id.to_string())), fields])
+            })
+            .collect(),
+    )
+}
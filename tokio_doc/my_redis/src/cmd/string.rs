@@ -0,0 +1,58 @@
+use super::Context;
+use crate::parse::Parse;
+use crate::pubsub::KeyspaceEvents;
+
+use mini_redis::{Frame, Result};
+use std::time::Duration;
+
+pub(super) fn get(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let key = parse.next_string()?;
+
+    match ctx.db().get(&key)? {
+        Some(value) => Ok(Frame::Bulk(value)),
+        None => Ok(Frame::Null),
+    }
+}
+
+pub(super) fn set(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let key = parse.next_string()?;
+    let value = parse.next_bytes()?;
+
+    let mut expire = None;
+    while parse.remaining() > 0 {
+        let millis = match parse.next_option()?.as_str() {
+            "ex" => 1000,
+            "px" => 1,
+            _ => return Err("ERR syntax error".into()),
+        };
+        if expire.is_some() {
+            return Err("ERR syntax error".into());
+        }
+
+        match parse.next_int()?.checked_mul(millis) {
+            Some(ttl) if ttl > 0 => expire = Some(Duration::from_millis(ttl)),
+            _ => return Err("ERR invalid expire time in 'set' command".into()),
+        }
+    }
+
+    ctx.db().set(key.clone(), value, expire);
+
+    ctx.notify(KeyspaceEvents::STRING, "set", &key);
+    if expire.is_some() {
+        ctx.notify(KeyspaceEvents::GENERIC, "expire", &key);
+    }
+    Ok(Frame::Simple("OK".to_string()))
+}
+
+pub(super) fn del(ctx: &mut Context<'_>, parse: &mut Parse) -> Result<Frame> {
+    let mut deleted = 0;
+    while parse.remaining() > 0 {
+        let key = parse.next_string()?;
+        if ctx.db().del(&key) {
+            ctx.notify(KeyspaceEvents::GENERIC, "del", &key);
+            deleted += 1;
+        }
+    }
+
+    Ok(Frame::Integer(deleted))
+}
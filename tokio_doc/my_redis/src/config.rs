@@ -0,0 +1,266 @@
+use crate::evict::EvictionPolicy;
+use crate::logging::LogFormat;
+use crate::pubsub::KeyspaceEvents;
+
+use mini_redis::Result;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+// Server settings. Each setting has a redis-style name, so the same
+// `set` method serves both the command line (`--port 6380`) and any
+// runtime configuration.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub bind: String,
+    pub port: u16,
+    // TLS listener, enabled by setting a port. The certificate and key are
+    // PEM files.
+    pub tls_port: Option<u16>,
+    pub tls_cert_file: Option<String>,
+    pub tls_key_file: Option<String>,
+    // path of a unix socket to listen on, and the permissions of the socket
+    // file, e.g. 700.
+    pub unixsocket: Option<String>,
+    pub unixsocketperm: Option<u32>,
+    // port of the Prometheus text endpoint, disabled if `None`.
+    pub metrics_port: Option<u16>,
+    // memory limit in bytes for the keyspace, 0 means no limit.
+    pub maxmemory: usize,
+    pub maxmemory_policy: EvictionPolicy,
+    // password of the default user. Without it, connections are logged in
+    // as the default user right away.
+    pub requirepass: Option<String>,
+    // ACL users, as `name rule...` lines, e.g. "alice on >pw ~cache:* +@read".
+    pub users: Vec<String>,
+    // scripts running longer are aborted.
+    pub script_time_limit: Duration,
+    // number of databases, selected with SELECT.
+    pub databases: usize,
+    // keyspace events published to pub/sub, none by default.
+    pub notify_keyspace_events: KeyspaceEvents,
+    // commands running longer go to the slow log, `None` disables it. Set
+    // in microseconds, a negative value disables it.
+    pub slowlog_log_slower_than: Option<Duration>,
+    // number of entries the slow log keeps.
+    pub slowlog_max_len: usize,
+    // cluster mode: keys are sharded over the `cluster_nodes`, as
+    // `id host:port slot-range...` lines, this node being the one with
+    // `cluster_node_id`.
+    pub cluster_enabled: bool,
+    pub cluster_node_id: Option<String>,
+    pub cluster_nodes: Vec<String>,
+    // connections silent for longer are closed, set in seconds, 0 for
+    // never. Subscribed clients aren't subject to it.
+    pub timeout: Option<Duration>,
+    // connections over the limit are refused with an error.
+    pub maxclients: usize,
+    // interval of the TCP keepalive probes, set in seconds, 0 disables
+    // them.
+    pub tcp_keepalive: Option<Duration>,
+    // "text" or "json", see `logging::init`.
+    pub log_format: LogFormat,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            bind: "127.0.0.1".to_string(),
+            port: crate::DEFAULT_PORT,
+            tls_port: None,
+            tls_cert_file: None,
+            tls_key_file: None,
+            unixsocket: None,
+            unixsocketperm: None,
+            metrics_port: None,
+            maxmemory: 0,
+            maxmemory_policy: EvictionPolicy::default(),
+            requirepass: None,
+            users: Vec::new(),
+            script_time_limit: Duration::from_secs(5),
+            databases: 16,
+            notify_keyspace_events: KeyspaceEvents::default(),
+            slowlog_log_slower_than: Some(Duration::from_millis(10)),
+            slowlog_max_len: 128,
+            cluster_enabled: false,
+            cluster_node_id: None,
+            cluster_nodes: Vec::new(),
+            timeout: None,
+            maxclients: 10000,
+            tcp_keepalive: Some(Duration::from_secs(300)),
+            log_format: LogFormat::default(),
+        }
+    }
+}
+
+impl Config {
+    // Build a config from `--name value` pairs, e.g. the process arguments
+    // with the program name skipped.
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Config> {
+        let mut config = Config::default();
+
+        while let Some(arg) = args.next() {
+            let name = arg
+                .strip_prefix("--")
+                .ok_or_else(|| format!("unexpected argument '{}'", arg))?;
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for '{}'", arg))?;
+            config.set(name, &value)?;
+        }
+
+        Ok(config)
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        match name {
+            "bind" => self.bind = value.to_string(),
+            "port" => self.port = parse(name, value)?,
+            "tls-port" => self.tls_port = Some(parse(name, value)?),
+            "tls-cert-file" => self.tls_cert_file = Some(value.to_string()),
+            "tls-key-file" => self.tls_key_file = Some(value.to_string()),
+            "unixsocket" => self.unixsocket = Some(value.to_string()),
+            "unixsocketperm" => {
+                let mode = u32::from_str_radix(value, 8)
+                    .map_err(|_| format!("invalid value '{}' for '{}'", value, name))?;
+                self.unixsocketperm = Some(mode);
+            }
+            "metrics-port" => self.metrics_port = Some(parse(name, value)?),
+            "maxmemory" => self.maxmemory = parse_memory(name, value)?,
+            "maxmemory-policy" => self.maxmemory_policy = parse(name, value)?,
+            "requirepass" => self.requirepass = Some(value.to_string()),
+            // may be given several times, one user each.
+            "user" => self.users.push(value.to_string()),
+            "script-time-limit" => {
+                self.script_time_limit = Duration::from_millis(parse(name, value)?)
+            }
+            "databases" => self.databases = parse(name, value)?,
+            "notify-keyspace-events" => self.notify_keyspace_events = parse(name, value)?,
+            "slowlog-log-slower-than" => {
+                let usec: i64 = parse(name, value)?;
+                self.slowlog_log_slower_than =
+                    (usec >= 0).then(|| Duration::from_micros(usec as u64));
+            }
+            "slowlog-max-len" => self.slowlog_max_len = parse(name, value)?,
+            "cluster-enabled" => {
+                self.cluster_enabled = match value {
+                    "yes" => true,
+                    "no" => false,
+                    _ => return Err(format!("invalid value '{}' for '{}'", value, name).into()),
+                }
+            }
+            "cluster-node-id" => self.cluster_node_id = Some(value.to_string()),
+            "cluster-node" => self.cluster_nodes.push(value.to_string()),
+            "timeout" => self.timeout = parse_seconds(name, value)?,
+            "maxclients" => {
+                // the clients are counted by a semaphore, which has a limit.
+                self.maxclients = match parse(name, value)? {
+                    n @ 1..=Semaphore::MAX_PERMITS => n,
+                    _ => {
+                        return Err(format!(
+                            "'{}' must be between 1 and {}",
+                            name,
+                            Semaphore::MAX_PERMITS
+                        )
+                        .into())
+                    }
+                }
+            }
+            "tcp-keepalive" => self.tcp_keepalive = parse_seconds(name, value)?,
+            "log-format" => self.log_format = parse(name, value)?,
+            _ => return Err(format!("unknown config '{}'", name).into()),
+        }
+
+        Ok(())
+    }
+}
+
+fn parse<T: std::str::FromStr>(name: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for '{}'", value, name).into())
+}
+
+// A number of seconds, 0 meaning none.
+fn parse_seconds(name: &str, value: &str) -> Result<Option<Duration>> {
+    let secs: u64 = parse(name, value)?;
+    Ok((secs > 0).then(|| Duration::from_secs(secs)))
+}
+
+// Parse a size like "100mb", "64kb" or a plain number of bytes.
+fn parse_memory(name: &str, value: &str) -> Result<usize> {
+    let lower = value.to_ascii_lowercase();
+    let (digits, unit) = match lower.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => lower.split_at(i),
+        None => (lower.as_str(), ""),
+    };
+    let unit = match unit {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return Err(format!("invalid value '{}' for '{}'", value, name).into()),
+    };
+
+    parse::<usize>(name, digits)?
+        .checked_mul(unit)
+        .ok_or_else(|| format!("invalid value '{}' for '{}'", value, name).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_units() {
+        assert_eq!(parse_memory("maxmemory", "1000").unwrap(), 1000);
+        assert_eq!(parse_memory("maxmemory", "1k").unwrap(), 1000);
+        assert_eq!(parse_memory("maxmemory", "2MB").unwrap(), 2 * 1024 * 1024);
+        assert!(parse_memory("maxmemory", "1tb").is_err());
+        assert!(parse_memory("maxmemory", "mb").is_err());
+        assert!(parse_memory("maxmemory", "20000000000gb").is_err());
+    }
+
+    #[test]
+    fn from_args() {
+        let args = [
+            "--port",
+            "7000",
+            "--maxmemory-policy",
+            "allkeys-lru",
+            "--log-format",
+            "json",
+        ];
+        let config = Config::from_args(args.iter().map(|s| s.to_string())).unwrap();
+        assert_eq!(config.port, 7000);
+        assert_eq!(config.maxmemory_policy, EvictionPolicy::AllKeysLru);
+        assert_eq!(config.log_format, LogFormat::Json);
+        assert_eq!(Config::default().log_format, LogFormat::Text);
+
+        assert!(Config::from_args(["--log-format", "xml"].iter().map(|s| s.to_string())).is_err());
+
+        assert!(Config::from_args(["--nope", "1"].iter().map(|s| s.to_string())).is_err());
+    }
+
+    #[test]
+    fn seconds_with_zero_disabled() {
+        let mut config = Config::default();
+        config.set("timeout", "30").unwrap();
+        config.set("tcp-keepalive", "0").unwrap();
+        assert_eq!(config.timeout, Some(Duration::from_secs(30)));
+        assert_eq!(config.tcp_keepalive, None);
+        assert!(config.set("timeout", "-1").is_err());
+    }
+
+    #[test]
+    fn maxclients_is_bounded() {
+        let mut config = Config::default();
+        config.set("maxclients", "1").unwrap();
+        assert_eq!(config.maxclients, 1);
+        assert!(config.set("maxclients", "0").is_err());
+        let above = (Semaphore::MAX_PERMITS + 1).to_string();
+        assert!(config.set("maxclients", &above).is_err());
+    }
+}
@@ -1,20 +1,50 @@
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, Bytes, BytesMut};
+use mini_redis::frame::Error as FrameError;
 use mini_redis::frame::Error::Incomplete;
 use mini_redis::{Frame, Result};
 use std::io::{self, Cursor};
-use tokio::io::{AsyncReadExt, AsyncWriteExt, BufWriter};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufWriter};
 use tokio::net::TcpStream;
 
-pub struct Connection {
-    stream: BufWriter<TcpStream>,
+// Longest bulk string accepted, like redis' proto-max-bulk-len.
+const MAX_BULK_LEN: u64 = 512 * 1024 * 1024;
+
+// Deepest nesting of arrays accepted, `Frame::parse` recurses into each.
+const MAX_DEPTH: usize = 64;
+
+// Longest inline command line accepted, like redis.
+const MAX_INLINE_LEN: usize = 64 * 1024;
+
+// Longest line of a frame accepted, e.g. a simple string or a length, like
+// redis' limit on the headers of a request.
+const MAX_LINE_LEN: usize = 64 * 1024;
+
+// Any byte stream a connection can run over: TCP, TLS, ...
+pub trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send> Stream for S {}
+
+pub struct Connection<S = TcpStream> {
+    stream: BufWriter<S>,
     buffer: BytesMut,
+    scanned: Scanned,
+}
+
+// Where the incomplete line a frame stopped at starts in the buffer, and
+// how far it was searched for its \r\n, so that a line arriving slowly
+// isn't searched from its start again on every read.
+#[derive(Clone, Copy, Default)]
+struct Scanned {
+    start: usize,
+    end: usize,
 }
 
-impl Connection {
-    pub fn new(stream: TcpStream) -> Connection {
+impl<S: Stream> Connection<S> {
+    pub fn new(stream: S) -> Connection<S> {
         Connection {
             stream: BufWriter::new(stream),
             buffer: BytesMut::with_capacity(4096),
+            scanned: Scanned::default(),
         }
     }
 
@@ -42,10 +72,35 @@ impl Connection {
         }
     }
 
+    // Bytes read and not parsed yet, and room left for more.
+    pub(crate) fn read_buffer(&self) -> (usize, usize) {
+        (
+            self.buffer.len(),
+            self.buffer.capacity() - self.buffer.len(),
+        )
+    }
+
     fn parse_frame(&mut self) -> Result<Option<Frame>> {
+        // anything but a RESP type byte starts an inline command, a line of
+        // text as typed in telnet. Blank lines are skipped.
+        while let Some(first) = self.buffer.first() {
+            if matches!(first, b'+' | b'-' | b':' | b'$' | b'*') {
+                break;
+            }
+            match self.parse_inline()? {
+                None => return Ok(None),
+                Some(args) if args.is_empty() => continue,
+                Some(args) => {
+                    return Ok(Some(Frame::Array(
+                        args.into_iter().map(Frame::Bulk).collect(),
+                    )))
+                }
+            }
+        }
+
         let mut buf = Cursor::new(&self.buffer[..]);
 
-        match Frame::check(&mut buf) {
+        match check(&mut buf, 0, &mut self.scanned) {
             Ok(_) => {
                 // the first `len` bytes are a frame.
                 // reset position -> parse frame -> advance buffer by len.
@@ -54,6 +109,7 @@ impl Connection {
 
                 let frame = Frame::parse(&mut buf)?;
                 self.buffer.advance(len);
+                self.scanned = Scanned::default();
 
                 Ok(Some(frame))
             }
@@ -62,8 +118,32 @@ impl Connection {
         }
     }
 
+    // Split the first line of the buffer into arguments, `None` if it isn't
+    // complete yet.
+    fn parse_inline(&mut self) -> Result<Option<Vec<Bytes>>> {
+        let end = match self.buffer.iter().position(|&b| b == b'\n') {
+            Some(end) => end,
+            None if self.buffer.len() > MAX_INLINE_LEN => {
+                return Err("protocol error; too big inline request".into())
+            }
+            None => return Ok(None),
+        };
+
+        let line = self.buffer.split_to(end + 1);
+        self.scanned = Scanned::default();
+        let line = &line[..end];
+        split_args(line.strip_suffix(b"\r").unwrap_or(line)).map(Some)
+    }
+
     // Write a frame to the connection.
     pub async fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
+        self.write_value(frame).await?;
+
+        self.stream.flush().await
+    }
+
+    // Write a single frame, recursing into the entries of arrays.
+    async fn write_value(&mut self, frame: &Frame) -> io::Result<()> {
         match frame {
             Frame::Simple(val) => {
                 self.stream.write_u8(b'+').await?;
@@ -90,10 +170,16 @@ impl Connection {
                 self.stream.write_all(val).await?;
                 self.stream.write_all(b"\r\n").await?;
             }
-            Frame::Array(_val) => unimplemented!(),
-        }
+            Frame::Array(val) => {
+                self.stream.write_u8(b'*').await?;
+                self.write_decimal(val.len() as u64).await?;
 
-        self.stream.flush().await;
+                for entry in val {
+                    // async recursion needs the future to be boxed.
+                    Box::pin(self.write_value(entry)).await?;
+                }
+            }
+        }
 
         Ok(())
     }
@@ -103,7 +189,7 @@ impl Connection {
         use std::io::Write;
 
         // Convert the value to a string
-        let mut buf = [0u8; 12];
+        let mut buf = [0u8; 20];
         let mut buf = Cursor::new(&mut buf[..]);
         write!(&mut buf, "{}", val)?;
 
@@ -114,3 +200,382 @@ impl Connection {
         Ok(())
     }
 }
+
+// Like `Frame::check`, but also rejects what `Frame::parse` can't take:
+// bulk lengths that overflow and arrays nested deep enough to blow the
+// stack. Once this passes, `Frame::parse` can't fail on missing bytes.
+fn check(
+    src: &mut Cursor<&[u8]>,
+    depth: usize,
+    scanned: &mut Scanned,
+) -> std::result::Result<(), FrameError> {
+    if !src.has_remaining() {
+        return Err(Incomplete);
+    }
+
+    match src.get_u8() {
+        b'+' | b'-' => {
+            get_line(src, scanned)?;
+        }
+        b':' => {
+            get_decimal(src, scanned)?;
+        }
+        b'$' if src.chunk().first() == Some(&b'-') => {
+            if get_line(src, scanned)? != b"-1" {
+                return Err("protocol error; invalid frame format".into());
+            }
+        }
+        b'$' => {
+            let len = get_decimal(src, scanned)?;
+            if len > MAX_BULK_LEN {
+                return Err("protocol error; invalid bulk length".into());
+            }
+
+            // the bytes, then \r\n.
+            let len = len as usize + 2;
+            if src.remaining() < len {
+                return Err(Incomplete);
+            }
+            src.advance(len);
+        }
+        b'*' => {
+            if depth == MAX_DEPTH {
+                return Err("protocol error; arrays nested too deep".into());
+            }
+
+            for _ in 0..get_decimal(src, scanned)? {
+                check(src, depth + 1, scanned)?;
+            }
+        }
+        actual => {
+            return Err(format!("protocol error; invalid frame type byte `{}`", actual).into())
+        }
+    }
+
+    Ok(())
+}
+
+// A line up to \r\n, the cursor moved past it.
+fn get_line<'a>(
+    src: &mut Cursor<&'a [u8]>,
+    scanned: &mut Scanned,
+) -> std::result::Result<&'a [u8], FrameError> {
+    let start = src.position() as usize;
+    let rest = &src.get_ref()[start..];
+    // the line was searched up to `scanned.end` by an earlier read, but for
+    // a \r it may end with.
+    let from = match *scanned {
+        Scanned { start: at, end } if at == start => end.saturating_sub(start + 1),
+        _ => 0,
+    };
+
+    match rest[from..].windows(2).position(|w| w == b"\r\n") {
+        Some(end) => {
+            let end = from + end;
+            src.set_position((start + end + 2) as u64);
+            Ok(&rest[..end])
+        }
+        // past the longest line and its \r.
+        None if rest.len() > MAX_LINE_LEN + 1 => Err("protocol error; too big line".into()),
+        None => {
+            *scanned = Scanned {
+                start,
+                end: start + rest.len(),
+            };
+            Err(Incomplete)
+        }
+    }
+}
+
+// Digits only: the `atoi` of `Frame::parse` stops at the first non-digit,
+// so a length like "3x" would be read differently by each.
+fn get_decimal(
+    src: &mut Cursor<&[u8]>,
+    scanned: &mut Scanned,
+) -> std::result::Result<u64, FrameError> {
+    let line = get_line(src, scanned)?;
+
+    if line.is_empty() || !line.iter().all(u8::is_ascii_digit) {
+        return Err("protocol error; invalid frame format".into());
+    }
+    line.iter()
+        .try_fold(0u64, |n, d| {
+            n.checked_mul(10)?.checked_add((d - b'0') as u64)
+        })
+        .ok_or_else(|| "protocol error; invalid frame format".into())
+}
+
+// Split an inline command into arguments, like redis' `sdssplitargs`:
+// arguments are separated by spaces and may be quoted. Double quotes take
+// escapes like \n or \x41, single quotes only \'.
+fn split_args(line: &[u8]) -> Result<Vec<Bytes>> {
+    let unbalanced =
+        || -> mini_redis::Error { "protocol error; unbalanced quotes in inline request".into() };
+    let mut args = Vec::new();
+    let mut i = 0;
+
+    loop {
+        while line.get(i).is_some_and(u8::is_ascii_whitespace) {
+            i += 1;
+        }
+        if i >= line.len() {
+            return Ok(args);
+        }
+
+        let mut arg = Vec::new();
+        let mut quote = None;
+        loop {
+            let b = line.get(i).copied();
+            i += 1;
+            match (quote, b) {
+                (None, None) => break,
+                (None, Some(b)) if b.is_ascii_whitespace() => break,
+                (None, Some(q @ (b'"' | b'\''))) => quote = Some(q),
+                (None, Some(b)) => arg.push(b),
+                // the closing quote must end the argument.
+                (Some(_), None) => return Err(unbalanced()),
+                (Some(q), Some(b)) if b == q => {
+                    if line.get(i).is_some_and(|b| !b.is_ascii_whitespace()) {
+                        return Err(unbalanced());
+                    }
+                    break;
+                }
+                (Some(b'\''), Some(b'\\')) if line.get(i) == Some(&b'\'') => {
+                    arg.push(b'\'');
+                    i += 1;
+                }
+                (Some(b'"'), Some(b'\\')) => {
+                    let c = *line.get(i).ok_or_else(unbalanced)?;
+                    i += 1;
+                    let byte = line.get(i..i + 2).and_then(hex).filter(|_| c == b'x');
+                    arg.push(match (c, byte) {
+                        (b'x', Some(byte)) => {
+                            i += 2;
+                            byte
+                        }
+                        (b'n', _) => b'\n',
+                        (b'r', _) => b'\r',
+                        (b't', _) => b'\t',
+                        (b'b', _) => 0x08,
+                        (b'a', _) => 0x07,
+                        (c, _) => c,
+                    });
+                }
+                (Some(_), Some(b)) => arg.push(b),
+            }
+        }
+        args.push(Bytes::from(arg));
+    }
+}
+
+// Two hex digits, e.g. "4f".
+fn hex(digits: &[u8]) -> Option<u8> {
+    let digit = |d: u8| (d as char).to_digit(16);
+    Some((digit(digits[0])? * 16 + digit(digits[1])?) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+    use tokio::runtime::Runtime;
+
+    // Arrays up to `depth` deep, of bulks up to `bulk_len` bytes.
+    fn frame(depth: u32, bulk_len: usize) -> impl Strategy<Value = Frame> {
+        let leaf = prop_oneof![
+            "[^\r\n]*".prop_map(Frame::Simple),
+            "[^\r\n]*".prop_map(Frame::Error),
+            any::<u64>().prop_map(Frame::Integer),
+            vec(any::<u8>(), 0..bulk_len).prop_map(|b| Frame::Bulk(Bytes::from(b))),
+            Just(Frame::Null),
+        ];
+        leaf.prop_recursive(depth, 64, 8, |inner| {
+            vec(inner, 0..8).prop_map(Frame::Array)
+        })
+    }
+
+    fn runtime() -> Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+    }
+
+    // `Frame` has no `PartialEq`, its `Debug` output tells frames apart.
+    fn same(a: &Frame, b: &Frame) -> bool {
+        format!("{:?}", a) == format!("{:?}", b)
+    }
+
+    async fn encode(frames: &[Frame]) -> Vec<u8> {
+        let (client, mut server) = tokio::io::duplex(64);
+        let mut conn = Connection::new(client);
+        let write = async {
+            for frame in frames {
+                conn.write_frame(frame).await.unwrap();
+            }
+            drop(conn);
+        };
+        let mut bytes = Vec::new();
+        let read = server.read_to_end(&mut bytes);
+
+        let (_, read) = tokio::join!(write, read);
+        read.unwrap();
+        bytes
+    }
+
+    // Read all frames of a stream that arrives in `chunks`.
+    async fn decode(chunks: &[&[u8]]) -> Result<Vec<Frame>> {
+        let mut mock = tokio_test::io::Builder::new();
+        for chunk in chunks.iter().filter(|chunk| !chunk.is_empty()) {
+            mock.read(chunk);
+        }
+        let mut conn = Connection::new(mock.build());
+
+        let mut frames = Vec::new();
+        while let Some(frame) = conn.read_frame().await? {
+            frames.push(frame);
+        }
+        Ok(frames)
+    }
+
+    proptest! {
+        #[test]
+        fn frames_round_trip(frames in vec(frame(4, 100), 1..4)) {
+            let rt = runtime();
+            let bytes = rt.block_on(encode(&frames));
+            let read = rt.block_on(decode(&[&bytes])).unwrap();
+
+            prop_assert_eq!(read.len(), frames.len());
+            for (read, frame) in read.iter().zip(&frames) {
+                prop_assert!(same(read, frame), "{:?} != {:?}", read, frame);
+            }
+        }
+
+        #[test]
+        fn frames_split_anywhere(frames in vec(frame(2, 10), 1..3)) {
+            let rt = runtime();
+            let bytes = rt.block_on(encode(&frames));
+
+            for at in 0..=bytes.len() {
+                let (head, tail) = bytes.split_at(at);
+                let read = rt.block_on(decode(&[head, tail])).unwrap();
+                prop_assert_eq!(read.len(), frames.len());
+                for (read, frame) in read.iter().zip(&frames) {
+                    prop_assert!(same(read, frame), "split at {}", at);
+                }
+            }
+        }
+
+        #[test]
+        fn garbage_never_panics(bytes in vec(any::<u8>(), 0..256)) {
+            let _ = runtime().block_on(decode(&[&bytes]));
+        }
+    }
+
+    #[test]
+    fn one_byte_at_a_time() {
+        let frames = [
+            Frame::Simple("OK".into()),
+            Frame::Error("ERR unknown command".into()),
+            Frame::Integer(u64::MAX),
+            Frame::Null,
+            Frame::Bulk(Bytes::from_static(b"line\r\nbreak")),
+            Frame::Array(vec![
+                Frame::Bulk(Bytes::new()),
+                Frame::Array(vec![Frame::Integer(0), Frame::Null]),
+            ]),
+        ];
+        let rt = runtime();
+        let bytes = rt.block_on(encode(&frames));
+
+        let chunks: Vec<&[u8]> = bytes.chunks(1).collect();
+        let read = rt.block_on(decode(&chunks)).unwrap();
+        assert_eq!(read.len(), frames.len());
+        assert!(read.iter().zip(&frames).all(|(a, b)| same(a, b)));
+
+        // a frame cut short by EOF.
+        let cut = &bytes[..bytes.len() - 1];
+        assert!(rt.block_on(decode(&[cut])).is_err());
+    }
+
+    #[test]
+    fn hostile_frames() {
+        let rt = runtime();
+        let cases: &[&[u8]] = &[
+            // `len + 2` overflows in `Frame::check`.
+            b"$18446744073709551615\r\n",
+            b"$18446744073709551616\r\n",
+            b"$536870913\r\n",
+            b"$3x\r\nabc\r\n",
+            b"$-2\r\n",
+            b"$-abc\r\n",
+            b":\r\n",
+            b"*-1\r\n",
+        ];
+        for case in cases {
+            assert!(rt.block_on(decode(&[case])).is_err(), "{:?}", case);
+        }
+
+        // nesting past `MAX_DEPTH` would blow the stack in `Frame::parse`.
+        let deep = "*1\r\n".repeat(MAX_DEPTH + 1) + ":1\r\n";
+        assert!(rt.block_on(decode(&[deep.as_bytes()])).is_err());
+        let deepest = "*1\r\n".repeat(MAX_DEPTH) + ":1\r\n";
+        assert!(rt.block_on(decode(&[deepest.as_bytes()])).is_ok());
+
+        // a huge array length allocates no more than the elements sent.
+        let read = rt.block_on(decode(&[b"*4294967296\r\n:1\r\n"]));
+        assert!(read.is_err());
+
+        // a line that never ends isn't buffered forever.
+        let mut endless = b"+".to_vec();
+        endless.resize(MAX_LINE_LEN + 3, b'a');
+        let chunks: Vec<&[u8]> = endless.chunks(4096).collect();
+        let msg = rt.block_on(decode(&chunks)).unwrap_err().to_string();
+        assert!(msg.contains("too big line"), "{}", msg);
+        // the longest one, its \n coming last.
+        let mut longest = endless[..MAX_LINE_LEN + 1].to_vec();
+        longest.push(b'\r');
+        assert!(rt.block_on(decode(&[&longest, b"\n"])).is_ok());
+    }
+
+    #[test]
+    fn inline_commands() {
+        let rt = runtime();
+        let read = rt.block_on(decode(&[
+            b"\r\nSET key value\r\n",
+            b"\n  get   key\nECHO \"a \\\"b\\\"\\n\\x41\\xZZ\" 'it\\'s' \"\"\r",
+            b"\n*1\r\n$4\r\nPING\r\n",
+        ]));
+        let frames: Vec<String> = read.unwrap().iter().map(|f| format!("{:?}", f)).collect();
+
+        let expected = [
+            Frame::Array(vec![
+                Frame::Bulk(Bytes::from("SET")),
+                Frame::Bulk(Bytes::from("key")),
+                Frame::Bulk(Bytes::from("value")),
+            ]),
+            Frame::Array(vec![
+                Frame::Bulk(Bytes::from("get")),
+                Frame::Bulk(Bytes::from("key")),
+            ]),
+            Frame::Array(vec![
+                Frame::Bulk(Bytes::from("ECHO")),
+                Frame::Bulk(Bytes::from("a \"b\"\nAxZZ")),
+                Frame::Bulk(Bytes::from("it's")),
+                Frame::Bulk(Bytes::new()),
+            ]),
+            // RESP still works after inline commands.
+            Frame::Array(vec![Frame::Bulk(Bytes::from("PING"))]),
+        ];
+        let expected: Vec<String> = expected.iter().map(|f| format!("{:?}", f)).collect();
+        assert_eq!(frames, expected);
+
+        for bad in [&b"GET \"key\n"[..], b"GET 'key\n", b"GET \"a\"b\n"] {
+            assert!(rt.block_on(decode(&[bad])).is_err(), "{:?}", bad);
+        }
+        let long = vec![b'a'; MAX_INLINE_LEN + 1];
+        let msg = rt.block_on(decode(&[&long])).unwrap_err().to_string();
+        assert!(msg.contains("too big inline request"), "{}", msg);
+    }
+}
@@ -0,0 +1,859 @@
+use crate::dump;
+use crate::evict::{self, Access, EvictionPolicy};
+use crate::stream::Stream;
+use crate::zset::SortedSet;
+
+use bytes::{Bytes, BytesMut};
+use indexmap::IndexMap;
+use rand::Rng;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+// Rough bookkeeping cost of one entry on top of its key and value bytes:
+// the map slot, the `String`/`Bytes` headers and allocator slack.
+const ENTRY_OVERHEAD: usize = 64;
+
+// `Arc` provides thread-safe reference counter.
+// `Mutex` provides locking.
+#[derive(Clone, Default)]
+pub struct Db {
+    shared: Arc<Mutex<State>>,
+}
+
+// Told about the keys the db drops by itself, with the reason: "expired"
+// or "evicted".
+pub(crate) type Listener = Arc<dyn Fn(&'static str, &str) + Send + Sync>;
+
+#[derive(Default)]
+struct State {
+    // an `IndexMap` rather than a `HashMap`, so that eviction can sample
+    // random keys by index.
+    entries: IndexMap<String, Entry>,
+    // keys with an expire set, soonest first.
+    expirations: BTreeSet<(Instant, String)>,
+    // approximate number of bytes held by `entries`.
+    used_memory: usize,
+    expired_keys: u64,
+    evicted_keys: u64,
+    listener: Option<Listener>,
+}
+
+struct Entry {
+    value: Value,
+    expires_at: Option<Instant>,
+    access: Access,
+}
+
+#[derive(Clone)]
+pub(crate) enum Value {
+    String(Bytes),
+    Stream(Box<Stream>),
+    ZSet(Box<SortedSet>),
+}
+
+impl Value {
+    fn size(&self) -> usize {
+        match self {
+            Value::String(value) => value.len(),
+            Value::Stream(stream) => stream.memory(),
+            Value::ZSet(set) => set.memory(),
+        }
+    }
+
+    // The name TYPE replies with.
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::Stream(_) => "stream",
+            Value::ZSet(_) => "zset",
+        }
+    }
+
+    // The name OBJECT ENCODING replies with. Values are all stored the
+    // same way, strings get the name redis would give them.
+    fn encoding(&self) -> &'static str {
+        match self {
+            Value::String(value) if value.len() <= 20 && is_int(value) => "int",
+            Value::String(value) if value.len() <= 44 => "embstr",
+            Value::String(_) => "raw",
+            Value::Stream(_) => "stream",
+            Value::ZSet(_) => "skiplist",
+        }
+    }
+}
+
+// What OBJECT tells about a key.
+pub(crate) struct Object {
+    pub(crate) encoding: &'static str,
+    // since the last access.
+    pub(crate) idle: Duration,
+    // the LFU counter.
+    pub(crate) freq: u8,
+}
+
+// Returned when memory can't be freed to make room for a write.
+#[derive(Debug)]
+pub(crate) struct OutOfMemory;
+
+// Returned when a key holds another type of value than the operation
+// expects, e.g. GET on a stream.
+#[derive(Debug)]
+pub struct WrongType;
+
+impl fmt::Display for WrongType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("WRONGTYPE Operation against a key holding the wrong kind of value")
+    }
+}
+
+impl std::error::Error for WrongType {}
+
+impl Db {
+    pub fn new() -> Db {
+        Db::default()
+    }
+
+    pub(crate) fn with_listener(listener: Listener) -> Db {
+        let db = Db::new();
+        db.shared.lock().unwrap().listener = Some(listener);
+        db
+    }
+
+    // The string value of `key`.
+    pub fn get(&self, key: &str) -> Result<Option<Bytes>, WrongType> {
+        let mut state = self.shared.lock().unwrap();
+        let now = Instant::now();
+
+        let entry = match state.live_entry(key, now) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        entry.access.touch(now);
+        match &entry.value {
+            Value::String(value) => Ok(Some(value.clone())),
+            _ => Err(WrongType),
+        }
+    }
+
+    // Set `key` to `value`, replacing any previous value and expire.
+    pub fn set(&self, key: String, value: Bytes, expire: Option<Duration>) {
+        let mut state = self.shared.lock().unwrap();
+        let now = Instant::now();
+
+        state.remove(&key);
+        state.insert(
+            key,
+            Value::String(value),
+            expire.map(|expire| now + expire),
+            now,
+        );
+    }
+
+    // Run `f` on the stream at `key`, creating an empty stream first if
+    // `create` is set. Returns `None` if there is no stream to run `f` on.
+    pub(crate) fn stream<R>(
+        &self,
+        key: &str,
+        create: bool,
+        f: impl FnOnce(&mut Stream) -> R,
+    ) -> Result<Option<R>, WrongType> {
+        let mut state = self.shared.lock().unwrap();
+        let now = Instant::now();
+
+        if state.live_entry(key, now).is_none() {
+            if !create {
+                return Ok(None);
+            }
+            state.insert(key.to_string(), Value::Stream(Box::default()), None, now);
+        }
+
+        let entry = state.entries.get_mut(key).unwrap();
+        entry.access.touch(now);
+        let stream = match &mut entry.value {
+            Value::Stream(stream) => stream,
+            _ => return Err(WrongType),
+        };
+
+        // the stream may grow or shrink.
+        let before = stream.memory();
+        let result = f(stream);
+        let after = stream.memory();
+        state.used_memory = state.used_memory + after - before;
+
+        Ok(Some(result))
+    }
+
+    // Same as `stream`, for the sorted set at `key`.
+    pub(crate) fn zset<R>(
+        &self,
+        key: &str,
+        create: bool,
+        f: impl FnOnce(&mut SortedSet) -> R,
+    ) -> Result<Option<R>, WrongType> {
+        let mut state = self.shared.lock().unwrap();
+        let now = Instant::now();
+
+        if state.live_entry(key, now).is_none() {
+            if !create {
+                return Ok(None);
+            }
+            state.insert(key.to_string(), Value::ZSet(Box::default()), None, now);
+        }
+
+        let entry = state.entries.get_mut(key).unwrap();
+        entry.access.touch(now);
+        let set = match &mut entry.value {
+            Value::ZSet(set) => set,
+            _ => return Err(WrongType),
+        };
+
+        let before = set.memory();
+        let result = f(set);
+        let after = set.memory();
+        state.used_memory = state.used_memory + after - before;
+
+        Ok(Some(result))
+    }
+
+    // Same as `stream`, for the bytes of the string at `key`. The expire is
+    // kept.
+    pub(crate) fn string_mut<R>(
+        &self,
+        key: &str,
+        create: bool,
+        f: impl FnOnce(&mut BytesMut) -> R,
+    ) -> Result<Option<R>, WrongType> {
+        let mut state = self.shared.lock().unwrap();
+        let now = Instant::now();
+
+        if state.live_entry(key, now).is_none() {
+            if !create {
+                return Ok(None);
+            }
+            state.insert(key.to_string(), Value::String(Bytes::new()), None, now);
+        }
+
+        let entry = state.entries.get_mut(key).unwrap();
+        entry.access.touch(now);
+        let value = match &mut entry.value {
+            Value::String(value) => value,
+            _ => return Err(WrongType),
+        };
+
+        // copied unless nothing else holds the bytes.
+        let mut bytes = BytesMut::from(std::mem::take(value));
+        let before = bytes.len();
+        let result = f(&mut bytes);
+        let after = bytes.len();
+        *value = bytes.freeze();
+        state.used_memory = state.used_memory + after - before;
+
+        Ok(Some(result))
+    }
+
+    // The type of the value at `key`, without counting as an access.
+    pub(crate) fn value_type(&self, key: &str) -> Option<&'static str> {
+        let mut state = self.shared.lock().unwrap();
+        let entry = state.live_entry(key, Instant::now())?;
+        Some(entry.value.type_name())
+    }
+
+    // Same as `value_type`, for OBJECT.
+    pub(crate) fn object(&self, key: &str) -> Option<Object> {
+        let mut state = self.shared.lock().unwrap();
+        let now = Instant::now();
+        let entry = state.live_entry(key, now)?;
+
+        Some(Object {
+            encoding: entry.value.encoding(),
+            idle: entry.access.idle(now),
+            freq: entry.access.frequency(now),
+        })
+    }
+
+    // Rename `from` to `to` with its expire, replacing `to` unless `nx`.
+    // Returns `None` if `from` doesn't exist, false if `to` does and `nx`.
+    pub(crate) fn rename(&self, from: &str, to: &str, nx: bool) -> Option<bool> {
+        let mut state = self.shared.lock().unwrap();
+        let now = Instant::now();
+
+        state.live_entry(from, now)?;
+        if state.live_entry(to, now).is_some() && (nx || from == to) {
+            // renaming a key to itself changes nothing, for RENAMENX it's
+            // just a name that exists.
+            return Some(!nx);
+        }
+
+        let entry = state.remove(from).unwrap();
+        state.remove(to);
+        state.put(to.to_string(), entry);
+        Some(true)
+    }
+
+    // The value of `key` in the DUMP format.
+    pub(crate) fn dump(&self, key: &str) -> Option<Bytes> {
+        let mut state = self.shared.lock().unwrap();
+        let entry = state.live_entry(key, Instant::now())?;
+        Some(dump::dump(&entry.value))
+    }
+
+    // Create `key` from a DUMP payload. Returns false if the key exists and
+    // `replace` isn't set.
+    pub(crate) fn restore(
+        &self,
+        key: String,
+        value: Value,
+        expire: Option<Duration>,
+        replace: bool,
+    ) -> bool {
+        let mut state = self.shared.lock().unwrap();
+        let now = Instant::now();
+        state.add(key, value, expire.map(|expire| now + expire), replace, now)
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        let mut state = self.shared.lock().unwrap();
+        state.live_entry(key, Instant::now()).is_some()
+    }
+
+    // The live keys `f` picks, at most `limit` of them.
+    pub(crate) fn keys_where(&self, limit: usize, mut f: impl FnMut(&str) -> bool) -> Vec<String> {
+        let state = self.shared.lock().unwrap();
+        let now = Instant::now();
+        state
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.expires_at.is_none_or(|at| at > now))
+            .map(|(key, _)| key)
+            .filter(|key| f(key))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    // Returns whether the key existed.
+    pub fn del(&self, key: &str) -> bool {
+        let mut state = self.shared.lock().unwrap();
+        let now = Instant::now();
+
+        state.live_entry(key, now).is_some() && state.remove(key).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.shared.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Remove every key.
+    pub fn clear(&self) {
+        let mut state = self.shared.lock().unwrap();
+        state.entries.clear();
+        state.expirations.clear();
+        state.used_memory = 0;
+    }
+
+    // Number of keys with an expire set.
+    pub fn expires(&self) -> usize {
+        self.shared.lock().unwrap().expirations.len()
+    }
+
+    pub fn used_memory(&self) -> usize {
+        self.shared.lock().unwrap().used_memory
+    }
+
+    pub fn expired_keys(&self) -> u64 {
+        self.shared.lock().unwrap().expired_keys
+    }
+
+    pub fn evicted_keys(&self) -> u64 {
+        self.shared.lock().unwrap().evicted_keys
+    }
+
+    // Remove all the keys whose expire is in the past.
+    pub(crate) fn purge_expired(&self) {
+        let mut state = self.shared.lock().unwrap();
+        let now = Instant::now();
+
+        while let Some((when, key)) = state.expirations.first().cloned() {
+            if when > now {
+                break;
+            }
+            state.remove(&key);
+            state.expired_keys += 1;
+            state.notify("expired", &key);
+        }
+    }
+
+    // Evict keys with `policy` until `used_memory` fits in `maxmemory`.
+    pub(crate) fn make_room(
+        &self,
+        maxmemory: usize,
+        policy: EvictionPolicy,
+    ) -> Result<(), OutOfMemory> {
+        let mut state = self.shared.lock().unwrap();
+        let now = Instant::now();
+
+        while state.used_memory > maxmemory {
+            if policy == EvictionPolicy::NoEviction {
+                return Err(OutOfMemory);
+            }
+
+            let key = state.pick_victim(policy, now).ok_or(OutOfMemory)?;
+            state.remove(&key);
+            state.evicted_keys += 1;
+            state.notify("evicted", &key);
+        }
+
+        Ok(())
+    }
+}
+
+// The numbered databases of a server, selected per connection.
+pub(crate) struct Databases {
+    dbs: Vec<Db>,
+}
+
+impl Databases {
+    // `listener` gets the index of the database along with the event.
+    pub(crate) fn new(
+        count: usize,
+        listener: impl Fn(usize, &'static str, &str) + Send + Sync + 'static,
+    ) -> Databases {
+        let listener = Arc::new(listener);
+        let dbs = (0..count)
+            .map(|index| {
+                let listener = listener.clone();
+                Db::with_listener(Arc::new(move |event, key| listener(index, event, key)))
+            })
+            .collect();
+
+        Databases { dbs }
+    }
+
+    // Panics if `index` is out of range, see `len`.
+    pub(crate) fn get(&self, index: usize) -> &Db {
+        &self.dbs[index]
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.dbs.len()
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Db> {
+        self.dbs.iter()
+    }
+
+    pub(crate) fn keys(&self) -> usize {
+        self.dbs.iter().map(Db::len).sum()
+    }
+
+    pub(crate) fn used_memory(&self) -> usize {
+        self.dbs.iter().map(Db::used_memory).sum()
+    }
+
+    pub(crate) fn expired_keys(&self) -> u64 {
+        self.dbs.iter().map(Db::expired_keys).sum()
+    }
+
+    pub(crate) fn evicted_keys(&self) -> u64 {
+        self.dbs.iter().map(Db::evicted_keys).sum()
+    }
+
+    pub(crate) fn purge_expired(&self) {
+        self.dbs.iter().for_each(Db::purge_expired);
+    }
+
+    // `maxmemory` is for all the databases together. Keys are evicted from
+    // the biggest databases first.
+    pub(crate) fn make_room(
+        &self,
+        maxmemory: usize,
+        policy: EvictionPolicy,
+    ) -> Result<(), OutOfMemory> {
+        let mut dbs: Vec<&Db> = self.dbs.iter().collect();
+        dbs.sort_by_key(|db| std::cmp::Reverse(db.used_memory()));
+
+        for db in dbs {
+            let used = self.used_memory();
+            if used <= maxmemory {
+                break;
+            }
+            // may not free enough, e.g. without volatile keys in this db.
+            let _ = db.make_room(db.used_memory().saturating_sub(used - maxmemory), policy);
+        }
+
+        if self.used_memory() <= maxmemory {
+            Ok(())
+        } else {
+            Err(OutOfMemory)
+        }
+    }
+
+    // Move `key` with its expire from db `from` to db `to`. Returns false if
+    // the key doesn't exist in `from` or already exists in `to`.
+    pub(crate) fn move_key(&self, key: &str, from: usize, to: usize) -> bool {
+        let (mut src, mut dst) = self.lock_pair(from, to);
+        let now = Instant::now();
+
+        if src.live_entry(key, now).is_none() || dst.live_entry(key, now).is_some() {
+            return false;
+        }
+
+        let entry = src.remove(key).unwrap();
+        dst.put(key.to_string(), entry);
+        true
+    }
+
+    // Copy `key` of db `from`, with its expire, to `dest` of db `to`.
+    // Returns false if `key` doesn't exist, or `dest` does and `replace`
+    // isn't set.
+    pub(crate) fn copy(
+        &self,
+        key: &str,
+        from: usize,
+        dest: &str,
+        to: usize,
+        replace: bool,
+    ) -> bool {
+        let now = Instant::now();
+        let copy = |src: &mut State| {
+            let entry = src.live_entry(key, now)?;
+            Some((entry.value.clone(), entry.expires_at))
+        };
+
+        if from == to {
+            let mut state = self.dbs[from].shared.lock().unwrap();
+            return match copy(&mut state) {
+                Some((value, expires_at)) => {
+                    state.add(dest.to_string(), value, expires_at, replace, now)
+                }
+                None => false,
+            };
+        }
+
+        let (mut src, mut dst) = self.lock_pair(from, to);
+        match copy(&mut src) {
+            Some((value, expires_at)) => dst.add(dest.to_string(), value, expires_at, replace, now),
+            None => false,
+        }
+    }
+
+    // Exchange the keys of two databases, connections using one see the
+    // keys of the other right away.
+    pub(crate) fn swap(&self, a: usize, b: usize) {
+        if a == b {
+            return;
+        }
+
+        let (mut a, mut b) = self.lock_pair(a, b);
+        std::mem::swap(&mut a.entries, &mut b.entries);
+        std::mem::swap(&mut a.expirations, &mut b.expirations);
+        std::mem::swap(&mut a.used_memory, &mut b.used_memory);
+    }
+
+    // Lock two different databases, always in the same order so that two
+    // callers can't deadlock.
+    fn lock_pair(&self, a: usize, b: usize) -> (MutexGuard<'_, State>, MutexGuard<'_, State>) {
+        assert_ne!(a, b);
+        if a < b {
+            let a = self.dbs[a].shared.lock().unwrap();
+            (a, self.dbs[b].shared.lock().unwrap())
+        } else {
+            let b_guard = self.dbs[b].shared.lock().unwrap();
+            (self.dbs[a].shared.lock().unwrap(), b_guard)
+        }
+    }
+}
+
+impl State {
+    fn insert(&mut self, key: String, value: Value, expires_at: Option<Instant>, now: Instant) {
+        self.put(
+            key,
+            Entry {
+                value,
+                expires_at,
+                access: Access::new(now),
+            },
+        );
+    }
+
+    // Insert `key` unless it exists and `replace` isn't set, returns whether
+    // it was.
+    fn add(
+        &mut self,
+        key: String,
+        value: Value,
+        expires_at: Option<Instant>,
+        replace: bool,
+        now: Instant,
+    ) -> bool {
+        if self.live_entry(&key, now).is_some() && !replace {
+            return false;
+        }
+
+        self.remove(&key);
+        self.insert(key, value, expires_at, now);
+        true
+    }
+
+    // Insert an entry, e.g. one taken out of another key with `remove`.
+    fn put(&mut self, key: String, entry: Entry) {
+        if let Some(when) = entry.expires_at {
+            self.expirations.insert((when, key.clone()));
+        }
+        self.used_memory += entry_size(&key, &entry.value);
+        self.entries.insert(key, entry);
+    }
+
+    // Look up `key`, dropping it first if it has expired.
+    fn live_entry(&mut self, key: &str, now: Instant) -> Option<&mut Entry> {
+        let expired = match self.entries.get(key)?.expires_at {
+            Some(when) => when <= now,
+            None => false,
+        };
+        if expired {
+            self.remove(key);
+            self.expired_keys += 1;
+            self.notify("expired", key);
+            return None;
+        }
+
+        self.entries.get_mut(key)
+    }
+
+    fn remove(&mut self, key: &str) -> Option<Entry> {
+        let entry = self.entries.swap_remove(key)?;
+
+        if let Some(when) = entry.expires_at {
+            self.expirations.remove(&(when, key.to_string()));
+        }
+        self.used_memory -= entry_size(key, &entry.value);
+
+        Some(entry)
+    }
+
+    fn notify(&self, event: &'static str, key: &str) {
+        if let Some(listener) = &self.listener {
+            listener(event, key);
+        }
+    }
+
+    // Sample a few keys and return the best candidate for `policy`.
+    fn pick_victim(&self, policy: EvictionPolicy, now: Instant) -> Option<String> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        if policy == EvictionPolicy::VolatileTtl {
+            // the expirations set is already ordered by ttl.
+            return self.expirations.first().map(|(_, key)| key.clone());
+        }
+
+        let mut rng = rand::thread_rng();
+        let mut best: Option<(u64, &String)> = None;
+        let mut sampled = 0;
+
+        // volatile policies skip keys without expire, so give up after a
+        // bounded number of tries to avoid spinning on a non-volatile keyspace.
+        for _ in 0..evict::SAMPLES * 10 {
+            let index = rng.gen_range(0..self.entries.len());
+            let (key, entry) = self.entries.get_index(index).unwrap();
+            if policy.volatile_only() && entry.expires_at.is_none() {
+                continue;
+            }
+
+            let score = policy.score(&entry.access, entry.expires_at, now);
+            if best.is_none_or(|(best, _)| score > best) {
+                best = Some((score, key));
+            }
+
+            sampled += 1;
+            if sampled == evict::SAMPLES {
+                break;
+            }
+        }
+
+        match best {
+            Some((_, key)) => Some(key.clone()),
+            // fall back to any key with an expire.
+            None if policy.volatile_only() => self.expirations.first().map(|(_, key)| key.clone()),
+            None => None,
+        }
+    }
+}
+
+fn entry_size(key: &str, value: &Value) -> usize {
+    key.len() + value.size() + ENTRY_OVERHEAD
+}
+
+// Whether redis would store `value` as an integer: a signed 64 bit number
+// without sign or leading zero oddities, e.g. not "+1" or "01".
+fn is_int(value: &[u8]) -> bool {
+    std::str::from_utf8(value)
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .is_some_and(|n| n.to_string().as_bytes() == value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn used_memory_tracks_overwrites() {
+        let db = Db::new();
+        db.set("foo".to_string(), Bytes::from("bar"), None);
+        assert_eq!(db.used_memory(), 3 + 3 + ENTRY_OVERHEAD);
+
+        db.set("foo".to_string(), Bytes::from("a longer value"), None);
+        assert_eq!(db.used_memory(), 3 + 14 + ENTRY_OVERHEAD);
+        assert_eq!(db.len(), 1);
+
+        db.del("foo");
+        assert_eq!(db.used_memory(), 0);
+    }
+
+    #[test]
+    fn expired_keys_are_dropped() {
+        let db = Db::new();
+        db.set("foo".to_string(), Bytes::from("bar"), Some(Duration::ZERO));
+        db.set("baz".to_string(), Bytes::from("qux"), Some(Duration::ZERO));
+        db.set("keep".to_string(), Bytes::from("me"), None);
+        assert_eq!(db.expires(), 2);
+
+        // lazily, on access.
+        assert!(db.get("foo").unwrap().is_none());
+        // and actively, by the purge.
+        db.purge_expired();
+
+        assert_eq!(db.len(), 1);
+        assert_eq!(db.expires(), 0);
+        assert_eq!(db.expired_keys(), 2);
+    }
+
+    fn fill(db: &Db, n: usize, expire: Option<Duration>) {
+        for i in 0..n {
+            db.set(format!("key:{}", i), Bytes::from(vec![0; 100]), expire);
+        }
+    }
+
+    #[test]
+    fn noeviction_rejects() {
+        let db = Db::new();
+        fill(&db, 10, None);
+
+        assert!(db
+            .make_room(db.used_memory(), EvictionPolicy::NoEviction)
+            .is_ok());
+        assert!(db.make_room(1000, EvictionPolicy::NoEviction).is_err());
+        assert_eq!(db.len(), 10);
+    }
+
+    #[test]
+    fn allkeys_policies_evict_down_to_the_limit() {
+        for policy in [
+            EvictionPolicy::AllKeysLru,
+            EvictionPolicy::AllKeysLfu,
+            EvictionPolicy::AllKeysRandom,
+        ] {
+            let db = Db::new();
+            fill(&db, 100, None);
+
+            db.make_room(5000, policy).unwrap();
+            assert!(db.used_memory() <= 5000);
+            assert_eq!(db.evicted_keys() as usize, 100 - db.len());
+        }
+    }
+
+    #[test]
+    fn lru_keeps_recently_used_keys() {
+        let db = Db::new();
+        fill(&db, 100, None);
+
+        std::thread::sleep(Duration::from_millis(10));
+        for i in 0..10 {
+            db.get(&format!("key:{}", i)).unwrap();
+        }
+
+        // evict half of the keys. The policy is sampled, so tolerate one
+        // recently used key being unlucky.
+        db.make_room(db.used_memory() / 2, EvictionPolicy::AllKeysLru)
+            .unwrap();
+        let kept = (0..10)
+            .filter(|i| db.get(&format!("key:{}", i)).unwrap().is_some())
+            .count();
+        assert!(kept >= 9, "kept {}", kept);
+    }
+
+    #[test]
+    fn databases_move_and_swap_keys() {
+        let dbs = Databases::new(3, |_, _, _| {});
+        dbs.get(0).set("a".to_string(), Bytes::from("1"), None);
+        dbs.get(0).set(
+            "b".to_string(),
+            Bytes::from("2"),
+            Some(Duration::from_secs(60)),
+        );
+        dbs.get(1).set("a".to_string(), Bytes::from("other"), None);
+
+        assert!(!dbs.move_key("a", 0, 1));
+        assert!(!dbs.move_key("missing", 0, 1));
+        assert!(dbs.move_key("b", 0, 2));
+        assert_eq!(dbs.get(2).get("b").unwrap().unwrap(), "2");
+        assert_eq!(dbs.get(2).expires(), 1);
+        assert_eq!(dbs.get(0).expires(), 0);
+        assert_eq!(dbs.keys(), 3);
+
+        dbs.swap(0, 1);
+        assert_eq!(dbs.get(0).get("a").unwrap().unwrap(), "other");
+        assert_eq!(dbs.get(1).get("a").unwrap().unwrap(), "1");
+        assert_eq!(dbs.get(1).used_memory(), 1 + 1 + ENTRY_OVERHEAD);
+    }
+
+    #[test]
+    fn maxmemory_is_shared_by_databases() {
+        let dbs = Databases::new(2, |_, _, _| {});
+        fill(dbs.get(0), 10, None);
+        fill(dbs.get(1), 30, None);
+
+        let limit = dbs.used_memory() / 2;
+        dbs.make_room(limit, EvictionPolicy::AllKeysRandom).unwrap();
+        assert!(dbs.used_memory() <= limit);
+        // the biggest database went first.
+        assert_eq!(dbs.get(0).len(), 10);
+
+        assert!(dbs.make_room(0, EvictionPolicy::NoEviction).is_err());
+    }
+
+    #[test]
+    fn volatile_policies_only_evict_keys_with_expire() {
+        for policy in [EvictionPolicy::VolatileLru, EvictionPolicy::VolatileTtl] {
+            let db = Db::new();
+            fill(&db, 10, None);
+            db.set(
+                "a".to_string(),
+                Bytes::from(vec![0; 100]),
+                Some(Duration::from_secs(60)),
+            );
+            db.set(
+                "b".to_string(),
+                Bytes::from(vec![0; 100]),
+                Some(Duration::from_secs(30)),
+            );
+
+            db.make_room(db.used_memory() - 1, policy).unwrap();
+            assert_eq!(db.len(), 11);
+            if policy == EvictionPolicy::VolatileTtl {
+                assert!(db.get("b").unwrap().is_none());
+            }
+
+            // nothing volatile left to evict.
+            assert!(db.make_room(0, policy).is_err());
+            assert_eq!(db.len(), 10);
+        }
+    }
+}
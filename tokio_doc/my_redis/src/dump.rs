@@ -0,0 +1,202 @@
+use crate::db::Value;
+use crate::stream::Stream;
+use crate::zset::SortedSet;
+
+use bytes::Bytes;
+
+// A DUMP payload is the type of the value and the value, then the version of
+// the format (u16) and a CRC-64 of everything before it (u64), both little
+// endian. Numbers and lengths inside the value are u64 little endian.
+//
+// The version is bumped when the format changes. RESTORE takes payloads of
+// its version or older.
+const VERSION: u16 = 1;
+
+const STRING: u8 = 0;
+const STREAM: u8 = 1;
+const ZSET: u8 = 2;
+
+// Returned by `restore` for a payload it can't read.
+pub(crate) const BAD_PAYLOAD: &str = "ERR DUMP payload version or checksum are wrong";
+
+pub(crate) fn dump(value: &Value) -> Bytes {
+    let mut out = Writer(Vec::new());
+    match value {
+        Value::String(value) => {
+            out.u8(STRING);
+            out.bytes(value);
+        }
+        Value::Stream(stream) => {
+            out.u8(STREAM);
+            stream.dump(&mut out);
+        }
+        Value::ZSet(set) => {
+            out.u8(ZSET);
+            set.dump(&mut out);
+        }
+    }
+
+    out.0.extend_from_slice(&VERSION.to_le_bytes());
+    let crc = crc64(&out.0);
+    out.0.extend_from_slice(&crc.to_le_bytes());
+    Bytes::from(out.0)
+}
+
+pub(crate) fn restore(payload: &[u8]) -> Result<Value, &'static str> {
+    // the footer: 2 bytes of version, 8 of checksum.
+    let (data, crc) = payload
+        .len()
+        .checked_sub(8)
+        .map(|at| payload.split_at(at))
+        .ok_or(BAD_PAYLOAD)?;
+    if crc64(data).to_le_bytes() != crc {
+        return Err(BAD_PAYLOAD);
+    }
+    let (data, version) = data
+        .len()
+        .checked_sub(2)
+        .map(|at| data.split_at(at))
+        .ok_or(BAD_PAYLOAD)?;
+    if u16::from_le_bytes([version[0], version[1]]) > VERSION {
+        return Err(BAD_PAYLOAD);
+    }
+
+    let mut src = Reader(data);
+    let value = match src.u8() {
+        Some(STRING) => src.bytes().map(Value::String),
+        Some(STREAM) => Stream::restore(&mut src).map(|stream| Value::Stream(Box::new(stream))),
+        Some(ZSET) => SortedSet::restore(&mut src).map(|set| Value::ZSet(Box::new(set))),
+        _ => None,
+    };
+
+    // a valid checksum over data that doesn't parse fully.
+    match value {
+        Some(value) if src.0.is_empty() => Ok(value),
+        _ => Err("ERR Bad data format"),
+    }
+}
+
+// Appends the parts of a payload.
+pub(crate) struct Writer(Vec<u8>);
+
+impl Writer {
+    pub(crate) fn u8(&mut self, n: u8) {
+        self.0.push(n);
+    }
+
+    pub(crate) fn u64(&mut self, n: u64) {
+        self.0.extend_from_slice(&n.to_le_bytes());
+    }
+
+    // Length prefixed.
+    pub(crate) fn bytes(&mut self, bytes: &[u8]) {
+        self.u64(bytes.len() as u64);
+        self.0.extend_from_slice(bytes);
+    }
+}
+
+// Reads the parts of a payload, `None` once it runs out of data.
+pub(crate) struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    pub(crate) fn u8(&mut self) -> Option<u8> {
+        let (&n, rest) = self.0.split_first()?;
+        self.0 = rest;
+        Some(n)
+    }
+
+    pub(crate) fn u64(&mut self) -> Option<u64> {
+        let n = self.0.get(..8)?;
+        let n = u64::from_le_bytes(n.try_into().unwrap());
+        self.0 = &self.0[8..];
+        Some(n)
+    }
+
+    pub(crate) fn bytes(&mut self) -> Option<Bytes> {
+        let len = usize::try_from(self.u64()?).ok()?;
+        let bytes = self.0.get(..len)?;
+        self.0 = &self.0[len..];
+        Some(Bytes::copy_from_slice(bytes))
+    }
+
+    pub(crate) fn string(&mut self) -> Option<String> {
+        String::from_utf8(self.bytes()?.to_vec()).ok()
+    }
+}
+
+// CRC-64/Jones, the checksum of redis' DUMP payloads.
+fn crc64(data: &[u8]) -> u64 {
+    const POLY: u64 = 0x95ac_9329_ac4b_c9b5;
+
+    let mut crc = 0u64;
+    for &b in data {
+        crc ^= b as u64;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ POLY
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::{NewId, StreamId};
+
+    #[test]
+    fn crc64_check_value() {
+        // the check value of CRC-64/Jones as used by redis.
+        assert_eq!(crc64(b"123456789"), 0xe9c6_d914_c4b8_d9ca);
+    }
+
+    #[test]
+    fn values_round_trip() {
+        let payload = dump(&Value::String(Bytes::from("hello")));
+        assert!(matches!(restore(&payload), Ok(Value::String(s)) if s == "hello"));
+
+        let mut stream = Stream::default();
+        let fields = vec![(Bytes::from("f"), Bytes::from("v"))];
+        stream
+            .add(NewId::Explicit(StreamId { ms: 1, seq: 0 }), fields.clone())
+            .unwrap();
+        stream
+            .add(NewId::Explicit(StreamId { ms: 2, seq: 0 }), fields)
+            .unwrap();
+        stream.create_group("g", StreamId::MIN).unwrap();
+        stream
+            .read_group("g", "alice", None, Some(1), false)
+            .unwrap();
+
+        let payload = dump(&Value::Stream(Box::new(stream.clone())));
+        let mut restored = match restore(&payload) {
+            Ok(Value::Stream(restored)) => restored,
+            _ => panic!("expected a stream"),
+        };
+        assert_eq!(restored.len(), 2);
+        assert_eq!(restored.last_id(), stream.last_id());
+        assert_eq!(restored.memory(), stream.memory());
+        assert_eq!(restored.pending_summary("g").unwrap().count, 1);
+    }
+
+    #[test]
+    fn damaged_payloads_are_rejected() {
+        let payload = dump(&Value::String(Bytes::from("hello"))).to_vec();
+
+        let mut flipped = payload.clone();
+        flipped[3] ^= 1;
+        assert_eq!(restore(&flipped).err(), Some(BAD_PAYLOAD));
+        assert_eq!(restore(&payload[..5]).err(), Some(BAD_PAYLOAD));
+        assert_eq!(restore(b"").err(), Some(BAD_PAYLOAD));
+
+        // a newer version, with a valid checksum.
+        let mut newer = payload[..payload.len() - 10].to_vec();
+        newer.extend_from_slice(&(VERSION + 1).to_le_bytes());
+        let crc = crc64(&newer);
+        newer.extend_from_slice(&crc.to_le_bytes());
+        assert_eq!(restore(&newer).err(), Some(BAD_PAYLOAD));
+    }
+}
@@ -0,0 +1,169 @@
+use rand::Rng;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+// Number of keys looked at to pick one eviction victim. Like redis, the
+// policies are approximated by sampling instead of keeping exact orderings.
+pub(crate) const SAMPLES: usize = 5;
+
+// Which key to drop when `maxmemory` is reached.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    // reject writes with an OOM error instead of dropping keys.
+    #[default]
+    NoEviction,
+    AllKeysLru,
+    AllKeysLfu,
+    AllKeysRandom,
+    // only keys with an expire set are candidates.
+    VolatileLru,
+    VolatileTtl,
+}
+
+impl EvictionPolicy {
+    pub(crate) fn volatile_only(self) -> bool {
+        matches!(
+            self,
+            EvictionPolicy::VolatileLru | EvictionPolicy::VolatileTtl
+        )
+    }
+
+    // Score of a candidate key, the highest score gets evicted.
+    pub(crate) fn score(self, access: &Access, expires_at: Option<Instant>, now: Instant) -> u64 {
+        match self {
+            EvictionPolicy::NoEviction => 0,
+            EvictionPolicy::AllKeysLru | EvictionPolicy::VolatileLru => {
+                access.idle(now).as_millis() as u64
+            }
+            EvictionPolicy::AllKeysLfu => 255 - access.frequency(now) as u64,
+            EvictionPolicy::AllKeysRandom => rand::thread_rng().gen(),
+            EvictionPolicy::VolatileTtl => match expires_at {
+                Some(when) => u64::MAX - when.saturating_duration_since(now).as_millis() as u64,
+                None => 0,
+            },
+        }
+    }
+}
+
+impl FromStr for EvictionPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<EvictionPolicy, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "noeviction" => Ok(EvictionPolicy::NoEviction),
+            "allkeys-lru" => Ok(EvictionPolicy::AllKeysLru),
+            "allkeys-lfu" => Ok(EvictionPolicy::AllKeysLfu),
+            "allkeys-random" | "random" => Ok(EvictionPolicy::AllKeysRandom),
+            "volatile-lru" => Ok(EvictionPolicy::VolatileLru),
+            "volatile-ttl" => Ok(EvictionPolicy::VolatileTtl),
+            _ => Err(format!("unknown eviction policy '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for EvictionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            EvictionPolicy::NoEviction => "noeviction",
+            EvictionPolicy::AllKeysLru => "allkeys-lru",
+            EvictionPolicy::AllKeysLfu => "allkeys-lfu",
+            EvictionPolicy::AllKeysRandom => "allkeys-random",
+            EvictionPolicy::VolatileLru => "volatile-lru",
+            EvictionPolicy::VolatileTtl => "volatile-ttl",
+        };
+        f.write_str(name)
+    }
+}
+
+// Counter given to new keys so they are not evicted right away by LFU.
+const LFU_INIT: u8 = 5;
+// Higher values make the counter saturate more slowly.
+const LFU_LOG_FACTOR: f64 = 10.0;
+// The counter loses one point per period without access.
+const LFU_DECAY: Duration = Duration::from_secs(60);
+
+// Access metadata kept per key for the LRU and LFU policies.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Access {
+    last: Instant,
+    // logarithmic access counter, see `touch`.
+    counter: u8,
+}
+
+impl Access {
+    pub(crate) fn new(now: Instant) -> Access {
+        Access {
+            last: now,
+            counter: LFU_INIT,
+        }
+    }
+
+    pub(crate) fn idle(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.last)
+    }
+
+    // The counter with decay applied.
+    pub(crate) fn frequency(&self, now: Instant) -> u8 {
+        let periods = self.idle(now).as_secs() / LFU_DECAY.as_secs();
+        self.counter.saturating_sub(periods.min(255) as u8)
+    }
+
+    // Record an access. The counter is incremented with a probability that
+    // shrinks as it grows, so 255 stands for millions of accesses.
+    pub(crate) fn touch(&mut self, now: Instant) {
+        let mut counter = self.frequency(now);
+        if counter < 255 {
+            let base = counter.saturating_sub(LFU_INIT) as f64;
+            let p = 1.0 / (base * LFU_LOG_FACTOR + 1.0);
+            if rand::thread_rng().gen::<f64>() < p {
+                counter += 1;
+            }
+        }
+
+        self.counter = counter;
+        self.last = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lfu_counter_grows_logarithmically() {
+        let now = Instant::now();
+        let mut access = Access::new(now);
+        for _ in 0..1000 {
+            access.touch(now);
+        }
+
+        let counter = access.frequency(now);
+        assert!(counter > LFU_INIT + 5, "counter {}", counter);
+        assert!(counter < 100, "counter {}", counter);
+    }
+
+    #[test]
+    fn lfu_counter_decays_when_idle() {
+        let now = Instant::now();
+        let access = Access::new(now);
+        let later = now + LFU_DECAY * 3;
+
+        assert_eq!(access.frequency(later), LFU_INIT - 3);
+    }
+
+    #[test]
+    fn policy_names_round_trip() {
+        for name in [
+            "noeviction",
+            "allkeys-lru",
+            "allkeys-lfu",
+            "allkeys-random",
+            "volatile-lru",
+            "volatile-ttl",
+        ] {
+            let policy: EvictionPolicy = name.parse().unwrap();
+            assert_eq!(policy.to_string(), name);
+        }
+    }
+}
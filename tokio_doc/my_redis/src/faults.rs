@@ -0,0 +1,244 @@
+use mini_redis::Result;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio::time::Sleep;
+
+// Faults injected in the writes of connections, to test how clients cope
+// with a misbehaving server. The probabilities are per write, and checked
+// in the order of the fields.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Faults {
+    // makes the faults of each connection reproducible.
+    pub seed: Option<u64>,
+    // each write waits `delay`, plus up to `jitter`.
+    pub delay: Duration,
+    pub jitter: Duration,
+    // the connection is reset instead of written to.
+    pub reset: f64,
+    // only part of the write goes through, then the connection is closed.
+    pub truncate: f64,
+    // a bit of the write is flipped.
+    pub corrupt: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Fault {
+    Reset,
+    // the number of bytes written.
+    Truncate(usize),
+    // the offset of the byte, and the bit flipped in it.
+    Corrupt(usize, u8),
+}
+
+// A TCP stream whose writes go through `Faults`. Reads are left alone.
+pub struct Faulty {
+    inner: TcpStream,
+    faults: Faults,
+    rng: StdRng,
+    // the delay of the write in progress, then its fault.
+    delay: Option<Pin<Box<Sleep>>>,
+    planned: Option<Option<Fault>>,
+    // reset or truncated, nothing goes through anymore.
+    broken: bool,
+}
+
+impl Faults {
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        match name {
+            "seed" => self.seed = Some(parse(name, value)?),
+            "delay-ms" => self.delay = Duration::from_millis(parse(name, value)?),
+            "jitter-ms" => self.jitter = Duration::from_millis(parse(name, value)?),
+            "reset" => self.reset = parse_probability(name, value)?,
+            "truncate" => self.truncate = parse_probability(name, value)?,
+            "corrupt" => self.corrupt = parse_probability(name, value)?,
+            _ => return Err(format!("unknown option '{}'", name).into()),
+        }
+        Ok(())
+    }
+
+    // Inject faults in the `n`th connection. With a seed, the faults of a
+    // connection only depend on `n` and on the writes made to it.
+    pub fn wrap(&self, stream: TcpStream, n: u64) -> Faulty {
+        let rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed.wrapping_add(n)),
+            None => StdRng::from_entropy(),
+        };
+        Faulty {
+            inner: stream,
+            faults: *self,
+            rng,
+            delay: None,
+            planned: None,
+            broken: false,
+        }
+    }
+
+    fn pick_delay(&self, rng: &mut impl Rng) -> Duration {
+        if self.jitter.is_zero() {
+            return self.delay;
+        }
+        self.delay + rng.gen_range(Duration::ZERO..=self.jitter)
+    }
+
+    // The fault of a write of `len` bytes, if any.
+    fn pick_fault(&self, rng: &mut impl Rng, len: usize) -> Option<Fault> {
+        if len == 0 {
+            return None;
+        }
+        let draw: f64 = rng.gen();
+        if draw < self.reset {
+            Some(Fault::Reset)
+        } else if draw < self.reset + self.truncate {
+            Some(Fault::Truncate(rng.gen_range(0..len)))
+        } else if draw < self.reset + self.truncate + self.corrupt {
+            Some(Fault::Corrupt(
+                rng.gen_range(0..len),
+                1 << rng.gen_range(0..8),
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+impl AsyncRead for Faulty {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Faulty {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        if this.broken {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+
+        // planned once per write, however many times it's polled.
+        if this.planned.is_none() {
+            let delay = this.faults.pick_delay(&mut this.rng);
+            if !delay.is_zero() {
+                this.delay = Some(Box::pin(tokio::time::sleep(delay)));
+            }
+            this.planned = Some(this.faults.pick_fault(&mut this.rng, buf.len()));
+        }
+        if let Some(delay) = &mut this.delay {
+            ready!(delay.as_mut().poll(cx));
+            this.delay = None;
+        }
+
+        let inner = Pin::new(&mut this.inner);
+        let written = match this.planned.flatten() {
+            None => ready!(inner.poll_write(cx, buf)),
+            Some(Fault::Reset) => {
+                // dropped with a zero linger, the socket sends a RST.
+                let _ = socket2::SockRef::from(&this.inner).set_linger(Some(Duration::ZERO));
+                this.broken = true;
+                Err(io::Error::new(
+                    io::ErrorKind::ConnectionReset,
+                    "injected connection reset",
+                ))
+            }
+            Some(Fault::Truncate(len)) => {
+                ready!(inner.poll_write(cx, &buf[..len]))?;
+                this.broken = true;
+                Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "injected truncated write",
+                ))
+            }
+            Some(Fault::Corrupt(at, bit)) => {
+                let mut corrupted = buf.to_vec();
+                corrupted[at] ^= bit;
+                let n = ready!(inner.poll_write(cx, &corrupted))?;
+                if n <= at {
+                    // the byte is in what the caller writes next.
+                    this.planned = Some(Some(Fault::Corrupt(at - n, bit)));
+                    return Poll::Ready(Ok(n));
+                }
+                Ok(n)
+            }
+        };
+        this.planned = None;
+        Poll::Ready(written)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+fn parse<T: std::str::FromStr>(name: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for '{}'", value, name).into())
+}
+
+fn parse_probability(name: &str, value: &str) -> Result<f64> {
+    match parse::<f64>(name, value)? {
+        p if (0.0..=1.0).contains(&p) => Ok(p),
+        _ => Err(format!("'{}' must be between 0 and 1", name).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_faults_are_reproducible() {
+        let mut faults = Faults::default();
+        for (name, value) in [("jitter-ms", "10"), ("reset", "0.1"), ("corrupt", "0.3")] {
+            faults.set(name, value).unwrap();
+        }
+        let picks = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..100)
+                .map(|_| (faults.pick_delay(&mut rng), faults.pick_fault(&mut rng, 16)))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(picks(7), picks(7));
+        assert_ne!(picks(7), picks(8));
+
+        let picked = picks(7);
+        assert!(picked.iter().all(|(delay, _)| *delay <= faults.jitter));
+        assert!(picked.iter().any(|(_, f)| *f == Some(Fault::Reset)));
+        assert!(picked
+            .iter()
+            .any(|(_, f)| matches!(f, Some(Fault::Corrupt(..)))));
+        assert!(picked.iter().any(|(_, f)| f.is_none()));
+        assert!(!picked
+            .iter()
+            .any(|(_, f)| matches!(f, Some(Fault::Truncate(_)))));
+    }
+
+    #[test]
+    fn probabilities_are_checked() {
+        let mut faults = Faults::default();
+        assert!(faults.set("truncate", "1").is_ok());
+        assert!(faults.set("truncate", "1.5").is_err());
+        assert!(faults.set("corrupt", "often").is_err());
+        assert!(faults.set("latency", "1").is_err());
+        // an empty write can't go wrong.
+        assert_eq!(faults.pick_fault(&mut StdRng::seed_from_u64(0), 0), None);
+    }
+}
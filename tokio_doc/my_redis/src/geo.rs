@@ -0,0 +1,329 @@
+// Positions stored as sorted set scores, like redis: the longitude and
+// latitude are each cut into 2^26 steps, and the bits of both interleaved
+// into a 52 bit geohash, so that close positions get close scores.
+
+use std::collections::BTreeSet;
+
+const LON_MIN: f64 = -180.0;
+const LON_MAX: f64 = 180.0;
+// the limits of the Web Mercator projection.
+const LAT_MIN: f64 = -85.051_128_78;
+const LAT_MAX: f64 = 85.051_128_78;
+
+pub(crate) const STEPS: u32 = 26;
+
+// the radius redis computes distances with, in meters.
+const EARTH_RADIUS: f64 = 6_372_797.560_856;
+const MERCATOR_MAX: f64 = 20_037_726.37;
+
+// A position, in degrees.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Point {
+    pub(crate) lon: f64,
+    pub(crate) lat: f64,
+}
+
+impl Point {
+    // Check that the position can be indexed.
+    pub(crate) fn new(lon: f64, lat: f64) -> Result<Point, String> {
+        if !(LON_MIN..=LON_MAX).contains(&lon) || !(LAT_MIN..=LAT_MAX).contains(&lat) {
+            return Err(format!(
+                "ERR invalid longitude,latitude pair {:.6},{:.6}",
+                lon, lat
+            ));
+        }
+        Ok(Point { lon, lat })
+    }
+
+    // The full precision geohash, used as score.
+    pub(crate) fn hash(self) -> u64 {
+        encode(self, STEPS)
+    }
+
+    // The center of the cell of a full precision geohash.
+    pub(crate) fn from_hash(hash: u64) -> Point {
+        Cell::new(hash, STEPS).center()
+    }
+
+    // Great circle distance, in meters.
+    pub(crate) fn distance(self, other: Point) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let v = ((other.lon - self.lon).to_radians() / 2.0).sin();
+        if v == 0.0 {
+            return EARTH_RADIUS * (lat2 - lat1).abs();
+        }
+        let u = ((lat2 - lat1) / 2.0).sin();
+        let a = u * u + lat1.cos() * lat2.cos() * v * v;
+        2.0 * EARTH_RADIUS * a.sqrt().asin()
+    }
+}
+
+// The area of a search, around its center. Sizes are in meters.
+#[derive(Clone, Copy)]
+pub(crate) enum Shape {
+    Radius(f64),
+    Box { width: f64, height: f64 },
+}
+
+impl Shape {
+    // The distance of `point` to `center` if it is in the area.
+    pub(crate) fn contains(self, center: Point, point: Point) -> Option<f64> {
+        match self {
+            Shape::Radius(radius) => {
+                let distance = center.distance(point);
+                (distance <= radius).then_some(distance)
+            }
+            Shape::Box { width, height } => {
+                let lat_distance = EARTH_RADIUS * (point.lat - center.lat).to_radians().abs();
+                if lat_distance > height / 2.0 {
+                    return None;
+                }
+                let lon_distance = point.distance(Point {
+                    lon: center.lon,
+                    lat: point.lat,
+                });
+                if lon_distance > width / 2.0 {
+                    return None;
+                }
+                Some(center.distance(point))
+            }
+        }
+    }
+
+    fn half_extents(self) -> (f64, f64) {
+        match self {
+            Shape::Radius(radius) => (radius, radius),
+            Shape::Box { width, height } => (width / 2.0, height / 2.0),
+        }
+    }
+
+    // The score ranges, `min` included and `max` excluded, holding every
+    // position of the area: the cell of the center and its 8 neighbours, at
+    // the finest precision where they cover the area.
+    pub(crate) fn ranges(self, center: Point) -> Vec<(f64, f64)> {
+        let (half_width, half_height) = self.half_extents();
+
+        // a box around the area, in degrees.
+        let lat_delta = (half_height / EARTH_RADIUS).to_degrees();
+        // the widest, at the latitude closest to a pole: of a circle, or
+        // along a parallel for a box.
+        let cos = (center.lat.abs() + lat_delta).min(90.0).to_radians().cos();
+        let angle = half_width / EARTH_RADIUS;
+        let lon_delta = match ((angle.sin() / cos), (angle / 2.0).sin() / cos) {
+            (circle, parallel) if circle < 1.0 && parallel < 1.0 => {
+                circle.asin().max(2.0 * parallel.asin()).to_degrees()
+            }
+            _ => 180.0,
+        };
+        let (lon_min, lon_max) = (center.lon - lon_delta, center.lon + lon_delta);
+        let lat_min = (center.lat - lat_delta).max(LAT_MIN);
+        let lat_max = (center.lat + lat_delta).min(LAT_MAX);
+
+        let mut step = estimate_steps(half_width.max(half_height), center.lat);
+        let cell = loop {
+            let cell = Cell::new(encode(center, step), step);
+            let (width, height) = cell.size();
+            let covered = lon_min >= cell.lon.0 - width
+                && lon_max <= cell.lon.1 + width
+                && lat_min >= cell.lat.0 - height
+                && lat_max <= cell.lat.1 + height;
+            // at one step, the neighbours cover the whole map.
+            if covered || step == 1 {
+                break cell;
+            }
+            step -= 1;
+        };
+
+        let (width, height) = cell.size();
+        let middle = cell.center();
+        let mut hashes = BTreeSet::new();
+        for dlat in [-1.0, 0.0, 1.0] {
+            for dlon in [-1.0, 0.0, 1.0] {
+                let lat = middle.lat + dlat * height;
+                if !(LAT_MIN..=LAT_MAX).contains(&lat) {
+                    continue;
+                }
+                let mut lon = middle.lon + dlon * width;
+                if lon >= LON_MAX {
+                    lon -= 360.0;
+                } else if lon < LON_MIN {
+                    lon += 360.0;
+                }
+                hashes.insert(encode(Point { lon, lat }, step));
+            }
+        }
+
+        let shift = 2 * (STEPS - step);
+        hashes
+            .into_iter()
+            .map(|hash| ((hash << shift) as f64, ((hash + 1) << shift) as f64))
+            .collect()
+    }
+}
+
+// Meters in a unit of distance.
+pub(crate) fn unit(name: &str) -> Result<f64, &'static str> {
+    match name.to_ascii_lowercase().as_str() {
+        "m" => Ok(1.0),
+        "km" => Ok(1000.0),
+        "ft" => Ok(0.3048),
+        "mi" => Ok(1609.34),
+        _ => Err("ERR unsupported unit provided. please use M, KM, FT, MI"),
+    }
+}
+
+// The area of a geohash of `step` bits per coordinate.
+struct Cell {
+    lon: (f64, f64),
+    lat: (f64, f64),
+}
+
+impl Cell {
+    fn new(hash: u64, step: u32) -> Cell {
+        let (lat, lon) = deinterleave(hash);
+        let cells = (1u64 << step) as f64;
+        let lon_size = (LON_MAX - LON_MIN) / cells;
+        let lat_size = (LAT_MAX - LAT_MIN) / cells;
+        Cell {
+            lon: (
+                LON_MIN + lon as f64 * lon_size,
+                LON_MIN + (lon + 1) as f64 * lon_size,
+            ),
+            lat: (
+                LAT_MIN + lat as f64 * lat_size,
+                LAT_MIN + (lat + 1) as f64 * lat_size,
+            ),
+        }
+    }
+
+    fn size(&self) -> (f64, f64) {
+        (self.lon.1 - self.lon.0, self.lat.1 - self.lat.0)
+    }
+
+    fn center(&self) -> Point {
+        Point {
+            lon: ((self.lon.0 + self.lon.1) / 2.0).clamp(LON_MIN, LON_MAX),
+            lat: ((self.lat.0 + self.lat.1) / 2.0).clamp(LAT_MIN, LAT_MAX),
+        }
+    }
+}
+
+fn encode(point: Point, step: u32) -> u64 {
+    let cells = 1u64 << step;
+    let offset = |value: f64, min: f64, max: f64| {
+        let offset = ((value - min) / (max - min) * cells as f64) as u64;
+        // the maximum belongs to the last cell.
+        offset.min(cells - 1)
+    };
+    interleave(
+        offset(point.lat, LAT_MIN, LAT_MAX),
+        offset(point.lon, LON_MIN, LON_MAX),
+    )
+}
+
+// The bits of `even` at even positions, the bits of `odd` at odd ones.
+fn interleave(even: u64, odd: u64) -> u64 {
+    (0..32).fold(0, |hash, i| {
+        hash | (even >> i & 1) << (2 * i) | (odd >> i & 1) << (2 * i + 1)
+    })
+}
+
+fn deinterleave(hash: u64) -> (u64, u64) {
+    (0..32).fold((0, 0), |(even, odd), i| {
+        (
+            even | (hash >> (2 * i) & 1) << i,
+            odd | (hash >> (2 * i + 1) & 1) << i,
+        )
+    })
+}
+
+// The precision at which cells are about as large as `range` meters around
+// `lat`, as redis estimates it.
+fn estimate_steps(mut range: f64, lat: f64) -> u32 {
+    if range == 0.0 {
+        return STEPS;
+    }
+    let mut step: i32 = 1;
+    while range < MERCATOR_MAX {
+        range *= 2.0;
+        step += 1;
+    }
+    // so that the range is inside the neighbours in most cases.
+    step -= 2;
+    // cells get narrower towards the poles.
+    if lat.abs() > 66.0 {
+        step -= 1;
+        if lat.abs() > 80.0 {
+            step -= 1;
+        }
+    }
+    step.clamp(1, STEPS as i32) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn hash_round_trip() {
+        // Palermo, as in redis' documentation.
+        let palermo = Point::new(13.361389, 38.115556).unwrap();
+        assert_eq!(palermo.hash(), 3_479_099_956_230_698);
+        let decoded = Point::from_hash(palermo.hash());
+        assert!((decoded.lon - palermo.lon).abs() < 1e-5);
+        assert!((decoded.lat - palermo.lat).abs() < 1e-5);
+
+        let catania = Point::new(15.087269, 37.502669).unwrap();
+        assert!((palermo.distance(catania) - 166_274.15).abs() < 1.0);
+
+        assert!(Point::new(180.5, 0.0).is_err());
+        assert!(Point::new(0.0, 86.0).is_err());
+        let corner = Point::new(LON_MAX, LAT_MAX).unwrap();
+        assert_eq!(Point::from_hash(corner.hash()).lat.round(), 85.0);
+    }
+
+    #[test]
+    fn ranges_hold_every_point_of_the_area() {
+        let mut rng = StdRng::seed_from_u64(45);
+        for _ in 0..200 {
+            let center = Point::new(rng.gen_range(-180.0..180.0), rng.gen_range(-85.0..85.0));
+            let center = center.unwrap();
+            let shape = if rng.gen() {
+                Shape::Radius(rng.gen_range(1.0..2_000_000.0))
+            } else {
+                Shape::Box {
+                    width: rng.gen_range(1.0..4_000_000.0),
+                    height: rng.gen_range(1.0..4_000_000.0),
+                }
+            };
+            let ranges = shape.ranges(center);
+
+            for _ in 0..200 {
+                // points around the center, some of them in the area.
+                let (w, h) = shape.half_extents();
+                let lat = center.lat + rng.gen_range(-1.5..1.5) * (h / EARTH_RADIUS).to_degrees();
+                let lon = center.lon
+                    + rng.gen_range(-1.5..1.5) * (w / EARTH_RADIUS).to_degrees()
+                        / center.lat.to_radians().cos();
+                let point = match Point::new(lon, lat) {
+                    Ok(point) => Point::from_hash(point.hash()),
+                    Err(_) => continue,
+                };
+                if shape.contains(center, point).is_some() {
+                    let score = point.hash() as f64;
+                    assert!(
+                        ranges
+                            .iter()
+                            .any(|(min, max)| (*min..*max).contains(&score)),
+                        "{:?} around {:?} not in {:?}",
+                        point,
+                        center,
+                        ranges
+                    );
+                }
+            }
+        }
+    }
+}
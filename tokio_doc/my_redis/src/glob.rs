@@ -0,0 +1,117 @@
+// Redis-style glob matching: `*`, `?`, `[abc]`, `[^a-z]` and `\` escapes.
+pub(crate) fn matches(pattern: &[u8], s: &[u8]) -> bool {
+    let (mut p, mut i) = (0, 0);
+    // where to resume after the last `*`: pattern index and string index.
+    let mut star: Option<(usize, usize)> = None;
+
+    while i < s.len() {
+        let step = if p < pattern.len() {
+            match pattern[p] {
+                b'*' => {
+                    star = Some((p, i));
+                    p += 1;
+                    continue;
+                }
+                b'?' => Some(1),
+                b'[' => match_class(&pattern[p..], s[i]),
+                b'\\' if p + 1 < pattern.len() => (pattern[p + 1] == s[i]).then_some(2),
+                c => (c == s[i]).then_some(1),
+            }
+        } else {
+            None
+        };
+
+        match (step, star) {
+            (Some(len), _) => {
+                p += len;
+                i += 1;
+            }
+            // backtrack: let the last `*` eat one more byte.
+            (None, Some((star_p, star_i))) => {
+                star = Some((star_p, star_i + 1));
+                p = star_p + 1;
+                i = star_i + 1;
+            }
+            (None, None) => return false,
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+// Match `c` against the class at the start of `pattern`. Returns the length
+// of the class in the pattern if it matches.
+fn match_class(pattern: &[u8], c: u8) -> Option<usize> {
+    let mut p = 1;
+    let negate = pattern.get(p) == Some(&b'^');
+    if negate {
+        p += 1;
+    }
+
+    let mut matched = false;
+    loop {
+        match pattern.get(p) {
+            // unterminated class, treat it as ending here.
+            None => break,
+            Some(b']') => {
+                p += 1;
+                break;
+            }
+            Some(b'\\') if p + 1 < pattern.len() => {
+                matched |= pattern[p + 1] == c;
+                p += 2;
+            }
+            Some(&lo) if pattern.get(p + 1) == Some(&b'-') && p + 2 < pattern.len() => {
+                let hi = pattern[p + 2];
+                let (lo, hi) = if lo <= hi { (lo, hi) } else { (hi, lo) };
+                matched |= lo <= c && c <= hi;
+                p += 3;
+            }
+            Some(&other) => {
+                matched |= other == c;
+                p += 1;
+            }
+        }
+    }
+
+    (matched != negate).then_some(p)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::matches;
+
+    #[test]
+    fn glob_patterns() {
+        let cases: &[(&str, &str, bool)] = &[
+            ("*", "", true),
+            ("*", "anything", true),
+            ("h?llo", "hello", true),
+            ("h?llo", "hllo", false),
+            ("h*llo", "heeeello", true),
+            ("h*llo", "hello world", false),
+            ("h[ae]llo", "hallo", true),
+            ("h[ae]llo", "hillo", false),
+            ("h[^e]llo", "hallo", true),
+            ("h[^e]llo", "hello", false),
+            ("h[a-b]llo", "hbllo", true),
+            ("h[a-b]llo", "hcllo", false),
+            ("cache:*", "cache:user:1", true),
+            ("cache:*", "session:1", false),
+            ("*:1", "cache:user:1", true),
+            ("a\\*b", "a*b", true),
+            ("a\\*b", "axb", false),
+            ("*a*b", "xaxxb", true),
+        ];
+
+        for (pattern, s, expected) in cases {
+            assert_eq!(
+                matches(pattern.as_bytes(), s.as_bytes()),
+                *expected,
+                "{} ~ {}",
+                pattern,
+                s
+            );
+        }
+    }
+}
@@ -1 +1,42 @@
+mod acl;
+pub mod benchmark;
+mod bitmap;
+mod blocking;
+pub mod client;
+mod cluster;
+mod cmd;
+mod config;
 mod connection;
+mod db;
+mod dump;
+mod evict;
+pub mod faults;
+mod geo;
+mod glob;
+mod hll;
+mod listener;
+pub mod logging;
+mod metrics;
+mod monitor;
+mod parse;
+pub mod proxy;
+mod pubsub;
+mod registry;
+mod script;
+pub mod server;
+mod session;
+mod slowlog;
+mod stream;
+pub mod tls;
+mod tracking;
+mod zset;
+
+pub use client::Client;
+pub use config::Config;
+pub use connection::Connection;
+pub use db::{Db, WrongType};
+pub use evict::EvictionPolicy;
+pub use pubsub::KeyspaceEvents;
+pub use server::Server;
+
+pub const DEFAULT_PORT: u16 = 6379;
@@ -0,0 +1,158 @@
+use crate::connection::Stream;
+
+use std::future::{self, Future};
+use std::io;
+use std::time::Duration;
+use tokio::io::DuplexStream;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, Mutex};
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+
+// A source of client connections, so that one accept loop serves TCP, TLS
+// and unix sockets alike.
+pub(crate) trait Listener: Send + 'static {
+    // the socket returned by `accept`.
+    type Io: Send + 'static;
+    // the stream the protocol runs over once the handshake is done.
+    type Stream: Stream + 'static;
+
+    // Accept a connection, along with the address of the peer.
+    fn accept(&self) -> impl Future<Output = io::Result<(Self::Io, String)>> + Send;
+
+    // Enable TCP keepalive probes every `time`, for sockets that have them.
+    fn set_keepalive(_io: &Self::Io, _time: Duration) -> io::Result<()> {
+        Ok(())
+    }
+
+    // Turn an accepted socket into a stream. This runs in the connection
+    // task, so that a slow handshake doesn't hold up the accept loop.
+    fn handshake(
+        &self,
+        io: Self::Io,
+    ) -> impl Future<Output = io::Result<Self::Stream>> + Send + 'static;
+}
+
+impl Listener for TcpListener {
+    type Io = TcpStream;
+    type Stream = TcpStream;
+
+    async fn accept(&self) -> io::Result<(TcpStream, String)> {
+        let (socket, addr) = TcpListener::accept(self).await?;
+        // replies are small and flushed one by one, Nagle's algorithm would
+        // hold the replies of pipelined commands back.
+        socket.set_nodelay(true)?;
+        Ok((socket, addr.to_string()))
+    }
+
+    fn set_keepalive(io: &TcpStream, time: Duration) -> io::Result<()> {
+        set_tcp_keepalive(io, time)
+    }
+
+    fn handshake(
+        &self,
+        io: TcpStream,
+    ) -> impl Future<Output = io::Result<TcpStream>> + Send + 'static {
+        future::ready(Ok(io))
+    }
+}
+
+pub(crate) struct TlsListener {
+    pub(crate) listener: TcpListener,
+    pub(crate) acceptor: TlsAcceptor,
+}
+
+impl Listener for TlsListener {
+    type Io = TcpStream;
+    type Stream = TlsStream<TcpStream>;
+
+    async fn accept(&self) -> io::Result<(TcpStream, String)> {
+        Listener::accept(&self.listener).await
+    }
+
+    fn set_keepalive(io: &TcpStream, time: Duration) -> io::Result<()> {
+        set_tcp_keepalive(io, time)
+    }
+
+    fn handshake(
+        &self,
+        io: TcpStream,
+    ) -> impl Future<Output = io::Result<Self::Stream>> + Send + 'static {
+        self.acceptor.accept(io)
+    }
+}
+
+fn set_tcp_keepalive(socket: &TcpStream, time: Duration) -> io::Result<()> {
+    let keepalive = socket2::TcpKeepalive::new().with_time(time);
+    socket2::SockRef::from(socket).set_tcp_keepalive(&keepalive)
+}
+
+#[cfg(unix)]
+impl Listener for tokio::net::UnixListener {
+    type Io = tokio::net::UnixStream;
+    type Stream = tokio::net::UnixStream;
+
+    async fn accept(&self) -> io::Result<(Self::Io, String)> {
+        let (socket, _) = tokio::net::UnixListener::accept(self).await?;
+        // clients of a unix socket are unnamed, they go by the socket path
+        // like in redis.
+        let path = self.local_addr()?;
+        let path = path.as_pathname().unwrap_or(std::path::Path::new(""));
+        Ok((socket, format!("{}:0", path.display())))
+    }
+
+    fn handshake(
+        &self,
+        io: Self::Io,
+    ) -> impl Future<Output = io::Result<Self::Stream>> + Send + 'static {
+        future::ready(Ok(io))
+    }
+}
+
+// Clients of an embedded server connected in memory, see
+// `Server::connect`.
+pub(crate) struct MemoryListener(Mutex<mpsc::UnboundedReceiver<DuplexStream>>);
+
+impl MemoryListener {
+    pub(crate) fn new(connections: mpsc::UnboundedReceiver<DuplexStream>) -> MemoryListener {
+        MemoryListener(Mutex::new(connections))
+    }
+}
+
+impl Listener for MemoryListener {
+    type Io = DuplexStream;
+    type Stream = DuplexStream;
+
+    async fn accept(&self) -> io::Result<(DuplexStream, String)> {
+        match self.0.lock().await.recv().await {
+            // nothing to tell clients apart by.
+            Some(stream) => Ok((stream, "memory:0".to_string())),
+            // the server is being dropped.
+            None => future::pending().await,
+        }
+    }
+
+    fn handshake(
+        &self,
+        io: DuplexStream,
+    ) -> impl Future<Output = io::Result<DuplexStream>> + Send + 'static {
+        future::ready(Ok(io))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn keepalive_is_enabled_on_accepted_sockets() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = TcpStream::connect(addr).await.unwrap();
+
+        let (socket, peer) = Listener::accept(&listener).await.unwrap();
+        assert_eq!(peer, client.local_addr().unwrap().to_string());
+        TcpListener::set_keepalive(&socket, Duration::from_secs(60)).unwrap();
+        assert!(socket2::SockRef::from(&socket).keepalive().unwrap());
+    }
+}
@@ -0,0 +1,48 @@
+use mini_redis::Result;
+use std::str::FromStr;
+use tracing_subscriber::EnvFilter;
+
+// How the server writes its logs: lines for people, or one JSON object per
+// line for log shippers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<LogFormat, String> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("unknown log format '{}'", s)),
+        }
+    }
+}
+
+// Log to stderr in `format`. What is logged is set by `RUST_LOG`, e.g.
+// `RUST_LOG=my_redis=debug` for every connection and command, and defaults
+// to `info`.
+//
+// Connections are logged in a `connection` span (`id`, `peer`), commands in
+// a `command` span (`cmd`, `key`, `duration_us`, `result`) within it. The
+// command is not in a `name` field, JSON spans have their name there.
+pub fn init(format: LogFormat) -> Result<()> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr);
+
+    match format {
+        LogFormat::Text => builder.try_init(),
+        // the fields of the spans go along with each event.
+        LogFormat::Json => builder
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .try_init(),
+    }
+}
@@ -0,0 +1,344 @@
+use crate::server::{self, Shared};
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tracing::{error, warn};
+
+// Server-wide counters, updated by the connection tasks and read by INFO and
+// the Prometheus endpoint.
+pub(crate) struct Metrics {
+    started: Instant,
+    connected_clients: AtomicUsize,
+    total_connections: AtomicU64,
+    // connections refused over `maxclients`.
+    rejected_connections: AtomicU64,
+    total_commands: AtomicU64,
+    // keyed by the command name from the command table.
+    commands: Mutex<HashMap<&'static str, CommandStats>>,
+}
+
+// Number of latency buckets, see `CommandStats::latency`.
+pub(crate) const LATENCY_BUCKETS: usize = 32;
+
+#[derive(Clone, Copy, Default)]
+pub(crate) struct CommandStats {
+    pub(crate) calls: u64,
+    pub(crate) usec: u64,
+    pub(crate) failed: u64,
+    // calls by latency: bucket `i` counts the calls that took up to 2^i
+    // microseconds, and more than 2^(i-1).
+    pub(crate) latency: [u64; LATENCY_BUCKETS],
+}
+
+impl Metrics {
+    pub(crate) fn new() -> Metrics {
+        Metrics {
+            started: Instant::now(),
+            connected_clients: AtomicUsize::new(0),
+            total_connections: AtomicU64::new(0),
+            rejected_connections: AtomicU64::new(0),
+            total_commands: AtomicU64::new(0),
+            commands: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub(crate) fn connected_clients(&self) -> usize {
+        self.connected_clients.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn total_connections(&self) -> u64 {
+        self.total_connections.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn rejected_connections(&self) -> u64 {
+        self.rejected_connections.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn reject(&self) {
+        self.rejected_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn total_commands(&self) -> u64 {
+        self.total_commands.load(Ordering::Relaxed)
+    }
+
+    // Count a new connection. The returned guard un-counts it when dropped,
+    // so a connection task ending on an error is accounted for as well.
+    pub(crate) fn connect(self: &Arc<Self>) -> ClientGuard {
+        self.connected_clients.fetch_add(1, Ordering::Relaxed);
+        self.total_connections.fetch_add(1, Ordering::Relaxed);
+        ClientGuard {
+            metrics: self.clone(),
+        }
+    }
+
+    pub(crate) fn record(&self, name: &'static str, elapsed: Duration, failed: bool) {
+        self.total_commands.fetch_add(1, Ordering::Relaxed);
+
+        let mut commands = self.commands.lock().unwrap();
+        let stats = commands.entry(name).or_default();
+        let usec = elapsed.as_micros() as u64;
+        stats.calls += 1;
+        stats.usec += usec;
+        stats.latency[latency_bucket(usec)] += 1;
+        if failed {
+            stats.failed += 1;
+        }
+    }
+
+    // Snapshot of the per-command stats, sorted by command name.
+    pub(crate) fn commands(&self) -> Vec<(&'static str, CommandStats)> {
+        let commands = self.commands.lock().unwrap();
+        let mut stats: Vec<_> = commands.iter().map(|(k, v)| (*k, *v)).collect();
+        stats.sort_by_key(|(name, _)| *name);
+        stats
+    }
+}
+
+fn latency_bucket(usec: u64) -> usize {
+    // the smallest power of two >= usec.
+    let bucket = (u64::BITS - usec.saturating_sub(1).leading_zeros()) as usize;
+    bucket.min(LATENCY_BUCKETS - 1)
+}
+
+pub(crate) struct ClientGuard {
+    metrics: Arc<Metrics>,
+}
+
+impl Drop for ClientGuard {
+    fn drop(&mut self) {
+        self.metrics
+            .connected_clients
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// Render the metrics in the Prometheus text exposition format.
+pub(crate) fn render(shared: &Shared) -> String {
+    let metrics = &shared.metrics;
+    let mut out = String::new();
+
+    let mut metric = |name: &str, help: &str, kind: &str, value: String| {
+        let _ = writeln!(out, "# HELP my_redis_{} {}", name, help);
+        let _ = writeln!(out, "# TYPE my_redis_{} {}", name, kind);
+        let _ = writeln!(out, "my_redis_{} {}", name, value);
+    };
+    metric(
+        "uptime_seconds",
+        "Seconds since the server started.",
+        "gauge",
+        metrics.uptime().as_secs().to_string(),
+    );
+    metric(
+        "connected_clients",
+        "Number of open client connections.",
+        "gauge",
+        metrics.connected_clients().to_string(),
+    );
+    metric(
+        "connections_received_total",
+        "Connections accepted since start.",
+        "counter",
+        metrics.total_connections().to_string(),
+    );
+    metric(
+        "rejected_connections_total",
+        "Connections refused because of maxclients.",
+        "counter",
+        metrics.rejected_connections().to_string(),
+    );
+    metric(
+        "commands_processed_total",
+        "Commands processed since start.",
+        "counter",
+        metrics.total_commands().to_string(),
+    );
+    metric(
+        "used_memory_bytes",
+        "Estimated bytes held by the keyspace.",
+        "gauge",
+        shared.dbs.used_memory().to_string(),
+    );
+    metric(
+        "maxmemory_bytes",
+        "Configured memory limit, 0 if unlimited.",
+        "gauge",
+        shared.config.maxmemory.to_string(),
+    );
+    metric(
+        "expired_keys_total",
+        "Keys removed because their expire was reached.",
+        "counter",
+        shared.dbs.expired_keys().to_string(),
+    );
+    metric(
+        "evicted_keys_total",
+        "Keys removed to stay under maxmemory.",
+        "counter",
+        shared.dbs.evicted_keys().to_string(),
+    );
+    metric(
+        "keys",
+        "Number of keys in all the databases.",
+        "gauge",
+        shared.dbs.keys().to_string(),
+    );
+
+    let commands = metrics.commands();
+    let _ = writeln!(
+        out,
+        "# HELP my_redis_command_calls_total Calls per command."
+    );
+    let _ = writeln!(out, "# TYPE my_redis_command_calls_total counter");
+    for (name, stats) in &commands {
+        let _ = writeln!(
+            out,
+            "my_redis_command_calls_total{{cmd=\"{}\"}} {}",
+            name, stats.calls
+        );
+    }
+    let _ = writeln!(
+        out,
+        "# HELP my_redis_command_failed_calls_total Calls per command that returned an error."
+    );
+    let _ = writeln!(out, "# TYPE my_redis_command_failed_calls_total counter");
+    for (name, stats) in &commands {
+        let _ = writeln!(
+            out,
+            "my_redis_command_failed_calls_total{{cmd=\"{}\"}} {}",
+            name, stats.failed
+        );
+    }
+    let _ = writeln!(
+        out,
+        "# HELP my_redis_command_duration_seconds_total Time spent per command."
+    );
+    let _ = writeln!(
+        out,
+        "# TYPE my_redis_command_duration_seconds_total counter"
+    );
+    for (name, stats) in &commands {
+        let _ = writeln!(
+            out,
+            "my_redis_command_duration_seconds_total{{cmd=\"{}\"}} {}",
+            name,
+            stats.usec as f64 / 1e6
+        );
+    }
+
+    out
+}
+
+// How long a client of the metrics endpoint has to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Serve `render` over plain HTTP. Every request gets its own task and the
+// connection is closed after the response.
+pub(crate) async fn serve(
+    listener: TcpListener,
+    shared: Arc<Shared>,
+    mut stop: watch::Receiver<bool>,
+) {
+    loop {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            _ = server::stopped(&mut stop) => return,
+        };
+        let socket = match accepted {
+            Ok((socket, _)) => socket,
+            Err(e) => {
+                // e.g. out of file descriptors, don't spin on it.
+                error!("metrics accept error: {}", e);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+
+        let shared = shared.clone();
+        tokio::spawn(async move {
+            if let Err(e) = respond(socket, &shared).await {
+                warn!("metrics connection error: {}", e);
+            }
+        });
+    }
+}
+
+async fn respond(mut socket: TcpStream, shared: &Shared) -> std::io::Result<()> {
+    // only the request line matters, read until the end of the headers.
+    // Clients that never finish them don't keep the task around.
+    let mut buf = Vec::with_capacity(1024);
+    let read = async {
+        while !buf.windows(4).any(|w| w == b"\r\n\r\n") && buf.len() < 8192 {
+            if socket.read_buf(&mut buf).await? == 0 {
+                break;
+            }
+        }
+        Ok::<_, std::io::Error>(())
+    };
+    tokio::time::timeout(READ_TIMEOUT, read)
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "request timed out"))??;
+
+    let request = String::from_utf8_lossy(&buf);
+    let path = request.split_whitespace().nth(1).unwrap_or("");
+
+    let (status, content_type, body) = if path == "/metrics" {
+        ("200 OK", "text/plain; version=0.0.4", render(shared))
+    } else {
+        ("404 Not Found", "text/plain", "not found\n".to_string())
+    };
+
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    socket.write_all(head.as_bytes()).await?;
+    socket.write_all(body.as_bytes()).await?;
+    socket.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    #[test]
+    fn render_includes_per_command_series() {
+        let shared = Shared::new(Config::default()).unwrap();
+        shared
+            .metrics
+            .record("get", Duration::from_micros(1500), false);
+        shared
+            .metrics
+            .record("get", Duration::from_micros(500), true);
+
+        let text = render(&shared);
+        assert!(text.contains("# TYPE my_redis_connected_clients gauge\n"));
+        assert!(text.contains("my_redis_command_calls_total{cmd=\"get\"} 2\n"));
+        assert!(text.contains("my_redis_command_failed_calls_total{cmd=\"get\"} 1\n"));
+        assert!(text.contains("my_redis_command_duration_seconds_total{cmd=\"get\"} 0.002\n"));
+    }
+
+    #[test]
+    fn latency_buckets() {
+        assert_eq!(latency_bucket(0), 0);
+        assert_eq!(latency_bucket(1), 0);
+        assert_eq!(latency_bucket(2), 1);
+        assert_eq!(latency_bucket(3), 2);
+        assert_eq!(latency_bucket(1024), 10);
+        assert_eq!(latency_bucket(1025), 11);
+        assert_eq!(latency_bucket(u64::MAX), LATENCY_BUCKETS - 1);
+    }
+}
@@ -0,0 +1,139 @@
+use crate::pubsub::Sender;
+use crate::slowlog::redact;
+
+use bytes::Bytes;
+use mini_redis::Frame;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Clients that ran MONITOR, by client id. Every command processed is sent
+// to them, as a line like redis':
+//
+//     +1700000000.123456 [0 127.0.0.1:50000] "set" "key" "value"
+pub(crate) struct Monitors {
+    feeds: Mutex<HashMap<u64, Sender>>,
+    // checked before building a line, so that connections don't pay for
+    // monitoring while nobody monitors.
+    count: AtomicUsize,
+}
+
+impl Monitors {
+    pub(crate) fn new() -> Monitors {
+        Monitors {
+            feeds: Mutex::new(HashMap::new()),
+            count: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn add(&self, id: u64, sender: &Sender) {
+        let mut feeds = self.feeds.lock().unwrap();
+        feeds.insert(id, sender.clone());
+        self.count.store(feeds.len(), Ordering::Relaxed);
+    }
+
+    pub(crate) fn remove(&self, id: u64) {
+        let mut feeds = self.feeds.lock().unwrap();
+        feeds.remove(&id);
+        self.count.store(feeds.len(), Ordering::Relaxed);
+    }
+
+    pub(crate) fn active(&self) -> bool {
+        self.count.load(Ordering::Relaxed) > 0
+    }
+
+    // Send a command run by client `id` on `db` to the monitors, but the
+    // client itself. `addr` is "lua" for commands called by scripts.
+    pub(crate) fn feed(&self, id: u64, db: usize, addr: &str, argv: &[Bytes]) {
+        let feeds = self.feeds.lock().unwrap();
+        if feeds.keys().all(|monitor| *monitor == id) {
+            return;
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let mut line = format!(
+            "{}.{:06} [{} {}]",
+            now.as_secs(),
+            now.subsec_micros(),
+            db,
+            addr
+        );
+        let mut argv = argv.to_vec();
+        redact(&mut argv);
+        for arg in &argv {
+            line.push(' ');
+            quote(&mut line, arg);
+        }
+
+        let frame = Frame::Simple(line);
+        for (monitor, sender) in feeds.iter() {
+            if *monitor != id {
+                // the receiver is gone when the monitor is disconnecting.
+                let _ = sender.send(frame.clone());
+            }
+        }
+    }
+}
+
+// Quote an argument so that the line stays on one line, like redis'
+// `sdscatrepr`.
+fn quote(line: &mut String, arg: &[u8]) {
+    line.push('"');
+    for &b in arg {
+        match b {
+            b'\\' => line.push_str("\\\\"),
+            b'"' => line.push_str("\\\""),
+            b'\n' => line.push_str("\\n"),
+            b'\r' => line.push_str("\\r"),
+            b'\t' => line.push_str("\\t"),
+            0x07 => line.push_str("\\a"),
+            0x08 => line.push_str("\\b"),
+            b' '..=b'~' => line.push(b as char),
+            b => {
+                let _ = write!(line, "\\x{:02x}", b);
+            }
+        }
+    }
+    line.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn feeds_other_clients() {
+        let monitors = Monitors::new();
+        assert!(!monitors.active());
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        monitors.add(1, &tx);
+        assert!(monitors.active());
+
+        let argv = [
+            Bytes::from("set"),
+            Bytes::from("k"),
+            Bytes::from("a \"b\"\n\x01"),
+        ];
+        monitors.feed(2, 3, "127.0.0.1:5000", &argv);
+        let line = match rx.try_recv() {
+            Ok(Frame::Simple(line)) => line,
+            frame => panic!("expected simple frame, got {:?}", frame),
+        };
+        let (timestamp, rest) = line.split_once(' ').unwrap();
+        assert_eq!(timestamp.split_once('.').unwrap().1.len(), 6);
+        assert_eq!(rest, r#"[3 127.0.0.1:5000] "set" "k" "a \"b\"\n\x01""#);
+
+        // a monitor doesn't see its own commands.
+        monitors.feed(1, 0, "127.0.0.1:5001", &argv);
+        assert!(rx.try_recv().is_err());
+
+        monitors.remove(1);
+        assert!(!monitors.active());
+    }
+}
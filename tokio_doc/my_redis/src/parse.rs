@@ -0,0 +1,48 @@
+use bytes::Bytes;
+use mini_redis::Result;
+use std::{str, vec};
+
+// Cursor over the arguments of a command, the command name excluded.
+// Each command handler pulls the fields it expects in order.
+pub(crate) struct Parse {
+    parts: vec::IntoIter<Bytes>,
+}
+
+impl Parse {
+    pub(crate) fn new(args: Vec<Bytes>) -> Parse {
+        Parse {
+            parts: args.into_iter(),
+        }
+    }
+
+    // Number of arguments not consumed yet.
+    pub(crate) fn remaining(&self) -> usize {
+        self.parts.len()
+    }
+
+    pub(crate) fn next_bytes(&mut self) -> Result<Bytes> {
+        self.parts
+            .next()
+            .ok_or_else(|| "ERR wrong number of arguments".into())
+    }
+
+    pub(crate) fn next_string(&mut self) -> Result<String> {
+        let bytes = self.next_bytes()?;
+
+        str::from_utf8(&bytes[..])
+            .map(|s| s.to_string())
+            .map_err(|_| "ERR invalid string".into())
+    }
+
+    // Same as `next_string`, but lowercased so that options can be matched
+    // case-insensitively.
+    pub(crate) fn next_option(&mut self) -> Result<String> {
+        Ok(self.next_string()?.to_ascii_lowercase())
+    }
+
+    pub(crate) fn next_int(&mut self) -> Result<u64> {
+        self.next_string()?
+            .parse()
+            .map_err(|_| "ERR value is not an integer or out of range".into())
+    }
+}
@@ -0,0 +1,268 @@
+use crate::faults::Faults;
+use crate::server::stopped;
+
+use mini_redis::Result;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::{error, info, info_span, warn, Instrument};
+
+// A TCP proxy forwarding each client on `listen` to its own connection to
+// `upstream`, e.g. to put a server behind the faults of a test.
+#[derive(Clone, Debug)]
+pub struct Proxy {
+    pub listen: String,
+    pub upstream: String,
+    // clients whose upstream connection isn't up by then are closed.
+    pub connect_timeout: Duration,
+    // injected in what the clients are sent.
+    pub faults: Faults,
+}
+
+// Totals over the connections of a proxy, updated as bytes go through.
+#[derive(Debug, Default)]
+pub struct Stats {
+    connections: AtomicU64,
+    connect_errors: AtomicU64,
+    upstream_bytes: AtomicU64,
+    client_bytes: AtomicU64,
+}
+
+// A proxy forwarding in the background, until shut down or dropped.
+pub struct Running {
+    addr: SocketAddr,
+    stats: Arc<Stats>,
+    stop: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+impl Default for Proxy {
+    fn default() -> Proxy {
+        Proxy {
+            listen: "127.0.0.1:0".to_string(),
+            upstream: format!("127.0.0.1:{}", crate::DEFAULT_PORT),
+            connect_timeout: Duration::from_secs(5),
+            faults: Faults::default(),
+        }
+    }
+}
+
+impl Proxy {
+    // Build a proxy from `--name value` pairs, like `Config::from_args`.
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Proxy> {
+        let mut proxy = Proxy::default();
+        while let Some(arg) = args.next() {
+            let name = arg
+                .strip_prefix("--")
+                .ok_or_else(|| format!("unexpected argument '{}'", arg))?;
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for '{}'", arg))?;
+            proxy.set(name, &value)?;
+        }
+        Ok(proxy)
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        match name {
+            "listen" => self.listen = value.to_string(),
+            "upstream" => self.upstream = value.to_string(),
+            "connect-timeout-ms" => {
+                let ms = value
+                    .parse()
+                    .map_err(|_| format!("invalid value '{}' for '{}'", value, name))?;
+                self.connect_timeout = Duration::from_millis(ms);
+            }
+            _ => self.faults.set(name, value)?,
+        }
+        Ok(())
+    }
+
+    // Listen and forward in the background. Must be called within a tokio
+    // runtime.
+    pub async fn start(self) -> Result<Running> {
+        let listener = TcpListener::bind(&self.listen).await?;
+        let addr = listener.local_addr()?;
+        info!(%addr, upstream = %self.upstream, "listening");
+
+        let stats = Arc::new(Stats::default());
+        let (stop, stopped) = watch::channel(false);
+        let task = tokio::spawn(serve(listener, self, stats.clone(), stopped));
+
+        Ok(Running {
+            addr,
+            stats,
+            stop,
+            task,
+        })
+    }
+}
+
+impl Stats {
+    // Clients accepted.
+    pub fn connections(&self) -> u64 {
+        self.connections.load(Ordering::Relaxed)
+    }
+
+    // Clients closed because the upstream couldn't be reached in time.
+    pub fn connect_errors(&self) -> u64 {
+        self.connect_errors.load(Ordering::Relaxed)
+    }
+
+    // Bytes read from the clients, for the upstream.
+    pub fn upstream_bytes(&self) -> u64 {
+        self.upstream_bytes.load(Ordering::Relaxed)
+    }
+
+    // Bytes read from the upstream, for the clients.
+    pub fn client_bytes(&self) -> u64 {
+        self.client_bytes.load(Ordering::Relaxed)
+    }
+}
+
+impl Running {
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    // Stop accepting clients and close the connections.
+    pub async fn shutdown(self) {
+        let _ = self.stop.send(true);
+        let _ = self.task.await;
+    }
+}
+
+async fn serve(
+    listener: TcpListener,
+    proxy: Proxy,
+    stats: Arc<Stats>,
+    mut stop: watch::Receiver<bool>,
+) {
+    let proxy = Arc::new(proxy);
+    let mut connections = JoinSet::new();
+
+    loop {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            Some(_) = connections.join_next() => continue,
+            _ = stopped(&mut stop) => break,
+        };
+        let (socket, addr) = match accepted {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                error!("accept error: {}", e);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        let n = stats.connections.fetch_add(1, Ordering::Relaxed);
+
+        let (proxy, stats) = (proxy.clone(), stats.clone());
+        let connection = async move {
+            if let Err(e) = forward(socket, n, &proxy, &stats).await {
+                warn!("connection error: {}", e);
+            }
+        };
+        connections.spawn(connection.instrument(info_span!("connection", peer = %addr)));
+    }
+
+    connections.shutdown().await;
+}
+
+// Forward the `n`th client of the proxy.
+async fn forward(client: TcpStream, n: u64, proxy: &Proxy, stats: &Stats) -> io::Result<()> {
+    let connect = TcpStream::connect(&proxy.upstream);
+    let upstream = match tokio::time::timeout(proxy.connect_timeout, connect).await {
+        Ok(Ok(upstream)) => upstream,
+        Ok(Err(e)) => {
+            stats.connect_errors.fetch_add(1, Ordering::Relaxed);
+            return Err(e);
+        }
+        Err(_) => {
+            stats.connect_errors.fetch_add(1, Ordering::Relaxed);
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("connecting to {} timed out", proxy.upstream),
+            ));
+        }
+    };
+    // the proxy shouldn't add the latency of Nagle's algorithm.
+    client.set_nodelay(true)?;
+    upstream.set_nodelay(true)?;
+
+    let mut client = Counted::new(proxy.faults.wrap(client, n), &stats.upstream_bytes);
+    let mut upstream = Counted::new(upstream, &stats.client_bytes);
+
+    // once a side is done writing, its peer's write half is shut down and
+    // the other direction keeps going until it's done too.
+    let copied = tokio::io::copy_bidirectional(&mut client, &mut upstream).await;
+    info!(
+        upstream_bytes = client.count,
+        client_bytes = upstream.count,
+        "connection closed"
+    );
+    copied.map(|_| ())
+}
+
+// A stream counting the bytes read from it, in `count` and in a total shared
+// with the other connections.
+struct Counted<'a, S> {
+    inner: S,
+    count: u64,
+    total: &'a AtomicU64,
+}
+
+impl<'a, S> Counted<'a, S> {
+    fn new(inner: S, total: &'a AtomicU64) -> Counted<'a, S> {
+        Counted {
+            inner,
+            count: 0,
+            total,
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Counted<'_, S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        let read = (buf.filled().len() - before) as u64;
+        self.count += read;
+        self.total.fetch_add(read, Ordering::Relaxed);
+        poll
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Counted<'_, S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
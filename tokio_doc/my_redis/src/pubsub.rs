@@ -0,0 +1,247 @@
+use crate::glob;
+
+use bytes::Bytes;
+use mini_redis::Frame;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+use tokio::sync::mpsc;
+
+// Where messages for a client go. The connection task writes them out
+// between commands.
+pub(crate) type Sender = mpsc::UnboundedSender<Frame>;
+
+// Channels and patterns clients subscribed to, by client id.
+pub(crate) struct PubSub {
+    state: Mutex<State>,
+    events: KeyspaceEvents,
+}
+
+#[derive(Default)]
+struct State {
+    channels: HashMap<Bytes, HashMap<u64, Sender>>,
+    patterns: HashMap<Bytes, HashMap<u64, Sender>>,
+}
+
+impl PubSub {
+    pub(crate) fn new(events: KeyspaceEvents) -> PubSub {
+        PubSub {
+            state: Mutex::new(State::default()),
+            events,
+        }
+    }
+
+    pub(crate) fn subscribe(&self, channel: Bytes, id: u64, sender: &Sender) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .channels
+            .entry(channel)
+            .or_default()
+            .insert(id, sender.clone());
+    }
+
+    pub(crate) fn unsubscribe(&self, channel: &Bytes, id: u64) {
+        let mut state = self.state.lock().unwrap();
+        remove(&mut state.channels, channel, id);
+    }
+
+    pub(crate) fn psubscribe(&self, pattern: Bytes, id: u64, sender: &Sender) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .patterns
+            .entry(pattern)
+            .or_default()
+            .insert(id, sender.clone());
+    }
+
+    pub(crate) fn punsubscribe(&self, pattern: &Bytes, id: u64) {
+        let mut state = self.state.lock().unwrap();
+        remove(&mut state.patterns, pattern, id);
+    }
+
+    // Drop every subscription of a client, e.g. when it disconnects.
+    pub(crate) fn unsubscribe_all(&self, id: u64) {
+        let state = &mut *self.state.lock().unwrap();
+        for subscribers in [&mut state.channels, &mut state.patterns] {
+            subscribers.retain(|_, clients| {
+                clients.remove(&id);
+                !clients.is_empty()
+            });
+        }
+    }
+
+    // Send `message` to the subscribers of `channel`, returns the number of
+    // clients that received it.
+    pub(crate) fn publish(&self, channel: &Bytes, message: &Bytes) -> usize {
+        let state = self.state.lock().unwrap();
+        let mut received = 0;
+
+        if let Some(clients) = state.channels.get(channel) {
+            let frame = push(&[b"message", channel, message]);
+            for sender in clients.values() {
+                // the receiver is gone when the client is disconnecting.
+                if sender.send(frame.clone()).is_ok() {
+                    received += 1;
+                }
+            }
+        }
+
+        for (pattern, clients) in &state.patterns {
+            if !glob::matches(pattern, channel) {
+                continue;
+            }
+            let frame = push(&[b"pmessage", pattern, channel, message]);
+            for sender in clients.values() {
+                if sender.send(frame.clone()).is_ok() {
+                    received += 1;
+                }
+            }
+        }
+
+        received
+    }
+
+    // Publish a keyspace event, if events of `class` are enabled. `event`
+    // is the name of the operation, e.g. "set" or "expired".
+    pub(crate) fn notify(&self, class: u32, event: &str, db: usize, key: &str) {
+        let events = self.events;
+        if events.0 & class == 0 {
+            return;
+        }
+
+        if events.0 & KeyspaceEvents::KEYSPACE != 0 {
+            let channel = Bytes::from(format!("__keyspace@{}__:{}", db, key));
+            self.publish(&channel, &Bytes::copy_from_slice(event.as_bytes()));
+        }
+        if events.0 & KeyspaceEvents::KEYEVENT != 0 {
+            let channel = Bytes::from(format!("__keyevent@{}__:{}", db, event));
+            self.publish(&channel, &Bytes::copy_from_slice(key.as_bytes()));
+        }
+    }
+}
+
+fn remove(subscribers: &mut HashMap<Bytes, HashMap<u64, Sender>>, name: &Bytes, id: u64) {
+    if let Some(clients) = subscribers.get_mut(name) {
+        clients.remove(&id);
+        if clients.is_empty() {
+            subscribers.remove(name);
+        }
+    }
+}
+
+// A pub/sub message: an array of bulk strings, the kind first.
+fn push(parts: &[&[u8]]) -> Frame {
+    Frame::Array(
+        parts
+            .iter()
+            .map(|part| Frame::Bulk(Bytes::copy_from_slice(part)))
+            .collect(),
+    )
+}
+
+// Which keyspace events are published, in the `notify-keyspace-events`
+// format: `K` and/or `E` for the keyspace and keyevent channels, then the
+// classes of events, e.g. "Ex" for the keyevent channel of expired keys.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KeyspaceEvents(u32);
+
+impl KeyspaceEvents {
+    pub(crate) const KEYSPACE: u32 = 1 << 0;
+    pub(crate) const KEYEVENT: u32 = 1 << 1;
+    pub(crate) const GENERIC: u32 = 1 << 2;
+    pub(crate) const STRING: u32 = 1 << 3;
+    pub(crate) const EXPIRED: u32 = 1 << 4;
+    pub(crate) const EVICTED: u32 = 1 << 5;
+    pub(crate) const STREAM: u32 = 1 << 6;
+    pub(crate) const ZSET: u32 = 1 << 7;
+    const ALL: u32 =
+        Self::GENERIC | Self::STRING | Self::EXPIRED | Self::EVICTED | Self::STREAM | Self::ZSET;
+
+    const FLAGS: &'static [(char, u32)] = &[
+        ('K', Self::KEYSPACE),
+        ('E', Self::KEYEVENT),
+        ('g', Self::GENERIC),
+        ('$', Self::STRING),
+        ('x', Self::EXPIRED),
+        ('e', Self::EVICTED),
+        ('t', Self::STREAM),
+        ('z', Self::ZSET),
+    ];
+}
+
+impl FromStr for KeyspaceEvents {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<KeyspaceEvents, String> {
+        let mut flags = 0;
+        for c in s.chars() {
+            flags |= match c {
+                'A' => KeyspaceEvents::ALL,
+                c => KeyspaceEvents::FLAGS
+                    .iter()
+                    .find(|(flag, _)| *flag == c)
+                    .map(|(_, bit)| *bit)
+                    .ok_or_else(|| format!("unknown keyspace event class '{}'", c))?,
+            };
+        }
+
+        // like redis, classes without a channel type publish nothing.
+        if flags & (KeyspaceEvents::KEYSPACE | KeyspaceEvents::KEYEVENT) == 0 {
+            flags = 0;
+        }
+        Ok(KeyspaceEvents(flags))
+    }
+}
+
+impl fmt::Display for KeyspaceEvents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut classes = String::new();
+        if self.0 & KeyspaceEvents::ALL == KeyspaceEvents::ALL {
+            classes.push('A');
+        }
+        for (flag, bit) in KeyspaceEvents::FLAGS {
+            let in_all = bit & KeyspaceEvents::ALL != 0;
+            if self.0 & bit != 0 && !(in_all && classes.starts_with('A')) {
+                classes.push(*flag);
+            }
+        }
+        f.write_str(&classes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyspace_events_format() {
+        let events: KeyspaceEvents = "Kx".parse().unwrap();
+        assert_eq!(events.0, KeyspaceEvents::KEYSPACE | KeyspaceEvents::EXPIRED);
+        assert_eq!(events.to_string(), "Kx");
+
+        let events: KeyspaceEvents = "KEA".parse().unwrap();
+        assert_eq!(events.to_string(), "AKE");
+        assert_eq!("AKE".parse::<KeyspaceEvents>().unwrap(), events);
+
+        // no channel type, nothing published.
+        assert_eq!("g$".parse::<KeyspaceEvents>().unwrap().0, 0);
+        assert!("K?".parse::<KeyspaceEvents>().is_err());
+    }
+
+    #[test]
+    fn publish_reaches_channels_and_patterns() {
+        let pubsub = PubSub::new(KeyspaceEvents::default());
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        pubsub.subscribe(Bytes::from("news"), 1, &tx);
+        pubsub.psubscribe(Bytes::from("n*"), 1, &tx);
+
+        assert_eq!(pubsub.publish(&Bytes::from("news"), &Bytes::from("hi")), 2);
+        assert_eq!(pubsub.publish(&Bytes::from("other"), &Bytes::from("hi")), 0);
+        assert!(matches!(rx.try_recv(), Ok(Frame::Array(parts)) if parts.len() == 3));
+        assert!(matches!(rx.try_recv(), Ok(Frame::Array(parts)) if parts.len() == 4));
+
+        pubsub.unsubscribe_all(1);
+        assert_eq!(pubsub.publish(&Bytes::from("news"), &Bytes::from("hi")), 0);
+    }
+}
@@ -0,0 +1,220 @@
+use crate::pubsub;
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::{watch, Notify};
+
+// The connections of a server, for CLIENT LIST/KILL/PAUSE.
+pub(crate) struct Registry {
+    clients: Mutex<BTreeMap<u64, Arc<Client>>>,
+    next_id: AtomicU64,
+    pause: watch::Sender<Option<Pause>>,
+}
+
+// A connected client. `info` is updated by the connection task around
+// every command it runs.
+pub(crate) struct Client {
+    pub(crate) id: u64,
+    pub(crate) addr: String,
+    // the pub/sub messages of the connection, e.g. for the invalidations
+    // redirected to it.
+    pub(crate) push: pubsub::Sender,
+    created: Instant,
+    killed: Notify,
+    pub(crate) info: Mutex<ClientInfo>,
+}
+
+#[derive(Default)]
+pub(crate) struct ClientInfo {
+    pub(crate) name: Option<String>,
+    pub(crate) user: Option<String>,
+    pub(crate) db: usize,
+    pub(crate) sub: usize,
+    pub(crate) psub: usize,
+    pub(crate) monitor: bool,
+    // the command running, or the last one that ran.
+    pub(crate) cmd: &'static str,
+    pub(crate) last_interaction: Option<Instant>,
+    // bytes read but not parsed yet, and room left in the read buffer.
+    pub(crate) qbuf: usize,
+    pub(crate) qbuf_free: usize,
+    // pub/sub messages waiting to be written.
+    pub(crate) oll: usize,
+}
+
+// Set by CLIENT PAUSE: commands, or only writes, wait until `until`.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Pause {
+    pub(crate) until: Instant,
+    pub(crate) writes_only: bool,
+}
+
+impl Registry {
+    pub(crate) fn new() -> Registry {
+        Registry {
+            clients: Mutex::new(BTreeMap::new()),
+            next_id: AtomicU64::new(1),
+            pause: watch::Sender::new(None),
+        }
+    }
+
+    // Add a connection, it is removed when the returned handle is dropped.
+    pub(crate) fn register(&self, addr: String, push: pubsub::Sender) -> Registration<'_> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let client = Arc::new(Client {
+            id,
+            addr,
+            push,
+            created: Instant::now(),
+            killed: Notify::new(),
+            info: Mutex::new(ClientInfo::default()),
+        });
+        self.clients.lock().unwrap().insert(id, client.clone());
+
+        Registration {
+            registry: self,
+            client,
+        }
+    }
+
+    pub(crate) fn clients(&self) -> Vec<Arc<Client>> {
+        self.clients.lock().unwrap().values().cloned().collect()
+    }
+
+    pub(crate) fn get(&self, id: u64) -> Option<Arc<Client>> {
+        self.clients.lock().unwrap().get(&id).cloned()
+    }
+
+    pub(crate) fn pause(&self, pause: Option<Pause>) {
+        self.pause.send_replace(pause);
+    }
+
+    // Wait for a pause to end. `write` tells whether the command waiting
+    // writes, writes only wait for a pause of writes.
+    pub(crate) async fn wait_unpaused(&self, write: bool) {
+        let mut pause = self.pause.subscribe();
+        loop {
+            let until = match *pause.borrow_and_update() {
+                Some(p) if (write || !p.writes_only) && p.until > Instant::now() => p.until,
+                _ => return,
+            };
+            // CLIENT UNPAUSE, or a new pause, wakes us up early.
+            tokio::select! {
+                _ = tokio::time::sleep_until(until.into()) => {}
+                _ = pause.changed() => {}
+            }
+        }
+    }
+}
+
+impl Client {
+    // Close the connection, once the command it runs, if any, replied.
+    pub(crate) fn kill(&self) {
+        self.killed.notify_one();
+    }
+
+    pub(crate) async fn killed(&self) {
+        self.killed.notified().await
+    }
+
+    // A CLIENT LIST line.
+    pub(crate) fn describe(&self, now: Instant) -> String {
+        let info = self.info.lock().unwrap();
+        let age = now.saturating_duration_since(self.created);
+        let idle = info
+            .last_interaction
+            .map_or(age, |at| now.saturating_duration_since(at));
+        let flags = if info.monitor {
+            "O"
+        } else if info.sub + info.psub > 0 {
+            "P"
+        } else {
+            "N"
+        };
+
+        let mut line = String::new();
+        let _ = write!(
+            line,
+            "id={} addr={} name={} age={} idle={} flags={} db={} sub={} psub={} qbuf={} qbuf-free={} oll={} cmd={} user={}",
+            self.id,
+            self.addr,
+            info.name.as_deref().unwrap_or(""),
+            age.as_secs(),
+            idle.as_secs(),
+            flags,
+            info.db,
+            info.sub,
+            info.psub,
+            info.qbuf,
+            info.qbuf_free,
+            info.oll,
+            if info.cmd.is_empty() { "NULL" } else { info.cmd },
+            info.user.as_deref().unwrap_or(""),
+        );
+        line
+    }
+}
+
+// A registered connection, see `Registry::register`.
+pub(crate) struct Registration<'a> {
+    registry: &'a Registry,
+    client: Arc<Client>,
+}
+
+impl std::ops::Deref for Registration<'_> {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        &self.client
+    }
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        self.registry
+            .clients
+            .lock()
+            .unwrap()
+            .remove(&self.client.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn clients_are_unregistered_on_drop() {
+        let registry = Registry::new();
+        let (push, _messages) = tokio::sync::mpsc::unbounded_channel();
+        let a = registry.register("127.0.0.1:1000".to_string(), push.clone());
+        let b = registry.register("127.0.0.1:1001".to_string(), push);
+        assert_eq!((a.id, b.id), (1, 2));
+
+        drop(a);
+        let clients = registry.clients();
+        assert_eq!(clients.len(), 1);
+        assert!(clients[0]
+            .describe(Instant::now())
+            .starts_with("id=2 addr=127.0.0.1:1001 name= age=0"));
+    }
+
+    #[tokio::test]
+    async fn pause_of_writes_lets_reads_through() {
+        let registry = Registry::new();
+        let until = Instant::now() + Duration::from_millis(50);
+        registry.pause(Some(Pause {
+            until,
+            writes_only: true,
+        }));
+
+        registry.wait_unpaused(false).await;
+        assert!(Instant::now() < until);
+        registry.wait_unpaused(true).await;
+        assert!(Instant::now() >= until);
+    }
+}
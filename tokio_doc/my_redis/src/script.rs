@@ -0,0 +1,284 @@
+use crate::cmd::{Command, Context};
+use crate::server::Shared;
+use crate::session::Session;
+
+use bytes::Bytes;
+use mini_redis::{Frame, Result};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Position, Scope, AST};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+// Server-side scripts, written in Rhai. A script sees its keys and
+// arguments as the `KEYS` and `ARGV` arrays and runs commands with
+// `redis_call("SET", KEYS[0], ARGV[0])`.
+pub(crate) struct Scripts {
+    engine: Engine,
+    // compiled scripts by the sha1 of their source.
+    cache: Mutex<HashMap<String, Arc<AST>>>,
+}
+
+// The script running on this thread. Rhai functions must be `'static`, so
+// `redis_call` finds the server and the caller's session through here.
+struct Running {
+    shared: Arc<Shared>,
+    session: Session,
+    deadline: Instant,
+}
+
+thread_local! {
+    static RUNNING: RefCell<Option<Running>> = const { RefCell::new(None) };
+}
+
+// Bounds on what a script may build, so that one can't exhaust the memory
+// of the server, or its stack by recursing.
+const MAX_STRING_SIZE: usize = 64 * 1024 * 1024;
+const MAX_ARRAY_SIZE: usize = 1024 * 1024;
+const MAX_MAP_SIZE: usize = 1024 * 1024;
+const MAX_CALL_LEVELS: usize = 32;
+
+impl Scripts {
+    pub(crate) fn new(time_limit: Duration) -> Scripts {
+        let mut engine = Engine::new();
+        engine.set_max_string_size(MAX_STRING_SIZE);
+        engine.set_max_array_size(MAX_ARRAY_SIZE);
+        engine.set_max_map_size(MAX_MAP_SIZE);
+        engine.set_max_call_levels(MAX_CALL_LEVELS);
+        // `print` and `debug` go to the log rather than to stdout.
+        engine.on_print(|text| info!(target: "script", "{}", text));
+        engine.on_debug(|text, _, pos| debug!(target: "script", %pos, "{}", text));
+
+        // `redis_call` stands for `redis.call` in Lua, `call` itself is a
+        // keyword in Rhai. There is no variadic function either, so it is
+        // registered once per number of arguments.
+        engine.register_fn("redis_call", |a: Dynamic| call(vec![a]));
+        engine.register_fn("redis_call", |a: Dynamic, b: Dynamic| call(vec![a, b]));
+        engine.register_fn("redis_call", |a: Dynamic, b: Dynamic, c: Dynamic| {
+            call(vec![a, b, c])
+        });
+        engine.register_fn(
+            "redis_call",
+            |a: Dynamic, b: Dynamic, c: Dynamic, d: Dynamic| call(vec![a, b, c, d]),
+        );
+        engine.register_fn(
+            "redis_call",
+            |a: Dynamic, b: Dynamic, c: Dynamic, d: Dynamic, e: Dynamic| call(vec![a, b, c, d, e]),
+        );
+        engine.register_fn(
+            "redis_call",
+            |a: Dynamic, b: Dynamic, c: Dynamic, d: Dynamic, e: Dynamic, f: Dynamic| {
+                call(vec![a, b, c, d, e, f])
+            },
+        );
+        // longer commands pass their arguments as an array.
+        engine.register_fn("redis_call", |args: Array| call(args));
+
+        // scripts hold every other client off, don't let one run forever.
+        engine.on_progress(move |_| {
+            let expired = RUNNING.with(|running| match &*running.borrow() {
+                Some(running) => Instant::now() > running.deadline,
+                None => false,
+            });
+            expired.then(|| {
+                Dynamic::from(format!(
+                    "ERR script killed after running for more than {}ms",
+                    time_limit.as_millis()
+                ))
+            })
+        });
+
+        Scripts {
+            engine,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Compile and cache `source`, returning its sha1 and the compiled
+    // script.
+    pub(crate) fn load(&self, source: &str) -> Result<(String, Arc<AST>)> {
+        let sha = sha1_smol::Sha1::from(source).digest().to_string();
+        if let Some(ast) = self.get(&sha) {
+            return Ok((sha, ast));
+        }
+
+        let ast = self
+            .engine
+            .compile(source)
+            .map_err(|e| format!("ERR Error compiling script: {}", e))?;
+        let ast = Arc::new(ast);
+        self.cache.lock().unwrap().insert(sha.clone(), ast.clone());
+
+        Ok((sha, ast))
+    }
+
+    pub(crate) fn get(&self, sha: &str) -> Option<Arc<AST>> {
+        self.cache
+            .lock()
+            .unwrap()
+            .get(&sha.to_ascii_lowercase())
+            .cloned()
+    }
+
+    pub(crate) fn exists(&self, sha: &str) -> bool {
+        self.cache
+            .lock()
+            .unwrap()
+            .contains_key(&sha.to_ascii_lowercase())
+    }
+
+    pub(crate) fn flush(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+
+    // Run a compiled script. The caller is expected to hold every other
+    // command off for the script to be atomic.
+    pub(crate) fn run(
+        &self,
+        ast: &AST,
+        ctx: &Context<'_>,
+        keys: Vec<Bytes>,
+        argv: Vec<Bytes>,
+    ) -> Result<Frame> {
+        let mut scope = Scope::new();
+        scope.push_constant("KEYS", to_array(keys));
+        scope.push_constant("ARGV", to_array(argv));
+
+        let running = Running {
+            shared: ctx.shared.clone(),
+            session: Session {
+                addr: "lua".to_string(),
+                ..ctx.session.clone()
+            },
+            deadline: Instant::now() + ctx.shared.config.script_time_limit,
+        };
+        RUNNING.with(|cell| *cell.borrow_mut() = Some(running));
+        let result = self.engine.eval_ast_with_scope::<Dynamic>(&mut scope, ast);
+        RUNNING.with(|cell| *cell.borrow_mut() = None);
+
+        match result {
+            Ok(value) => to_frame(value),
+            // errors raised by `redis_call` and the timeout carry a redis error
+            // message already.
+            Err(e) => match *e {
+                EvalAltResult::ErrorRuntime(msg, _) | EvalAltResult::ErrorTerminated(msg, _) => {
+                    Err(msg.to_string().into())
+                }
+                e => Err(format!("ERR Error running script: {}", e).into()),
+            },
+        }
+    }
+}
+
+// `redis_call` from a script: run a command and convert the reply.
+fn call(args: Array) -> std::result::Result<Dynamic, Box<EvalAltResult>> {
+    let mut parts = Vec::with_capacity(args.len());
+    for arg in args {
+        parts.push(to_bytes(arg)?);
+    }
+
+    let frame = RUNNING.with(|running| {
+        let mut running = running.borrow_mut();
+        let running = running
+            .as_mut()
+            .ok_or("ERR redis_call() used outside of a script")?;
+
+        let cmd = Command::from_args(parts)?;
+        if !cmd.allowed_in_scripts() {
+            return Err(format!(
+                "ERR This Redis command is not allowed from script: '{}'",
+                cmd.name()
+            )
+            .into());
+        }
+
+        let mut ctx = Context {
+            shared: &running.shared,
+            session: &mut running.session,
+        };
+        Ok::<_, mini_redis::Error>(cmd.run(&mut ctx))
+    });
+
+    match frame.map_err(|e| runtime_error(e.to_string()))? {
+        Frame::Error(msg) => Err(runtime_error(msg)),
+        frame => Ok(to_dynamic(frame)),
+    }
+}
+
+fn runtime_error(msg: String) -> Box<EvalAltResult> {
+    Box::new(EvalAltResult::ErrorRuntime(msg.into(), Position::NONE))
+}
+
+fn to_bytes(value: Dynamic) -> std::result::Result<Bytes, Box<EvalAltResult>> {
+    if value.is_string() {
+        return Ok(Bytes::from(value.into_string().unwrap()));
+    }
+    if value.is_blob() {
+        return Ok(Bytes::from(value.into_blob().unwrap()));
+    }
+    if let Ok(n) = value.as_int() {
+        return Ok(Bytes::from(n.to_string()));
+    }
+    if let Ok(f) = value.as_float() {
+        return Ok(Bytes::from(f.to_string()));
+    }
+
+    Err(runtime_error(format!(
+        "ERR Command arguments must be strings or integers, got {}",
+        value.type_name()
+    )))
+}
+
+fn to_array(values: Vec<Bytes>) -> Array {
+    values.into_iter().map(bytes_to_dynamic).collect()
+}
+
+// Strings are UTF-8 in Rhai, binary values become blobs.
+fn bytes_to_dynamic(bytes: Bytes) -> Dynamic {
+    match String::from_utf8(bytes.to_vec()) {
+        Ok(s) => Dynamic::from(s),
+        Err(e) => Dynamic::from_blob(e.into_bytes()),
+    }
+}
+
+fn to_dynamic(frame: Frame) -> Dynamic {
+    match frame {
+        Frame::Simple(s) | Frame::Error(s) => Dynamic::from(s),
+        Frame::Integer(n) => Dynamic::from(n as rhai::INT),
+        Frame::Bulk(bytes) => bytes_to_dynamic(bytes),
+        Frame::Null => Dynamic::UNIT,
+        Frame::Array(frames) => Dynamic::from_array(frames.into_iter().map(to_dynamic).collect()),
+    }
+}
+
+// Convert the value a script returns into a reply.
+fn to_frame(value: Dynamic) -> Result<Frame> {
+    if value.is_unit() {
+        return Ok(Frame::Null);
+    }
+    if let Ok(b) = value.as_bool() {
+        // like Lua scripts in redis: true is 1 and false is nil.
+        return Ok(if b { Frame::Integer(1) } else { Frame::Null });
+    }
+    if let Ok(n) = value.as_int() {
+        // `Frame` can't hold negative integers.
+        return Ok(match u64::try_from(n) {
+            Ok(n) => Frame::Integer(n),
+            Err(_) => Frame::Bulk(Bytes::from(n.to_string())),
+        });
+    }
+    if value.is_array() {
+        let frames = value
+            .into_array()
+            .unwrap()
+            .into_iter()
+            .map(to_frame)
+            .collect::<Result<Vec<_>>>()?;
+        return Ok(Frame::Array(frames));
+    }
+
+    to_bytes(value)
+        .map(Frame::Bulk)
+        .map_err(|_| "ERR unsupported script return type".into())
+}
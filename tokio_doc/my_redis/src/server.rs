@@ -0,0 +1,560 @@
+use crate::acl::Acl;
+use crate::blocking::Waker;
+use crate::cluster::Cluster;
+use crate::cmd::{Command, Context};
+use crate::connection::Stream;
+use crate::db::Databases;
+use crate::listener::{Listener, MemoryListener, TlsListener};
+use crate::metrics::{self, Metrics};
+use crate::monitor::Monitors;
+use crate::pubsub::{KeyspaceEvents, PubSub};
+use crate::registry::Registry;
+use crate::script::Scripts;
+use crate::session::Session;
+use crate::slowlog::SlowLog;
+use crate::tracking::Tracking;
+use crate::{tls, Client, Config, Connection};
+
+use bytes::Bytes;
+use mini_redis::{Frame, Result};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::DuplexStream;
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, watch, RwLock, Semaphore};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, debug_span, error, field, info, info_span, warn, Instrument, Span};
+
+// Bytes buffered each way by in-memory connections.
+const DUPLEX_BUFFER: usize = 64 * 1024;
+
+// State shared by every connection of a server.
+pub(crate) struct Shared {
+    pub(crate) dbs: Databases,
+    pub(crate) config: Config,
+    pub(crate) metrics: Arc<Metrics>,
+    pub(crate) acl: Acl,
+    pub(crate) scripts: Scripts,
+    pub(crate) pubsub: Arc<PubSub>,
+    // wakes up clients blocked on data.
+    pub(crate) waker: Waker,
+    // held shared by commands, and exclusively by the ones that must not
+    // interleave with others, see `Command::execute`.
+    pub(crate) exec_lock: RwLock<()>,
+    pub(crate) clients: Registry,
+    pub(crate) slowlog: SlowLog,
+    pub(crate) monitors: Monitors,
+    pub(crate) tracking: Arc<Tracking>,
+    // set in cluster mode.
+    pub(crate) cluster: Option<Cluster>,
+    // a permit per connection, up to `maxclients`.
+    max_clients: Arc<Semaphore>,
+}
+
+impl Shared {
+    pub(crate) fn new(config: Config) -> Result<Shared> {
+        let pubsub = Arc::new(PubSub::new(config.notify_keyspace_events));
+
+        if config.databases == 0 {
+            return Err("databases must be at least 1".into());
+        }
+
+        // keys dropped by the dbs themselves are notified from there.
+        let notify = pubsub.clone();
+        let tracking = Arc::new(Tracking::new());
+        let invalidate = tracking.clone();
+        let dbs = Databases::new(config.databases, move |db, event, key| {
+            let class = match event {
+                "expired" => KeyspaceEvents::EXPIRED,
+                _ => KeyspaceEvents::EVICTED,
+            };
+            notify.notify(class, event, db, key);
+            if invalidate.active() {
+                invalidate.invalidate([&Bytes::copy_from_slice(key.as_bytes())], None);
+            }
+        });
+
+        let cluster = match (&config.cluster_enabled, &config.cluster_node_id) {
+            (false, _) => None,
+            (true, Some(id)) => Some(Cluster::new(id, &config.cluster_nodes)?),
+            (true, None) => return Err("cluster-enabled requires cluster-node-id".into()),
+        };
+
+        Ok(Shared {
+            dbs,
+            cluster,
+            max_clients: Arc::new(Semaphore::new(config.maxclients)),
+            pubsub,
+            waker: Waker::default(),
+            acl: Acl::new(&config)?,
+            scripts: Scripts::new(config.script_time_limit),
+            exec_lock: RwLock::new(()),
+            slowlog: SlowLog::new(config.slowlog_max_len),
+            monitors: Monitors::new(),
+            tracking,
+            config,
+            metrics: Arc::new(Metrics::new()),
+            clients: Registry::new(),
+        })
+    }
+}
+
+// A server running in the background of the process, e.g. for the tests of
+// an application:
+//
+//     let server = Server::builder().start().await?;
+//     let mut client = server.client();
+//     client.set("hello", "world".into()).await?;
+//     server.shutdown().await;
+//
+// Dropping the handle stops the server too, without waiting for it.
+pub struct Server {
+    // `None` when the server doesn't listen on TCP.
+    addr: Option<SocketAddr>,
+    // streams for the in-memory listener.
+    connector: mpsc::UnboundedSender<DuplexStream>,
+    stop: watch::Sender<bool>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+// Where a server takes TCP clients from.
+enum Bind {
+    Addr(String, u16),
+    Listener(TcpListener),
+    None,
+}
+
+pub struct Builder {
+    config: Config,
+    bind: Bind,
+}
+
+impl Server {
+    // By default, the server has the default config and listens on an
+    // ephemeral port of 127.0.0.1.
+    pub fn builder() -> Builder {
+        Builder {
+            config: Config::default(),
+            bind: Bind::Addr("127.0.0.1".to_string(), 0),
+        }
+    }
+
+    // The TCP address the server listens on.
+    pub fn addr(&self) -> Option<SocketAddr> {
+        self.addr
+    }
+
+    // Open a connection to the server in memory, without going through the
+    // network.
+    pub fn connect(&self) -> DuplexStream {
+        let (client, server) = tokio::io::duplex(DUPLEX_BUFFER);
+        // the listener lives as long as the handle.
+        let _ = self.connector.send(server);
+        client
+    }
+
+    // A client connected in memory.
+    pub fn client(&self) -> Client {
+        Client::from_stream(Box::new(self.connect()))
+    }
+
+    // Stop accepting clients, close the connections and wait for the tasks
+    // of the server to end.
+    pub async fn shutdown(self) {
+        let _ = self.stop.send(true);
+        for task in self.tasks {
+            let _ = task.await;
+        }
+    }
+}
+
+impl Builder {
+    pub fn config(mut self, config: Config) -> Builder {
+        self.config = config;
+        self
+    }
+
+    // Listen on `host`:`port`, 0 for an ephemeral port.
+    pub fn bind(mut self, host: &str, port: u16) -> Builder {
+        self.bind = Bind::Addr(host.to_string(), port);
+        self
+    }
+
+    // Serve the clients of a listener bound by the caller.
+    pub fn listener(mut self, listener: TcpListener) -> Builder {
+        self.bind = Bind::Listener(listener);
+        self
+    }
+
+    // Don't listen on TCP, clients connect with `Server::connect`. The
+    // listeners enabled in the config are still started.
+    pub fn in_memory(mut self) -> Builder {
+        self.bind = Bind::None;
+        self
+    }
+
+    // Start serving in the background. Must be called within a tokio
+    // runtime.
+    pub async fn start(self) -> Result<Server> {
+        let shared = Arc::new(Shared::new(self.config)?);
+        let (stop, stopped) = watch::channel(false);
+        let mut tasks = Vec::new();
+
+        if let Some(port) = shared.config.metrics_port {
+            // the metrics endpoint is only exposed locally.
+            let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+            let serve = metrics::serve(listener, shared.clone(), stopped.clone());
+            tasks.push(tokio::spawn(serve));
+        }
+
+        if let Some(port) = shared.config.tls_port {
+            let config = &shared.config;
+            let (cert, key) = match (&config.tls_cert_file, &config.tls_key_file) {
+                (Some(cert), Some(key)) => (cert, key),
+                _ => return Err("tls-port requires tls-cert-file and tls-key-file".into()),
+            };
+            let acceptor = TlsAcceptor::from(tls::server_config(cert, key)?);
+
+            let listener = TcpListener::bind((config.bind.as_str(), port)).await?;
+            let listener = TlsListener { listener, acceptor };
+            tasks.push(tokio::spawn(serve(
+                listener,
+                shared.clone(),
+                stopped.clone(),
+            )));
+        }
+
+        if let Some(path) = &shared.config.unixsocket {
+            #[cfg(unix)]
+            {
+                let listener = bind_unix(path, shared.config.unixsocketperm)?;
+                tasks.push(tokio::spawn(serve(
+                    listener,
+                    shared.clone(),
+                    stopped.clone(),
+                )));
+            }
+            #[cfg(not(unix))]
+            return Err(format!("unixsocket {} is only supported on unix", path).into());
+        }
+
+        let listener = match self.bind {
+            Bind::Addr(host, port) => Some(TcpListener::bind((host.as_str(), port)).await?),
+            Bind::Listener(listener) => Some(listener),
+            Bind::None => None,
+        };
+        let addr = match listener {
+            Some(listener) => {
+                let addr = listener.local_addr()?;
+                info!(%addr, "listening");
+                tasks.push(tokio::spawn(serve(
+                    listener,
+                    shared.clone(),
+                    stopped.clone(),
+                )));
+                Some(addr)
+            }
+            None => None,
+        };
+
+        let (connector, connections) = mpsc::unbounded_channel();
+        let listener = MemoryListener::new(connections);
+        tasks.push(tokio::spawn(serve(
+            listener,
+            shared.clone(),
+            stopped.clone(),
+        )));
+
+        tasks.push(tokio::spawn(purge_expired_keys(shared, stopped)));
+
+        Ok(Server {
+            addr,
+            connector,
+            stop,
+            tasks,
+        })
+    }
+}
+
+// Serve the clients of `listener`, and of the other listeners enabled in
+// `config`, until the process exits.
+pub async fn run(listener: TcpListener, config: Config) -> Result<()> {
+    let _server = Server::builder()
+        .config(config)
+        .listener(listener)
+        .start()
+        .await?;
+
+    std::future::pending().await
+}
+
+// Resolves once the server is stopped, or its handle dropped.
+pub(crate) async fn stopped(stop: &mut watch::Receiver<bool>) {
+    let _ = stop.wait_for(|stopped| *stopped).await;
+}
+
+async fn serve<L: Listener>(listener: L, shared: Arc<Shared>, mut stop: watch::Receiver<bool>) {
+    let mut connections = JoinSet::new();
+
+    loop {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            // reaped so that the set doesn't grow with every client.
+            Some(_) = connections.join_next() => continue,
+            _ = stopped(&mut stop) => break,
+        };
+        let (io, addr) = match accepted {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                // e.g. out of file descriptors, give connections some time
+                // to close.
+                error!("accept error: {}", e);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+
+        if let Some(time) = shared.config.tcp_keepalive {
+            if let Err(e) = L::set_keepalive(&io, time) {
+                warn!("keepalive error: {}", e);
+            }
+        }
+
+        // taken now so that connections are admitted in order, and held by
+        // the connection task.
+        let permit = shared.max_clients.clone().try_acquire_owned().ok();
+
+        let handshake = listener.handshake(io);
+        let shared = shared.clone();
+        // the id is known once the client is registered.
+        let span = info_span!("connection", id = field::Empty, peer = %addr);
+        // the connection tasks are kept in a `JoinSet`, so that they are
+        // aborted when the server stops.
+        let connection = async move {
+            let stream = match handshake.await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!("handshake error: {}", e);
+                    return;
+                }
+            };
+            if permit.is_none() {
+                shared.metrics.reject();
+                let mut conn = Connection::new(stream);
+                let refused = Frame::Error("ERR max number of clients reached".to_string());
+                let _ = conn.write_frame(&refused).await;
+                return;
+            }
+
+            if let Err(e) = process(stream, addr, shared).await {
+                warn!("connection error: {}", e);
+            }
+        }
+        .instrument(span);
+        connections.spawn(connection);
+    }
+
+    // closes the connections.
+    connections.shutdown().await;
+}
+
+#[cfg(unix)]
+fn bind_unix(path: &str, perm: Option<u32>) -> Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::PermissionsExt;
+
+    // a socket file left over by a previous run would make `bind` fail.
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+
+    let listener = tokio::net::UnixListener::bind(path)?;
+    if let Some(mode) = perm {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    }
+    Ok(listener)
+}
+
+// Keys are dropped lazily when accessed after their expire, this task
+// takes care of the keys nobody accesses anymore.
+async fn purge_expired_keys(shared: Arc<Shared>, mut stop: watch::Receiver<bool>) {
+    let mut interval = tokio::time::interval(Duration::from_millis(100));
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = stopped(&mut stop) => return,
+        }
+
+        // keys don't expire while a script runs.
+        let _guard = shared.exec_lock.read().await;
+        shared.dbs.purge_expired();
+    }
+}
+
+async fn process<S: Stream>(socket: S, addr: String, shared: Arc<Shared>) -> Result<()> {
+    let _client = shared.metrics.connect();
+    let mut conn = Connection::new(socket);
+
+    let (push, mut messages) = mpsc::unbounded_channel();
+    let client = shared.clients.register(addr, push.clone());
+    let id = client.id;
+    Span::current().record("id", id);
+    debug!("client connected");
+    let mut session = Session::new(id, client.addr.clone(), shared.acl.auto_login(), push);
+    let _subscriber = Subscriber {
+        shared: &shared,
+        id,
+    };
+
+    loop {
+        // subscribed and monitoring clients may stay silent for good.
+        let idle_timeout = match shared.config.timeout {
+            Some(timeout) if session.subscriptions() == 0 && !session.monitoring => Some(timeout),
+            _ => None,
+        };
+
+        // wait for a command, writing out pub/sub messages meanwhile.
+        let frame = tokio::select! {
+            frame = conn.read_frame() => match frame? {
+                Some(frame) => frame,
+                None => break,
+            },
+            _ = sleep_for(idle_timeout) => break,
+            Some(message) = messages.recv() => {
+                conn.write_frame(&message).await?;
+                continue;
+            }
+            _ = client.killed() => break,
+        };
+
+        let resp = match Command::from_frame(frame) {
+            Ok(cmd) => {
+                let name = cmd.name();
+                client.info.lock().unwrap().cmd = name;
+                // kept for the slow log, the command itself is consumed.
+                let argv = shared.config.slowlog_log_slower_than.map(|_| cmd.argv());
+                let span = debug_span!(
+                    "command",
+                    cmd = name,
+                    key = field::Empty,
+                    duration_us = field::Empty,
+                    result = field::Empty,
+                );
+                if let Some(key) = cmd.keys().next().filter(|_| !span.is_disabled()) {
+                    span.record("key", String::from_utf8_lossy(key).as_ref());
+                }
+
+                // a client killed while blocked is closed right away.
+                let (resp, elapsed) = tokio::select! {
+                    done = execute(cmd, &shared, &mut session).instrument(span.clone()) => done,
+                    _ = client.killed() => break,
+                };
+
+                let failed = matches!(resp, Frame::Error(_));
+                span.record("duration_us", elapsed.as_micros() as u64);
+                span.record("result", if failed { "error" } else { "ok" });
+                span.in_scope(|| debug!("command executed"));
+                shared.metrics.record(name, elapsed, failed);
+                if let (Some(threshold), Some(argv)) = (shared.config.slowlog_log_slower_than, argv)
+                {
+                    if elapsed > threshold {
+                        let name = session.name.as_deref().unwrap_or("");
+                        shared.slowlog.push(&argv, elapsed, &client.addr, name);
+                    }
+                }
+                resp
+            }
+            Err(e) => Frame::Error(e.to_string()),
+        };
+
+        // what CLIENT LIST shows of this connection.
+        {
+            let mut info = client.info.lock().unwrap();
+            info.name.clone_from(&session.name);
+            info.user.clone_from(&session.user);
+            info.db = session.db;
+            info.sub = session.channels.len();
+            info.psub = session.patterns.len();
+            info.monitor = session.monitoring;
+            (info.qbuf, info.qbuf_free) = conn.read_buffer();
+            info.oll = messages.len();
+            info.last_interaction = Some(Instant::now());
+        }
+
+        for reply in session.replies.drain(..) {
+            conn.write_frame(&reply).await?;
+        }
+        conn.write_frame(&resp).await?;
+
+        if session.closing {
+            break;
+        }
+    }
+
+    debug!("client disconnected");
+    Ok(())
+}
+
+// Execute `cmd`. A blocking command that found no data is run again after
+// each write, until it replies or its deadline passes. Returns the reply and
+// the time spent executing, not waiting.
+async fn execute(
+    mut cmd: Command,
+    shared: &Arc<Shared>,
+    session: &mut Session,
+) -> (Frame, Duration) {
+    // CLIENT itself isn't paused, so that CLIENT UNPAUSE goes through.
+    if cmd.name() != "client" {
+        shared.clients.wait_unpaused(cmd.is_write()).await;
+    }
+
+    let mut elapsed = Duration::ZERO;
+    // the deadline of the first run holds for the retries.
+    let mut deadline = None;
+    session.retrying = false;
+
+    loop {
+        let start = Instant::now();
+        let mut ctx = Context { shared, session };
+        let resp = cmd.execute(&mut ctx).await;
+        elapsed += start.elapsed();
+
+        let blocked = match session.blocked.take() {
+            Some(blocked) => blocked,
+            None => return (resp, elapsed),
+        };
+
+        let deadline = *deadline.get_or_insert(blocked.deadline);
+        if !shared.waker.wait(blocked.version, deadline).await {
+            return (resp, elapsed);
+        }
+        cmd = blocked.retry;
+        session.retrying = true;
+    }
+}
+
+// Sleep for `duration`, or forever if `None`.
+async fn sleep_for(duration: Option<Duration>) {
+    match duration {
+        Some(duration) => tokio::time::sleep(duration).await,
+        None => std::future::pending().await,
+    }
+}
+
+// Drops the subscriptions of a connection, and stops its monitoring and
+// tracking, when it ends.
+struct Subscriber<'a> {
+    shared: &'a Shared,
+    id: u64,
+}
+
+impl Drop for Subscriber<'_> {
+    fn drop(&mut self) {
+        self.shared.pubsub.unsubscribe_all(self.id);
+        self.shared.monitors.remove(self.id);
+        self.shared.tracking.disable(self.id);
+    }
+}
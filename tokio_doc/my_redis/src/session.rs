@@ -0,0 +1,71 @@
+use crate::blocking::Blocked;
+use crate::pubsub;
+
+use bytes::Bytes;
+use mini_redis::Frame;
+use std::collections::HashSet;
+
+// Per-connection state, owned by the connection task and handed to every
+// command it runs.
+#[derive(Clone)]
+pub(crate) struct Session {
+    pub(crate) id: u64,
+    // the peer address, or "lua" for the commands of scripts.
+    pub(crate) addr: String,
+    // the ACL user, `None` until the connection authenticates.
+    pub(crate) user: Option<String>,
+    // set with HELLO ... SETNAME.
+    pub(crate) name: Option<String>,
+    // index of the selected database.
+    pub(crate) db: usize,
+    // set by commands like QUIT, the connection is closed after the reply.
+    pub(crate) closing: bool,
+    // pub/sub messages for this connection.
+    pub(crate) push: pubsub::Sender,
+    pub(crate) channels: HashSet<Bytes>,
+    pub(crate) patterns: HashSet<Bytes>,
+    // written before the reply of the current command, for commands that
+    // reply more than once like SUBSCRIBE.
+    pub(crate) replies: Vec<Frame>,
+    // set by a blocking command waiting for data.
+    pub(crate) blocked: Option<Blocked>,
+    // set by MONITOR, the connection gets the commands of the others then.
+    pub(crate) monitoring: bool,
+    // set by ASKING, lets the next command use a slot being imported.
+    pub(crate) asking: bool,
+    // set while a blocked command runs again, it was fed to the monitors
+    // already.
+    pub(crate) retrying: bool,
+}
+
+impl Session {
+    pub(crate) fn new(
+        id: u64,
+        addr: String,
+        user: Option<String>,
+        push: pubsub::Sender,
+    ) -> Session {
+        Session {
+            id,
+            addr,
+            user,
+            name: None,
+            db: 0,
+            closing: false,
+            push,
+            channels: HashSet::new(),
+            patterns: HashSet::new(),
+            replies: Vec::new(),
+            blocked: None,
+            monitoring: false,
+            asking: false,
+            retrying: false,
+        }
+    }
+
+    // Number of channels and patterns subscribed to. While it isn't zero,
+    // only pub/sub commands are accepted.
+    pub(crate) fn subscriptions(&self) -> usize {
+        self.channels.len() + self.patterns.len()
+    }
+}
@@ -0,0 +1,205 @@
+use bytes::{Bytes, BytesMut};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Like redis, long commands are cut down so the log stays small.
+const MAX_ARGS: usize = 32;
+const MAX_ARG_LEN: usize = 128;
+
+// The last commands that ran longer than `slowlog-log-slower-than`, newest
+// first.
+pub(crate) struct SlowLog {
+    state: Mutex<State>,
+    max_len: usize,
+}
+
+#[derive(Default)]
+struct State {
+    entries: VecDeque<Entry>,
+    next_id: u64,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Entry {
+    pub(crate) id: u64,
+    // unix time the command was logged at, in seconds.
+    pub(crate) timestamp: u64,
+    pub(crate) duration: Duration,
+    pub(crate) args: Vec<Bytes>,
+    pub(crate) addr: String,
+    pub(crate) name: String,
+}
+
+impl SlowLog {
+    pub(crate) fn new(max_len: usize) -> SlowLog {
+        SlowLog {
+            state: Mutex::new(State::default()),
+            max_len,
+        }
+    }
+
+    // Log a command, dropping the oldest entry when the log is full.
+    pub(crate) fn push(&self, args: &[Bytes], duration: Duration, addr: &str, name: &str) {
+        let mut logged: Vec<Bytes> = args.iter().take(MAX_ARGS).map(truncate).collect();
+        redact(&mut logged);
+        if args.len() > MAX_ARGS {
+            // the last slot says how many arguments were left out.
+            logged[MAX_ARGS - 1] = Bytes::from(format!(
+                "... ({} more arguments)",
+                args.len() - MAX_ARGS + 1
+            ));
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_secs());
+
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.entries.push_front(Entry {
+            id,
+            timestamp,
+            duration,
+            args: logged,
+            addr: addr.to_string(),
+            name: name.to_string(),
+        });
+        state.entries.truncate(self.max_len);
+    }
+
+    // The `count` newest entries.
+    pub(crate) fn get(&self, count: usize) -> Vec<Entry> {
+        let state = self.state.lock().unwrap();
+        state.entries.iter().take(count).cloned().collect()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+
+    pub(crate) fn reset(&self) {
+        self.state.lock().unwrap().entries.clear();
+    }
+}
+
+// Like redis, the arguments that may be passwords are replaced: those of
+// AUTH, the credentials of HELLO ... AUTH and the rules of ACL SETUSER.
+pub(crate) fn redact(args: &mut [Bytes]) {
+    let is = |i: usize, name: &str| {
+        args.get(i)
+            .is_some_and(|arg| arg.eq_ignore_ascii_case(name.as_bytes()))
+    };
+    let from = if is(0, "auth") {
+        1..args.len()
+    } else if is(0, "acl") && is(1, "setuser") {
+        3..args.len()
+    } else if is(0, "hello") {
+        match (2..args.len()).find(|&i| is(i, "auth")) {
+            Some(i) => i + 1..(i + 3).min(args.len()),
+            None => return,
+        }
+    } else {
+        return;
+    };
+    for arg in &mut args[from] {
+        *arg = Bytes::from_static(b"(redacted)");
+    }
+}
+
+fn truncate(arg: &Bytes) -> Bytes {
+    if arg.len() <= MAX_ARG_LEN {
+        return arg.clone();
+    }
+
+    let mut cut = BytesMut::from(&arg[..MAX_ARG_LEN]);
+    let more = format!("... ({} more bytes)", arg.len() - MAX_ARG_LEN);
+    cut.extend_from_slice(more.as_bytes());
+    cut.freeze()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<Bytes> {
+        args.iter()
+            .map(|arg| Bytes::from(arg.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn keeps_the_newest_entries() {
+        let log = SlowLog::new(2);
+        for cmd in ["get", "set", "del"] {
+            log.push(&args(&[cmd, "k"]), Duration::from_millis(20), "addr", "");
+        }
+
+        assert_eq!(log.len(), 2);
+        let entries = log.get(10);
+        assert_eq!(entries[0].id, 2);
+        assert_eq!(entries[0].args[0], "del");
+        assert_eq!(entries[1].args[0], "set");
+
+        log.reset();
+        assert_eq!(log.len(), 0);
+    }
+
+    #[test]
+    fn passwords_are_redacted() {
+        let log = SlowLog::new(10);
+        let commands: [&[&str]; 4] = [
+            &["auth", "user", "secret"],
+            &["hello", "2", "AUTH", "user", "secret", "SETNAME", "name"],
+            &["acl", "SETUSER", "user", "on", ">secret"],
+            &["set", "auth", "secret"],
+        ];
+        for cmd in commands {
+            log.push(&args(cmd), Duration::ZERO, "addr", "");
+        }
+
+        let entries = log.get(10);
+        let logged: Vec<_> = entries
+            .iter()
+            .rev()
+            .map(|entry| entry.args.clone())
+            .collect();
+        assert_eq!(logged[0], args(&["auth", "(redacted)", "(redacted)"]));
+        assert_eq!(
+            logged[1],
+            args(&[
+                "hello",
+                "2",
+                "AUTH",
+                "(redacted)",
+                "(redacted)",
+                "SETNAME",
+                "name"
+            ])
+        );
+        assert_eq!(
+            logged[2],
+            args(&["acl", "SETUSER", "user", "(redacted)", "(redacted)"])
+        );
+        assert_eq!(logged[3], args(&["set", "auth", "secret"]));
+    }
+
+    #[test]
+    fn long_commands_are_truncated() {
+        let log = SlowLog::new(10);
+        let mut cmd = vec!["mset".to_string(); 40];
+        cmd[1] = "x".repeat(200);
+        let cmd: Vec<_> = cmd.into_iter().map(Bytes::from).collect();
+        log.push(&cmd, Duration::ZERO, "addr", "");
+
+        let entry = &log.get(1)[0];
+        assert_eq!(entry.args.len(), MAX_ARGS);
+        assert_eq!(entry.args[MAX_ARGS - 1], "... (9 more arguments)");
+        assert!(entry.args[1].ends_with(b"... (72 more bytes)"));
+        assert_eq!(
+            entry.args[1].len(),
+            MAX_ARG_LEN + "... (72 more bytes)".len()
+        );
+    }
+}
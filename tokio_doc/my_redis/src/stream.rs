@@ -0,0 +1,649 @@
+use crate::dump::{Reader, Writer};
+
+use bytes::Bytes;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Rough cost of an entry on top of its fields, and of a pending entry.
+const ENTRY_OVERHEAD: usize = 48;
+const PENDING_OVERHEAD: usize = 64;
+
+// ID of a stream entry: a millisecond timestamp and a sequence number for
+// the entries added during the same millisecond.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct StreamId {
+    pub(crate) ms: u64,
+    pub(crate) seq: u64,
+}
+
+impl StreamId {
+    pub(crate) const MIN: StreamId = StreamId { ms: 0, seq: 0 };
+    pub(crate) const MAX: StreamId = StreamId {
+        ms: u64::MAX,
+        seq: u64::MAX,
+    };
+
+    // The smallest ID greater than this one.
+    pub(crate) fn next(self) -> Option<StreamId> {
+        match self.seq.checked_add(1) {
+            Some(seq) => Some(StreamId { ms: self.ms, seq }),
+            None => Some(StreamId {
+                ms: self.ms.checked_add(1)?,
+                seq: 0,
+            }),
+        }
+    }
+
+    // The greatest ID smaller than this one.
+    pub(crate) fn prev(self) -> Option<StreamId> {
+        match self.seq.checked_sub(1) {
+            Some(seq) => Some(StreamId { ms: self.ms, seq }),
+            None => Some(StreamId {
+                ms: self.ms.checked_sub(1)?,
+                seq: u64::MAX,
+            }),
+        }
+    }
+
+    // Parse "ms-seq", or just "ms" with `seq` as the sequence number, e.g.
+    // 0 for the start of a range and `u64::MAX` for its end.
+    pub(crate) fn parse(s: &str, seq: u64) -> Result<StreamId, String> {
+        let invalid = || "ERR Invalid stream ID specified as stream command argument".to_string();
+
+        let (ms, seq) = match s.split_once('-') {
+            Some((ms, seq)) => (ms, seq.parse().map_err(|_| invalid())?),
+            None => (s, seq),
+        };
+        let ms = ms.parse().map_err(|_| invalid())?;
+
+        Ok(StreamId { ms, seq })
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+// The ID given to XADD.
+pub(crate) enum NewId {
+    // `*`, generated from the clock.
+    Auto,
+    // `ms-*`, the sequence number is generated.
+    Ms(u64),
+    Explicit(StreamId),
+}
+
+impl NewId {
+    pub(crate) fn parse(s: &str) -> Result<NewId, String> {
+        if s == "*" {
+            return Ok(NewId::Auto);
+        }
+        if let Some(ms) = s.strip_suffix("-*") {
+            let ms = StreamId::parse(ms, 0)?.ms;
+            return Ok(NewId::Ms(ms));
+        }
+        Ok(NewId::Explicit(StreamId::parse(s, 0)?))
+    }
+}
+
+pub(crate) type Fields = Vec<(Bytes, Bytes)>;
+
+// An append-only log of entries, with consumer groups reading it.
+#[derive(Clone, Default)]
+pub(crate) struct Stream {
+    entries: BTreeMap<StreamId, Fields>,
+    // the last ID ever added, entries may have been trimmed since.
+    last_id: StreamId,
+    groups: BTreeMap<String, Group>,
+    // approximate bytes held, kept up to date for `Db::used_memory`.
+    memory: usize,
+}
+
+#[derive(Clone)]
+struct Group {
+    // entries after this one haven't been delivered to the group yet.
+    last_delivered: StreamId,
+    // entries delivered to a consumer and not acknowledged yet.
+    pending: BTreeMap<StreamId, Pending>,
+    consumers: BTreeMap<String, Consumer>,
+}
+
+#[derive(Clone)]
+pub(crate) struct Pending {
+    pub(crate) consumer: String,
+    pub(crate) delivered_at: Instant,
+    pub(crate) deliveries: u64,
+}
+
+#[derive(Clone)]
+struct Consumer {
+    seen: Instant,
+}
+
+// The PEL of a group at a glance, see XPENDING.
+pub(crate) struct PendingSummary {
+    pub(crate) count: usize,
+    pub(crate) first: Option<StreamId>,
+    pub(crate) last: Option<StreamId>,
+    // consumers with pending entries, and how many.
+    pub(crate) consumers: Vec<(String, usize)>,
+}
+
+impl Stream {
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn last_id(&self) -> StreamId {
+        self.last_id
+    }
+
+    pub(crate) fn memory(&self) -> usize {
+        self.memory
+    }
+
+    // Append an entry, returns its ID. IDs only grow.
+    pub(crate) fn add(&mut self, id: NewId, fields: Fields) -> Result<StreamId, String> {
+        let last = self.last_id;
+        let id = match id {
+            NewId::Auto => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as u64;
+                // the clock may go backwards, IDs don't.
+                if now > last.ms {
+                    Some(StreamId { ms: now, seq: 0 })
+                } else {
+                    last.next()
+                }
+            }
+            NewId::Ms(ms) if ms == last.ms => last.next(),
+            NewId::Ms(ms) if ms > last.ms => Some(StreamId { ms, seq: 0 }),
+            NewId::Ms(_) => None,
+            NewId::Explicit(StreamId::MIN) => {
+                return Err("ERR The ID specified in XADD must be greater than 0-0".to_string())
+            }
+            NewId::Explicit(id) => (id > last).then_some(id),
+        };
+        let id = id.ok_or_else(|| {
+            "ERR The ID specified in XADD is equal or smaller than the target stream top item"
+                .to_string()
+        })?;
+
+        self.memory += entry_size(&fields);
+        self.entries.insert(id, fields);
+        self.last_id = id;
+        Ok(id)
+    }
+
+    // Drop the oldest entries to keep at most `maxlen`, returns how many
+    // were dropped.
+    pub(crate) fn trim(&mut self, maxlen: usize) -> usize {
+        let mut trimmed = 0;
+        while self.entries.len() > maxlen {
+            let (_, fields) = self.entries.pop_first().unwrap();
+            self.memory -= entry_size(&fields);
+            trimmed += 1;
+        }
+        trimmed
+    }
+
+    // Entries between `start` and `end` included, oldest first unless
+    // `rev`.
+    pub(crate) fn range(
+        &self,
+        start: StreamId,
+        end: StreamId,
+        count: Option<usize>,
+        rev: bool,
+    ) -> Vec<(StreamId, Fields)> {
+        if start > end {
+            return Vec::new();
+        }
+
+        let range = self.entries.range(start..=end);
+        let count = count.unwrap_or(usize::MAX);
+        let clone = |(id, fields): (&StreamId, &Fields)| (*id, fields.clone());
+        if rev {
+            range.rev().take(count).map(clone).collect()
+        } else {
+            range.take(count).map(clone).collect()
+        }
+    }
+
+    // Entries added after `id`.
+    pub(crate) fn after(&self, id: StreamId, count: Option<usize>) -> Vec<(StreamId, Fields)> {
+        match id.next() {
+            Some(start) => self.range(start, StreamId::MAX, count, false),
+            None => Vec::new(),
+        }
+    }
+
+    pub(crate) fn create_group(&mut self, name: &str, id: StreamId) -> Result<(), String> {
+        if self.groups.contains_key(name) {
+            return Err("BUSYGROUP Consumer Group name already exists".to_string());
+        }
+
+        self.groups.insert(
+            name.to_string(),
+            Group {
+                last_delivered: id,
+                pending: BTreeMap::new(),
+                consumers: BTreeMap::new(),
+            },
+        );
+        Ok(())
+    }
+
+    pub(crate) fn destroy_group(&mut self, name: &str) -> bool {
+        match self.groups.remove(name) {
+            Some(group) => {
+                self.memory -= group.pending.len() * PENDING_OVERHEAD;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Returns whether the consumer was created.
+    pub(crate) fn create_consumer(&mut self, group: &str, consumer: &str) -> Result<bool, String> {
+        let group = self.group(group)?;
+        if group.consumers.contains_key(consumer) {
+            return Ok(false);
+        }
+
+        group.consumers.insert(
+            consumer.to_string(),
+            Consumer {
+                seen: Instant::now(),
+            },
+        );
+        Ok(true)
+    }
+
+    // Remove a consumer and its pending entries, returns how many entries
+    // it had pending.
+    pub(crate) fn delete_consumer(&mut self, group: &str, consumer: &str) -> Result<usize, String> {
+        let group = self.group(group)?;
+        group.consumers.remove(consumer);
+        let before = group.pending.len();
+        group
+            .pending
+            .retain(|_, pending| pending.consumer != consumer);
+
+        let deleted = before - group.pending.len();
+        self.memory -= deleted * PENDING_OVERHEAD;
+        Ok(deleted)
+    }
+
+    // Deliver entries to `consumer` of `group`. With `id` unset (`>`), the
+    // entries never delivered to the group, which become pending unless
+    // `noack`. Otherwise the entries already pending for the consumer after
+    // `id`, `None` for the ones trimmed since.
+    pub(crate) fn read_group(
+        &mut self,
+        group: &str,
+        consumer: &str,
+        id: Option<StreamId>,
+        count: Option<usize>,
+        noack: bool,
+    ) -> Result<Vec<(StreamId, Option<Fields>)>, String> {
+        let now = Instant::now();
+        let entries = &self.entries;
+        let group = self.groups.get_mut(group).ok_or_else(|| no_group(group))?;
+        group
+            .consumers
+            .entry(consumer.to_string())
+            .or_insert(Consumer { seen: now })
+            .seen = now;
+
+        let count = count.unwrap_or(usize::MAX);
+        let mut delivered = Vec::new();
+
+        match id {
+            None => {
+                let start = match group.last_delivered.next() {
+                    Some(start) => start,
+                    None => return Ok(delivered),
+                };
+                for (id, fields) in entries.range(start..).take(count) {
+                    group.last_delivered = *id;
+                    delivered.push((*id, Some(fields.clone())));
+                    if noack {
+                        continue;
+                    }
+
+                    let previous = group.pending.insert(
+                        *id,
+                        Pending {
+                            consumer: consumer.to_string(),
+                            delivered_at: now,
+                            deliveries: 1,
+                        },
+                    );
+                    if previous.is_none() {
+                        self.memory += PENDING_OVERHEAD;
+                    }
+                }
+            }
+            Some(id) => {
+                let start = match id.next() {
+                    Some(start) => start,
+                    None => return Ok(delivered),
+                };
+                let mine = group
+                    .pending
+                    .range_mut(start..)
+                    .filter(|(_, pending)| pending.consumer == consumer)
+                    .take(count);
+                for (id, pending) in mine {
+                    pending.delivered_at = now;
+                    pending.deliveries += 1;
+                    delivered.push((*id, entries.get(id).cloned()));
+                }
+            }
+        }
+
+        Ok(delivered)
+    }
+
+    // Remove entries from the PEL of `group`, returns how many were
+    // pending.
+    pub(crate) fn ack(&mut self, group: &str, ids: &[StreamId]) -> Result<usize, String> {
+        let group = self.group(group)?;
+        let acked = ids
+            .iter()
+            .filter(|id| group.pending.remove(id).is_some())
+            .count();
+
+        self.memory -= acked * PENDING_OVERHEAD;
+        Ok(acked)
+    }
+
+    pub(crate) fn pending_summary(&mut self, group: &str) -> Result<PendingSummary, String> {
+        let group = self.group(group)?;
+
+        let mut consumers = BTreeMap::<&str, usize>::new();
+        for pending in group.pending.values() {
+            *consumers.entry(&pending.consumer).or_default() += 1;
+        }
+
+        Ok(PendingSummary {
+            count: group.pending.len(),
+            first: group.pending.keys().next().copied(),
+            last: group.pending.keys().next_back().copied(),
+            consumers: consumers
+                .into_iter()
+                .map(|(name, count)| (name.to_string(), count))
+                .collect(),
+        })
+    }
+
+    // Pending entries between `start` and `end`, optionally of a single
+    // consumer, with how long ago they were delivered.
+    pub(crate) fn pending_range(
+        &mut self,
+        group: &str,
+        start: StreamId,
+        end: StreamId,
+        count: usize,
+        consumer: Option<&str>,
+    ) -> Result<Vec<(StreamId, String, Duration, u64)>, String> {
+        let group = self.group(group)?;
+        if start > end {
+            return Ok(Vec::new());
+        }
+
+        let now = Instant::now();
+        Ok(group
+            .pending
+            .range(start..=end)
+            .filter(|(_, pending)| consumer.is_none_or(|name| pending.consumer == name))
+            .take(count)
+            .map(|(id, pending)| {
+                (
+                    *id,
+                    pending.consumer.clone(),
+                    now.saturating_duration_since(pending.delivered_at),
+                    pending.deliveries,
+                )
+            })
+            .collect())
+    }
+
+    fn group(&mut self, name: &str) -> Result<&mut Group, String> {
+        self.groups.get_mut(name).ok_or_else(|| no_group(name))
+    }
+
+    // Write the entries, groups and their pending entries for DUMP. Times
+    // are written as milliseconds ago.
+    pub(crate) fn dump(&self, out: &mut Writer) {
+        let now = Instant::now();
+        let ago = |at: Instant| now.saturating_duration_since(at).as_millis() as u64;
+
+        out.u64(self.entries.len() as u64);
+        for (id, fields) in &self.entries {
+            write_id(out, *id);
+            out.u64(fields.len() as u64);
+            for (field, value) in fields {
+                out.bytes(field);
+                out.bytes(value);
+            }
+        }
+        write_id(out, self.last_id);
+
+        out.u64(self.groups.len() as u64);
+        for (name, group) in &self.groups {
+            out.bytes(name.as_bytes());
+            write_id(out, group.last_delivered);
+            out.u64(group.consumers.len() as u64);
+            for (name, consumer) in &group.consumers {
+                out.bytes(name.as_bytes());
+                out.u64(ago(consumer.seen));
+            }
+            out.u64(group.pending.len() as u64);
+            for (id, pending) in &group.pending {
+                write_id(out, *id);
+                out.bytes(pending.consumer.as_bytes());
+                out.u64(ago(pending.delivered_at));
+                out.u64(pending.deliveries);
+            }
+        }
+    }
+
+    // Read a stream written by `dump`, `None` if the data is malformed.
+    pub(crate) fn restore(src: &mut Reader<'_>) -> Option<Stream> {
+        let now = Instant::now();
+        let at = |ago: u64| now.checked_sub(Duration::from_millis(ago)).unwrap_or(now);
+        let mut stream = Stream::default();
+
+        // counts aren't trusted for allocations, reads fail once the data
+        // runs out.
+        for _ in 0..src.u64()? {
+            let id = read_id(src)?;
+            let mut fields = Vec::new();
+            for _ in 0..src.u64()? {
+                fields.push((src.bytes()?, src.bytes()?));
+            }
+            stream.memory += entry_size(&fields);
+            if stream.entries.insert(id, fields).is_some() {
+                return None;
+            }
+        }
+        stream.last_id = read_id(src)?;
+        if stream.entries.keys().next_back() > Some(&stream.last_id) {
+            return None;
+        }
+
+        for _ in 0..src.u64()? {
+            let name = src.string()?;
+            let mut group = Group {
+                last_delivered: read_id(src)?,
+                pending: BTreeMap::new(),
+                consumers: BTreeMap::new(),
+            };
+            for _ in 0..src.u64()? {
+                let name = src.string()?;
+                let seen = at(src.u64()?);
+                group.consumers.insert(name, Consumer { seen });
+            }
+            for _ in 0..src.u64()? {
+                let id = read_id(src)?;
+                let pending = Pending {
+                    consumer: src.string()?,
+                    delivered_at: at(src.u64()?),
+                    deliveries: src.u64()?,
+                };
+                if group.pending.insert(id, pending).is_none() {
+                    stream.memory += PENDING_OVERHEAD;
+                }
+            }
+            if stream.groups.insert(name, group).is_some() {
+                return None;
+            }
+        }
+
+        Some(stream)
+    }
+}
+
+fn write_id(out: &mut Writer, id: StreamId) {
+    out.u64(id.ms);
+    out.u64(id.seq);
+}
+
+fn read_id(src: &mut Reader<'_>) -> Option<StreamId> {
+    Some(StreamId {
+        ms: src.u64()?,
+        seq: src.u64()?,
+    })
+}
+
+fn no_group(name: &str) -> String {
+    format!("NOGROUP No such consumer group '{}' for key name", name)
+}
+
+fn entry_size(fields: &Fields) -> usize {
+    let bytes: usize = fields.iter().map(|(f, v)| f.len() + v.len()).sum();
+    bytes + ENTRY_OVERHEAD
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(value: &str) -> Fields {
+        vec![(Bytes::from("field"), Bytes::from(value.to_string()))]
+    }
+
+    fn id(ms: u64, seq: u64) -> StreamId {
+        StreamId { ms, seq }
+    }
+
+    #[test]
+    fn ids_only_grow() {
+        let mut stream = Stream::default();
+        assert!(stream
+            .add(NewId::Explicit(StreamId::MIN), fields("a"))
+            .is_err());
+
+        assert_eq!(
+            stream.add(NewId::Explicit(id(5, 1)), fields("a")).unwrap(),
+            id(5, 1)
+        );
+        assert_eq!(stream.add(NewId::Ms(5), fields("b")).unwrap(), id(5, 2));
+        assert!(stream.add(NewId::Explicit(id(5, 2)), fields("c")).is_err());
+        assert!(stream.add(NewId::Ms(4), fields("c")).is_err());
+
+        let auto = stream.add(NewId::Auto, fields("c")).unwrap();
+        assert!(auto > id(5, 2));
+        assert_eq!(stream.len(), 3);
+        assert_eq!(stream.last_id(), auto);
+
+        assert_eq!(StreamId::parse("12", u64::MAX).unwrap(), id(12, u64::MAX));
+        assert_eq!(StreamId::parse("12-3", 0).unwrap(), id(12, 3));
+        assert!(StreamId::parse("12-x", 0).is_err());
+    }
+
+    #[test]
+    fn range_and_trim() {
+        let mut stream = Stream::default();
+        for i in 1..=5 {
+            stream.add(NewId::Explicit(id(i, 0)), fields("x")).unwrap();
+        }
+        let memory = stream.memory();
+
+        let ids = |entries: Vec<(StreamId, Fields)>| {
+            entries.into_iter().map(|(id, _)| id.ms).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            ids(stream.range(id(2, 0), id(4, 0), None, false)),
+            [2, 3, 4]
+        );
+        assert_eq!(
+            ids(stream.range(StreamId::MIN, StreamId::MAX, Some(2), true)),
+            [5, 4]
+        );
+        assert_eq!(ids(stream.after(id(3, 0), None)), [4, 5]);
+
+        assert_eq!(stream.trim(2), 3);
+        assert_eq!(
+            ids(stream.range(StreamId::MIN, StreamId::MAX, None, false)),
+            [4, 5]
+        );
+        assert_eq!(stream.memory(), memory * 2 / 5);
+    }
+
+    #[test]
+    fn consumer_groups_track_pending_entries() {
+        let mut stream = Stream::default();
+        for i in 1..=3 {
+            stream.add(NewId::Explicit(id(i, 0)), fields("x")).unwrap();
+        }
+        stream.create_group("g", StreamId::MIN).unwrap();
+        assert!(stream.create_group("g", StreamId::MIN).is_err());
+        assert!(stream
+            .read_group("nope", "alice", None, None, false)
+            .is_err());
+
+        let read = stream
+            .read_group("g", "alice", None, Some(2), false)
+            .unwrap();
+        assert_eq!(read.len(), 2);
+        let read = stream.read_group("g", "bob", None, None, false).unwrap();
+        assert_eq!(read[0].0, id(3, 0));
+        // everything was delivered.
+        assert!(stream
+            .read_group("g", "bob", None, None, false)
+            .unwrap()
+            .is_empty());
+
+        let summary = stream.pending_summary("g").unwrap();
+        assert_eq!(summary.count, 3);
+        assert_eq!(summary.first, Some(id(1, 0)));
+        assert_eq!(
+            summary.consumers,
+            [("alice".to_string(), 2), ("bob".to_string(), 1)]
+        );
+
+        // alice's history, delivered a second time.
+        let history = stream
+            .read_group("g", "alice", Some(StreamId::MIN), None, false)
+            .unwrap();
+        assert_eq!(history.len(), 2);
+        let pending = stream
+            .pending_range("g", StreamId::MIN, StreamId::MAX, 10, Some("alice"))
+            .unwrap();
+        assert_eq!(pending[0].3, 2);
+
+        assert_eq!(stream.ack("g", &[id(1, 0), id(1, 0), id(9, 0)]).unwrap(), 1);
+        assert_eq!(stream.delete_consumer("g", "alice").unwrap(), 1);
+        assert_eq!(stream.pending_summary("g").unwrap().count, 1);
+        assert!(stream.destroy_group("g"));
+        assert!(!stream.destroy_group("g"));
+    }
+}
@@ -0,0 +1,226 @@
+use crate::pubsub::Sender;
+
+use bytes::Bytes;
+use mini_redis::Frame;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+// The channel invalidations are sent on. Only RESP2 is spoken, so they are
+// pub/sub messages rather than RESP3 pushes.
+const CHANNEL: &[u8] = b"__redis__:invalidate";
+
+// Clients with CLIENT TRACKING on, and the keys they read, for the
+// invalidation messages of the keys modified.
+pub(crate) struct Tracking {
+    state: Mutex<State>,
+    // checked without the lock, on every command.
+    clients: AtomicUsize,
+}
+
+#[derive(Default)]
+struct State {
+    trackers: HashMap<u64, Tracker>,
+    // the clients that read each key, in the default mode. A client is
+    // removed once told about the key, until it reads it again.
+    readers: HashMap<Bytes, HashSet<u64>>,
+}
+
+// How a client tracks keys, set with CLIENT TRACKING ON.
+#[derive(Clone)]
+pub(crate) struct Tracker {
+    // the client itself, or the one it redirects to.
+    pub(crate) target: Sender,
+    // BCAST: every key matching one of `prefixes`, or every key if there
+    // are none, whoever read it.
+    pub(crate) bcast: bool,
+    pub(crate) prefixes: Vec<Bytes>,
+    // NOLOOP: not told about keys it modified itself.
+    pub(crate) noloop: bool,
+}
+
+impl Tracking {
+    pub(crate) fn new() -> Tracking {
+        Tracking {
+            state: Mutex::new(State::default()),
+            clients: AtomicUsize::new(0),
+        }
+    }
+
+    // Whether any client tracks keys.
+    pub(crate) fn active(&self) -> bool {
+        self.clients.load(Ordering::Relaxed) > 0
+    }
+
+    // Whether client `id` tracks keys, and if so in BCAST mode.
+    pub(crate) fn mode(&self, id: u64) -> Option<bool> {
+        let state = self.state.lock().unwrap();
+        state.trackers.get(&id).map(|tracker| tracker.bcast)
+    }
+
+    pub(crate) fn enable(&self, id: u64, tracker: Tracker) {
+        let mut state = self.state.lock().unwrap();
+        state.trackers.insert(id, tracker);
+        self.clients.store(state.trackers.len(), Ordering::Relaxed);
+    }
+
+    // Stop tracking for client `id`, e.g. when it disconnects.
+    pub(crate) fn disable(&self, id: u64) {
+        let state = &mut *self.state.lock().unwrap();
+        if state.trackers.remove(&id).is_none() {
+            return;
+        }
+        self.clients.store(state.trackers.len(), Ordering::Relaxed);
+        state.readers.retain(|_, readers| {
+            readers.remove(&id);
+            !readers.is_empty()
+        });
+    }
+
+    // Remember that client `id` read `keys`, if it tracks them.
+    pub(crate) fn read<'a>(&self, id: u64, keys: impl IntoIterator<Item = &'a Bytes>) {
+        let state = &mut *self.state.lock().unwrap();
+        // clients in BCAST mode are told about keys whoever read them.
+        if state.trackers.get(&id).is_none_or(|tracker| tracker.bcast) {
+            return;
+        }
+        for key in keys {
+            state.readers.entry(key.clone()).or_default().insert(id);
+        }
+    }
+
+    // Tell the clients tracking `keys` that they were modified, by client
+    // `by` or by the server itself, e.g. for expired keys.
+    pub(crate) fn invalidate<'a>(
+        &self,
+        keys: impl IntoIterator<Item = &'a Bytes>,
+        by: Option<u64>,
+    ) {
+        let state = &mut *self.state.lock().unwrap();
+        // a message per client, with all its keys.
+        let mut invalidated: HashMap<u64, Vec<Frame>> = HashMap::new();
+
+        for key in keys {
+            let readers = state.readers.remove(key).unwrap_or_default();
+            for (&id, tracker) in &state.trackers {
+                let tracked = if tracker.bcast {
+                    tracker.prefixes.is_empty()
+                        || tracker
+                            .prefixes
+                            .iter()
+                            .any(|prefix| key.starts_with(prefix))
+                } else {
+                    readers.contains(&id)
+                };
+                if tracked && !(tracker.noloop && by == Some(id)) {
+                    invalidated
+                        .entry(id)
+                        .or_default()
+                        .push(Frame::Bulk(key.clone()));
+                }
+            }
+        }
+
+        for (id, keys) in invalidated {
+            // the target is gone when it's disconnecting.
+            let _ = state.trackers[&id].target.send(message(Frame::Array(keys)));
+        }
+    }
+
+    // Tell every tracking client that all keys were modified, e.g. by
+    // FLUSHALL.
+    pub(crate) fn invalidate_all(&self) {
+        let state = &mut *self.state.lock().unwrap();
+        state.readers.clear();
+        for tracker in state.trackers.values() {
+            let _ = tracker.target.send(message(Frame::Null));
+        }
+    }
+}
+
+// An invalidation message, `keys` is null when all keys are invalidated.
+fn message(keys: Frame) -> Frame {
+    Frame::Array(vec![
+        Frame::Bulk(Bytes::from_static(b"message")),
+        Frame::Bulk(Bytes::from_static(CHANNEL)),
+        keys,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    // The keys of the invalidation messages received.
+    fn received(messages: &mut mpsc::UnboundedReceiver<Frame>) -> Vec<Vec<String>> {
+        let mut received = Vec::new();
+        while let Ok(Frame::Array(mut parts)) = messages.try_recv() {
+            let keys = match parts.pop() {
+                Some(Frame::Array(keys)) => keys
+                    .into_iter()
+                    .map(|key| match key {
+                        Frame::Bulk(key) => String::from_utf8(key.to_vec()).unwrap(),
+                        key => panic!("expected bulk frame, got {:?}", key),
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            received.push(keys);
+        }
+        received
+    }
+
+    #[test]
+    fn readers_are_told_once() {
+        let tracking = Tracking::new();
+        let (target, mut messages) = mpsc::unbounded_channel();
+        tracking.enable(
+            1,
+            Tracker {
+                target,
+                bcast: false,
+                prefixes: Vec::new(),
+                noloop: false,
+            },
+        );
+        assert!(tracking.active());
+
+        let (a, b, c) = (Bytes::from("a"), Bytes::from("b"), Bytes::from("c"));
+        tracking.read(1, [&a, &b]);
+        tracking.invalidate([&a, &b, &c], Some(2));
+        tracking.invalidate([&a], Some(2));
+        assert_eq!(received(&mut messages), [vec!["a", "b"]]);
+
+        tracking.read(1, [&a]);
+        tracking.disable(1);
+        tracking.invalidate([&a], None);
+        assert!(received(&mut messages).is_empty());
+        assert!(!tracking.active());
+    }
+
+    #[test]
+    fn broadcast_by_prefix() {
+        let tracking = Tracking::new();
+        let (target, mut messages) = mpsc::unbounded_channel();
+        tracking.enable(
+            1,
+            Tracker {
+                target,
+                bcast: true,
+                prefixes: vec![Bytes::from("user:")],
+                noloop: true,
+            },
+        );
+
+        let keys = ["user:1", "order:1", "user:2"].map(Bytes::from);
+        tracking.invalidate(&keys, Some(2));
+        tracking.invalidate(&keys, Some(2));
+        // not about its own writes.
+        tracking.invalidate(&keys, Some(1));
+        assert_eq!(
+            received(&mut messages),
+            [vec!["user:1", "user:2"], vec!["user:1", "user:2"]]
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use crate::dump::{Reader, Writer};
+
+use bytes::Bytes;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+
+// Rough cost of a member on top of its bytes: its slot in both maps.
+const MEMBER_OVERHEAD: usize = 64;
+
+// A sorted set: members with a score, ordered by score then member. The
+// geo commands store positions in one.
+#[derive(Clone, Default)]
+pub(crate) struct SortedSet {
+    scores: HashMap<Bytes, f64>,
+    by_score: BTreeSet<(Score, Bytes)>,
+    // approximate bytes held, kept up to date for `Db::used_memory`.
+    memory: usize,
+}
+
+// A score ordered with `total_cmp`. Scores are never NaN.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Score(f64);
+
+impl Eq for Score {}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Score) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Score) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl SortedSet {
+    pub(crate) fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub(crate) fn memory(&self) -> usize {
+        self.memory
+    }
+
+    pub(crate) fn score(&self, member: &[u8]) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    // Set the score of `member`, returns its previous score.
+    pub(crate) fn insert(&mut self, member: Bytes, score: f64) -> Option<f64> {
+        let previous = self.scores.insert(member.clone(), score);
+        match previous {
+            Some(previous) => {
+                self.by_score.remove(&(Score(previous), member.clone()));
+            }
+            None => self.memory += member.len() + MEMBER_OVERHEAD,
+        }
+        self.by_score.insert((Score(score), member));
+        previous
+    }
+
+    // Members with a score from `min` included to `max` excluded, in order.
+    pub(crate) fn range(&self, min: f64, max: f64) -> impl Iterator<Item = (&Bytes, f64)> {
+        let start = (Score(min), Bytes::new());
+        self.by_score
+            .range(start..)
+            .take_while(move |(score, _)| score.0 < max)
+            .map(|(score, member)| (member, score.0))
+    }
+
+    // Write the members and scores for DUMP, in order.
+    pub(crate) fn dump(&self, out: &mut Writer) {
+        out.u64(self.len() as u64);
+        for (score, member) in &self.by_score {
+            out.bytes(member);
+            out.u64(score.0.to_bits());
+        }
+    }
+
+    // Read a set written by `dump`, `None` if the data is malformed.
+    pub(crate) fn restore(src: &mut Reader<'_>) -> Option<SortedSet> {
+        let mut set = SortedSet::default();
+        for _ in 0..src.u64()? {
+            let member = src.bytes()?;
+            let score = f64::from_bits(src.u64()?);
+            if score.is_nan() || set.insert(member, score).is_some() {
+                return None;
+            }
+        }
+        Some(set)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ordered_by_score_then_member() {
+        let mut set = SortedSet::default();
+        assert_eq!(set.insert(Bytes::from("b"), 2.0), None);
+        set.insert(Bytes::from("a"), 2.0);
+        set.insert(Bytes::from("c"), 1.0);
+        assert_eq!(set.insert(Bytes::from("c"), 3.0), Some(1.0));
+        assert_eq!(set.len(), 3);
+
+        let members: Vec<_> = set.range(2.0, 3.0).map(|(m, _)| m.clone()).collect();
+        assert_eq!(members, ["a", "b"]);
+        assert_eq!(set.range(0.0, f64::INFINITY).count(), 3);
+        assert_eq!(set.score(b"c"), Some(3.0));
+        assert_eq!(set.score(b"d"), None);
+        assert_eq!(set.memory(), 3 * (1 + MEMBER_OVERHEAD));
+    }
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use common::*;
+use mini_redis::Frame;
+use my_redis::Config;
+
+#[tokio::test]
+async fn requirepass_limits_unauthenticated_connections() {
+    let mut config = Config::default();
+    config.set("requirepass", "secret").unwrap();
+    let addr = start_server_with(config).await;
+    let mut conn = connect(addr).await;
+
+    let msg = error(send(&mut conn, &["GET", "foo"]).await);
+    assert!(msg.starts_with("NOAUTH "), "{}", msg);
+    let msg = error(send(&mut conn, &["AUTH", "wrong"]).await);
+    assert!(msg.starts_with("WRONGPASS "), "{}", msg);
+
+    assert!(matches!(
+        send(&mut conn, &["AUTH", "secret"]).await,
+        Frame::Simple(_)
+    ));
+    assert!(matches!(
+        send(&mut conn, &["GET", "foo"]).await,
+        Frame::Null
+    ));
+}
+
+#[tokio::test]
+async fn hello_can_authenticate() {
+    let mut config = Config::default();
+    config.set("requirepass", "secret").unwrap();
+    let addr = start_server_with(config).await;
+    let mut conn = connect(addr).await;
+
+    let msg = error(send(&mut conn, &["HELLO", "2"]).await);
+    assert!(msg.starts_with("NOAUTH "), "{}", msg);
+    // nor is a name given along.
+    let msg = error(send(&mut conn, &["HELLO", "2", "SETNAME", "early"]).await);
+    assert!(msg.starts_with("NOAUTH "), "{}", msg);
+
+    let resp = send(&mut conn, &["HELLO", "2", "AUTH", "default", "secret"]).await;
+    assert!(matches!(resp, Frame::Array(_)), "{:?}", resp);
+    assert_eq!(
+        bulk_string(send(&mut conn, &["ACL", "WHOAMI"]).await),
+        "default"
+    );
+    assert!(matches!(
+        send(&mut conn, &["CLIENT", "GETNAME"]).await,
+        Frame::Null
+    ));
+    send(&mut conn, &["HELLO", "2", "SETNAME", "late"]).await;
+    assert_eq!(
+        bulk_string(send(&mut conn, &["CLIENT", "GETNAME"]).await),
+        "late"
+    );
+}
+
+#[tokio::test]
+async fn quit_closes_the_connection() {
+    let addr = start_server().await;
+    let mut conn = connect(addr).await;
+
+    assert!(matches!(send(&mut conn, &["QUIT"]).await, Frame::Simple(_)));
+    assert!(conn.read_frame().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn users_are_limited_to_their_commands_and_keys() {
+    let mut config = Config::default();
+    config.set("user", "reader on >pw ~cache:* +@read").unwrap();
+    let addr = start_server_with(config).await;
+
+    let mut admin = connect(addr).await;
+    send(&mut admin, &["SET", "cache:1", "hit"]).await;
+    send(&mut admin, &["SET", "session:1", "secret"]).await;
+
+    let mut conn = connect(addr).await;
+    assert!(matches!(
+        send(&mut conn, &["AUTH", "reader", "pw"]).await,
+        Frame::Simple(_)
+    ));
+    assert_eq!(
+        bulk_string(send(&mut conn, &["GET", "cache:1"]).await),
+        "hit"
+    );
+
+    let msg = error(send(&mut conn, &["GET", "session:1"]).await);
+    assert_eq!(msg, "NOPERM No permissions to access a key");
+    let msg = error(send(&mut conn, &["SET", "cache:1", "miss"]).await);
+    assert!(msg.starts_with("NOPERM "), "{}", msg);
+
+    // permissions can be changed at runtime.
+    send(&mut admin, &["ACL", "SETUSER", "reader", "+set"]).await;
+    assert!(matches!(
+        send(&mut conn, &["SET", "cache:1", "miss"]).await,
+        Frame::Simple(_)
+    ));
+}
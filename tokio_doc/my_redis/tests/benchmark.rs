@@ -0,0 +1,40 @@
+mod common;
+
+use common::*;
+use my_redis::benchmark::Benchmark;
+
+#[tokio::test]
+async fn benchmark_runs_every_request() {
+    let addr = start_server().await;
+    let args = [
+        "--addr",
+        &addr.to_string(),
+        "--clients",
+        "4",
+        "--requests",
+        "1001",
+        "--pipeline",
+        "8",
+        "--keyspace",
+        "50",
+        "--value-size",
+        "16",
+        "--mix",
+        "get:2,set:1,ping",
+        "--seed",
+        "7",
+    ];
+    let benchmark = Benchmark::from_args(args.iter().map(|s| s.to_string())).unwrap();
+
+    let report = benchmark.run().await.unwrap();
+    assert_eq!(report.requests, 1001);
+    assert_eq!(report.errors, 0);
+    assert!(report.percentile(0.5) <= report.percentile(0.99));
+    assert!(report.percentile(0.99) <= report.percentile(0.999));
+    assert!(report.to_string().contains("p999"));
+
+    // the SETs went through.
+    let mut conn = connect(addr).await;
+    let info = bulk_string(send(&mut conn, &["INFO", "keyspace"]).await);
+    assert!(info.contains("db0:keys="), "{}", info);
+}
@@ -0,0 +1,157 @@
+mod common;
+
+use common::*;
+use mini_redis::Frame;
+
+#[tokio::test]
+async fn setbit_getbit_and_bitcount() {
+    let addr = start_server().await;
+    let mut conn = connect(addr).await;
+
+    for offset in ["1", "2", "4", "9"] {
+        assert!(matches!(
+            send(&mut conn, &["SETBIT", "bits", offset, "1"]).await,
+            Frame::Integer(0)
+        ));
+    }
+    assert!(matches!(
+        send(&mut conn, &["SETBIT", "bits", "9", "0"]).await,
+        Frame::Integer(1)
+    ));
+    // 0110 1000 0000 0000, the bits of "h\0".
+    assert_eq!(bulk_string(send(&mut conn, &["GET", "bits"]).await), "h\0");
+    assert!(matches!(
+        send(&mut conn, &["GETBIT", "bits", "2"]).await,
+        Frame::Integer(1)
+    ));
+    assert!(matches!(
+        send(&mut conn, &["GETBIT", "bits", "100"]).await,
+        Frame::Integer(0)
+    ));
+
+    send(&mut conn, &["SET", "s", "foobar"]).await;
+    for (args, count) in [
+        (&["BITCOUNT", "s"][..], 26),
+        (&["BITCOUNT", "s", "0", "0"], 4),
+        (&["BITCOUNT", "s", "1", "1"], 6),
+        (&["BITCOUNT", "s", "-1", "-1"], 4),
+        (&["BITCOUNT", "s", "5", "30", "BIT"], 17),
+        (&["BITCOUNT", "s", "3", "1"], 0),
+        (&["BITCOUNT", "missing"], 0),
+    ] {
+        let reply = send(&mut conn, args).await;
+        assert!(
+            matches!(reply, Frame::Integer(n) if n == count),
+            "{:?}",
+            args
+        );
+    }
+
+    let msg = error(send(&mut conn, &["SETBIT", "bits", "4294967296", "1"]).await);
+    assert_eq!(msg, "ERR bit offset is not an integer or out of range");
+    let msg = error(send(&mut conn, &["SETBIT", "bits", "1", "2"]).await);
+    assert_eq!(msg, "ERR bit is not an integer or out of range");
+    send(&mut conn, &["XADD", "stream", "*", "f", "v"]).await;
+    let msg = error(send(&mut conn, &["SETBIT", "stream", "1", "1"]).await);
+    assert!(msg.starts_with("WRONGTYPE"), "{}", msg);
+}
+
+#[tokio::test]
+async fn bitop_combines_strings() {
+    let addr = start_server().await;
+    let mut conn = connect(addr).await;
+
+    send(&mut conn, &["SET", "a", "abc"]).await;
+    send(&mut conn, &["SET", "b", "\x01"]).await;
+    send(&mut conn, &["SET", "dest", "old", "EX", "100"]).await;
+
+    for (op, expected) in [("AND", "\x01\0\0"), ("OR", "abc"), ("XOR", "`bc")] {
+        let reply = send(&mut conn, &["BITOP", op, "dest", "a", "b"]).await;
+        assert!(matches!(reply, Frame::Integer(3)), "{}", op);
+        assert_eq!(
+            bulk_string(send(&mut conn, &["GET", "dest"]).await),
+            expected
+        );
+    }
+    // the expire went with the old value.
+    let info = bulk_string(send(&mut conn, &["INFO", "keyspace"]).await);
+    assert!(info.contains("expires=0"), "{}", info);
+
+    send(&mut conn, &["BITOP", "NOT", "dest", "b"]).await;
+    let reply = send(&mut conn, &["GET", "dest"]).await;
+    assert!(
+        matches!(&reply, Frame::Bulk(b) if b[..] == [0xfe]),
+        "{:?}",
+        reply
+    );
+    let msg = error(send(&mut conn, &["BITOP", "NOT", "dest", "a", "b"]).await);
+    assert_eq!(
+        msg,
+        "ERR BITOP NOT must be called with a single source key."
+    );
+
+    // nothing to combine, the destination is deleted.
+    assert!(matches!(
+        send(&mut conn, &["BITOP", "OR", "dest", "missing"]).await,
+        Frame::Integer(0)
+    ));
+    assert!(matches!(
+        send(&mut conn, &["GET", "dest"]).await,
+        Frame::Null
+    ));
+}
+
+#[tokio::test]
+async fn hyperloglog_counts_and_merges() {
+    let addr = start_server().await;
+    let mut conn = connect(addr).await;
+
+    assert!(matches!(
+        send(&mut conn, &["PFADD", "empty"]).await,
+        Frame::Integer(1)
+    ));
+    assert!(matches!(
+        send(&mut conn, &["PFCOUNT", "empty"]).await,
+        Frame::Integer(0)
+    ));
+
+    let users: Vec<String> = (0..1000).map(|i| format!("user:{}", i)).collect();
+    let mut args = vec!["PFADD", "monday"];
+    args.extend(users[..600].iter().map(String::as_str));
+    assert!(matches!(send(&mut conn, &args).await, Frame::Integer(1)));
+    // the same elements again change nothing.
+    assert!(matches!(send(&mut conn, &args).await, Frame::Integer(0)));
+
+    let mut args = vec!["PFADD", "tuesday"];
+    args.extend(users[400..].iter().map(String::as_str));
+    send(&mut conn, &args).await;
+
+    let count = |frame: Frame| match frame {
+        Frame::Integer(n) => n,
+        frame => panic!("expected integer frame, got {:?}", frame),
+    };
+    let monday = count(send(&mut conn, &["PFCOUNT", "monday"]).await);
+    assert!((594..=606).contains(&monday), "{}", monday);
+    let union = count(send(&mut conn, &["PFCOUNT", "monday", "tuesday"]).await);
+    assert!((990..=1010).contains(&union), "{}", union);
+
+    let reply = send(&mut conn, &["PFMERGE", "week", "monday", "tuesday"]).await;
+    assert!(matches!(reply, Frame::Simple(s) if s == "OK"));
+    assert_eq!(count(send(&mut conn, &["PFCOUNT", "week"]).await), union);
+    // a counter is a plain string value.
+    assert!(matches!(send(&mut conn, &["TYPE", "week"]).await, Frame::Simple(s) if s == "string"));
+
+    send(&mut conn, &["SET", "plain", "hello"]).await;
+    for args in [
+        &["PFADD", "plain", "x"][..],
+        &["PFCOUNT", "monday", "plain"],
+        &["PFMERGE", "week", "plain"],
+    ] {
+        let msg = error(send(&mut conn, args).await);
+        assert_eq!(
+            msg, "WRONGTYPE Key is not a valid HyperLogLog string value.",
+            "{:?}",
+            args
+        );
+    }
+}
@@ -0,0 +1,240 @@
+mod common;
+
+use common::*;
+use mini_redis::Frame;
+use my_redis::{Config, Connection};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+
+#[tokio::test]
+async fn ping_and_echo() {
+    let addr = start_server().await;
+    let mut conn = connect(addr).await;
+
+    assert!(matches!(send(&mut conn, &["PING"]).await, Frame::Simple(s) if s == "PONG"));
+    assert_eq!(bulk_string(send(&mut conn, &["PING", "hi"]).await), "hi");
+    assert_eq!(
+        bulk_string(send(&mut conn, &["ECHO", "hello"]).await),
+        "hello"
+    );
+
+    // subscribed clients get a pub/sub like reply.
+    send(&mut conn, &["SUBSCRIBE", "news"]).await;
+    match send(&mut conn, &["PING"]).await {
+        Frame::Array(parts) => {
+            assert_eq!(parts[0], "pong");
+            assert_eq!(parts[1], "");
+        }
+        frame => panic!("expected array frame, got {:?}", frame),
+    }
+}
+
+#[tokio::test]
+async fn client_list_shows_connections() {
+    let addr = start_server().await;
+    let socket = TcpStream::connect(addr).await.unwrap();
+    let local = socket.local_addr().unwrap();
+    let mut conn = Connection::new(socket);
+    let mut other = connect(addr).await;
+
+    assert!(matches!(
+        send(&mut conn, &["CLIENT", "GETNAME"]).await,
+        Frame::Null
+    ));
+    send(&mut conn, &["CLIENT", "SETNAME", "worker"]).await;
+    assert_eq!(
+        bulk_string(send(&mut conn, &["CLIENT", "GETNAME"]).await),
+        "worker"
+    );
+    let msg = error(send(&mut conn, &["CLIENT", "SETNAME", "a b"]).await);
+    assert!(msg.contains("cannot contain spaces"), "{}", msg);
+    send(&mut other, &["SELECT", "3"]).await;
+
+    let list = bulk_string(send(&mut other, &["CLIENT", "LIST"]).await);
+    let lines: Vec<_> = list.lines().collect();
+    assert_eq!(lines.len(), 2, "{}", list);
+    let line = lines
+        .iter()
+        .find(|line| line.contains(&format!(" addr={} ", local)))
+        .expect("client listed by address");
+    assert!(line.contains(" name=worker "), "{}", line);
+    assert!(line.contains(" cmd=client "), "{}", line);
+    assert!(line.contains(" user=default"), "{}", line);
+    let line = lines.iter().find(|line| !line.contains("worker")).unwrap();
+    assert!(line.contains(" db=3 "), "{}", line);
+
+    let id = match send(&mut conn, &["CLIENT", "ID"]).await {
+        Frame::Integer(id) => id,
+        frame => panic!("expected integer frame, got {:?}", frame),
+    };
+    let list = bulk_string(send(&mut other, &["CLIENT", "LIST", "ID", &id.to_string()]).await);
+    assert_eq!(list.lines().count(), 1);
+    assert!(list.starts_with(&format!("id={} ", id)));
+}
+
+#[tokio::test]
+async fn client_kill_closes_other_connections() {
+    let addr = start_server().await;
+    let mut killer = connect(addr).await;
+    let mut victim = connect(addr).await;
+
+    let id = match send(&mut victim, &["CLIENT", "ID"]).await {
+        Frame::Integer(id) => id,
+        frame => panic!("expected integer frame, got {:?}", frame),
+    };
+    let resp = send(&mut killer, &["CLIENT", "KILL", "ID", &id.to_string()]).await;
+    assert!(matches!(resp, Frame::Integer(1)), "{:?}", resp);
+    assert!(victim.read_frame().await.unwrap().is_none());
+
+    // killing ourselves takes SKIPME no.
+    let resp = send(&mut killer, &["CLIENT", "KILL", "USER", "default"]).await;
+    assert!(matches!(resp, Frame::Integer(0)), "{:?}", resp);
+    let msg = error(send(&mut killer, &["CLIENT", "KILL", "1.2.3.4:5"]).await);
+    assert_eq!(msg, "ERR No such client");
+    let resp = send(
+        &mut killer,
+        &["CLIENT", "KILL", "USER", "default", "SKIPME", "no"],
+    )
+    .await;
+    assert!(matches!(resp, Frame::Integer(1)), "{:?}", resp);
+    assert!(killer.read_frame().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn client_kill_interrupts_blocked_clients() {
+    let addr = start_server().await;
+    let mut killer = connect(addr).await;
+    let socket = TcpStream::connect(addr).await.unwrap();
+    let local = socket.local_addr().unwrap();
+    let mut victim = Connection::new(socket);
+
+    let blocked = tokio::spawn(async move {
+        let frame = Frame::Array(
+            ["XREAD", "BLOCK", "0", "STREAMS", "s", "$"]
+                .iter()
+                .map(|arg| Frame::Bulk(arg.as_bytes().to_vec().into()))
+                .collect(),
+        );
+        victim.write_frame(&frame).await.unwrap();
+        victim.read_frame().await.unwrap()
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let resp = send(&mut killer, &["CLIENT", "KILL", &local.to_string()]).await;
+    assert!(matches!(resp, Frame::Simple(s) if s == "OK"));
+    assert!(blocked.await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn client_pause_holds_writes() {
+    let addr = start_server().await;
+    let mut admin = connect(addr).await;
+    let mut conn = connect(addr).await;
+
+    send(&mut admin, &["CLIENT", "PAUSE", "200", "WRITE"]).await;
+
+    // reads go through.
+    let start = Instant::now();
+    send(&mut conn, &["GET", "k"]).await;
+    assert!(start.elapsed() < Duration::from_millis(200));
+
+    send(&mut conn, &["SET", "k", "v"]).await;
+    assert!(start.elapsed() >= Duration::from_millis(200));
+
+    // CLIENT UNPAUSE ends a pause early.
+    send(&mut admin, &["CLIENT", "PAUSE", "10000"]).await;
+    let write = tokio::spawn(async move { send(&mut conn, &["GET", "k"]).await });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!write.is_finished());
+    send(&mut admin, &["CLIENT", "UNPAUSE"]).await;
+    assert_eq!(bulk_string(write.await.unwrap()), "v");
+}
+
+#[tokio::test]
+async fn monitor_streams_commands() {
+    let addr = start_server().await;
+    let mut monitor = connect(addr).await;
+    let socket = TcpStream::connect(addr).await.unwrap();
+    let local = socket.local_addr().unwrap();
+    let mut conn = Connection::new(socket);
+
+    assert!(matches!(send(&mut monitor, &["MONITOR"]).await, Frame::Simple(s) if s == "OK"));
+
+    send(&mut conn, &["SELECT", "2"]).await;
+    send(&mut conn, &["SET", "key", "two words"]).await;
+    send(&mut conn, &["AUTH", "secret"]).await;
+    send(
+        &mut conn,
+        &["EVAL", "redis_call(\"GET\", KEYS[0])", "1", "key"],
+    )
+    .await;
+
+    let mut lines = Vec::new();
+    for _ in 0..4 {
+        match monitor.read_frame().await.unwrap().unwrap() {
+            Frame::Simple(line) => lines.push(line),
+            frame => panic!("expected simple frame, got {:?}", frame),
+        }
+    }
+    // "<unix time>.<micros> [<db> <addr>] <args>"
+    let line = lines[1].split_once(' ').unwrap().1;
+    assert_eq!(line, format!("[2 {}] \"set\" \"key\" \"two words\"", local));
+    assert!(lines[0].ends_with("\"select\" \"2\""), "{}", lines[0]);
+    // AUTH isn't shown, the script and the commands it calls are.
+    assert!(lines[2].ends_with("\"eval\" \"redis_call(\\\"GET\\\", KEYS[0])\" \"1\" \"key\""));
+    assert!(
+        lines[3].ends_with("[2 lua] \"get\" \"key\""),
+        "{}",
+        lines[3]
+    );
+
+    let list = bulk_string(send(&mut conn, &["CLIENT", "LIST"]).await);
+    assert!(list.contains(" flags=O "), "{}", list);
+
+    let line = monitor.read_frame().await.unwrap().unwrap();
+    assert!(matches!(&line, Frame::Simple(s) if s.ends_with("\"client\" \"LIST\"")));
+
+    // the monitor only gets to quit.
+    let msg = error(send(&mut monitor, &["GET", "key"]).await);
+    assert!(msg.contains("only QUIT is allowed"), "{}", msg);
+    assert!(matches!(send(&mut monitor, &["QUIT"]).await, Frame::Simple(s) if s == "OK"));
+}
+
+#[tokio::test]
+async fn monitor_hides_passwords_and_rejected_commands() {
+    let mut config = Config::default();
+    config.set("requirepass", "secret").unwrap();
+    let addr = start_server_with(config).await;
+    let mut monitor = connect(addr).await;
+    let mut conn = connect(addr).await;
+    send(&mut monitor, &["AUTH", "secret"]).await;
+    send(&mut monitor, &["MONITOR"]).await;
+
+    // rejected before running, not shown.
+    let msg = error(send(&mut conn, &["GET", "k"]).await);
+    assert!(msg.starts_with("NOAUTH "), "{}", msg);
+    send(&mut conn, &["AUTH", "secret"]).await;
+    send(
+        &mut conn,
+        &["ACL", "SETUSER", "alice", "on", ">pw", "~*", "+get"],
+    )
+    .await;
+    send(&mut conn, &["AUTH", "alice", "pw"]).await;
+    let msg = error(send(&mut conn, &["SET", "k", "v"]).await);
+    assert!(msg.starts_with("NOPERM "), "{}", msg);
+    send(&mut conn, &["GET", "k"]).await;
+
+    let mut lines = Vec::new();
+    for _ in 0..2 {
+        match monitor.read_frame().await.unwrap().unwrap() {
+            Frame::Simple(line) => lines.push(line.split_once("] ").unwrap().1.to_string()),
+            frame => panic!("expected simple frame, got {:?}", frame),
+        }
+    }
+    let redacted = "\"(redacted)\"";
+    assert_eq!(
+        lines[0],
+        format!("\"acl\" \"SETUSER\" \"alice\" {0} {0} {0} {0}", redacted)
+    );
+    assert_eq!(lines[1], "\"get\" \"k\"");
+}
@@ -0,0 +1,200 @@
+mod common;
+
+use bytes::Bytes;
+use common::*;
+use mini_redis::Frame;
+use my_redis::{server, Client, Config};
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+
+// slot 12182 on node c, 5061 on node a.
+const FOO: &str = "foo";
+const BAR: &str = "bar";
+
+// Three nodes in this process, splitting the slots evenly.
+async fn start_cluster() -> Vec<SocketAddr> {
+    let mut listeners = Vec::new();
+    for _ in 0..3 {
+        listeners.push(TcpListener::bind("127.0.0.1:0").await.unwrap());
+    }
+    let addrs: Vec<_> = listeners.iter().map(|l| l.local_addr().unwrap()).collect();
+    let ranges = ["0-5460", "5461-10922", "10923-16383"];
+
+    for (i, listener) in listeners.into_iter().enumerate() {
+        let mut config = Config::default();
+        config.set("cluster-enabled", "yes").unwrap();
+        config
+            .set("cluster-node-id", &format!("node{}", i))
+            .unwrap();
+        for (j, addr) in addrs.iter().enumerate() {
+            let node = format!("node{} {} {}", j, addr, ranges[j]);
+            config.set("cluster-node", &node).unwrap();
+        }
+        tokio::spawn(async move { server::run(listener, config).await });
+    }
+
+    addrs
+}
+
+#[tokio::test]
+async fn keys_are_served_by_their_slot_owner() {
+    let addrs = start_cluster().await;
+    let mut a = connect(addrs[0]).await;
+    let mut c = connect(addrs[2]).await;
+
+    assert!(matches!(
+        send(&mut a, &["SET", BAR, "1"]).await,
+        Frame::Simple(_)
+    ));
+    assert_eq!(
+        error(send(&mut a, &["SET", FOO, "1"]).await),
+        format!("MOVED 12182 {}", addrs[2])
+    );
+    assert!(matches!(
+        send(&mut c, &["SET", FOO, "1"]).await,
+        Frame::Simple(_)
+    ));
+
+    // multi-key commands need the keys in one slot, which hash tags allow.
+    let msg = error(send(&mut a, &["DEL", "bar", "{bar}2", "zap"]).await);
+    assert!(msg.starts_with("CROSSSLOT "), "{}", msg);
+    send(&mut a, &["SET", "{bar}2", "2"]).await;
+    assert!(matches!(
+        send(&mut a, &["DEL", "bar", "{bar}2"]).await,
+        Frame::Integer(2)
+    ));
+
+    // keyless commands run anywhere, SELECT doesn't.
+    assert!(matches!(send(&mut a, &["PING"]).await, Frame::Simple(_)));
+    let msg = error(send(&mut a, &["SELECT", "1"]).await);
+    assert!(msg.contains("cluster mode"), "{}", msg);
+}
+
+#[tokio::test]
+async fn cluster_commands_describe_the_topology() {
+    let addrs = start_cluster().await;
+    let mut conn = connect(addrs[1]).await;
+
+    assert!(matches!(
+        send(&mut conn, &["CLUSTER", "KEYSLOT", "{user1000}.following"]).await,
+        Frame::Integer(3443)
+    ));
+    assert_eq!(
+        bulk_string(send(&mut conn, &["CLUSTER", "MYID"]).await),
+        "node1"
+    );
+    let info = bulk_string(send(&mut conn, &["CLUSTER", "INFO"]).await);
+    assert!(info.contains("cluster_state:ok\r\n"), "{}", info);
+
+    let slots = match send(&mut conn, &["CLUSTER", "SLOTS"]).await {
+        Frame::Array(slots) => slots,
+        frame => panic!("expected array frame, got {:?}", frame),
+    };
+    assert_eq!(slots.len(), 3);
+    match &slots[1] {
+        Frame::Array(range) => {
+            assert!(matches!(range[0], Frame::Integer(5461)));
+            assert!(matches!(range[1], Frame::Integer(10922)));
+            match &range[2] {
+                Frame::Array(node) => {
+                    assert_eq!(node[0], "127.0.0.1");
+                    assert!(
+                        matches!(node[1], Frame::Integer(port) if port == addrs[1].port() as u64)
+                    );
+                    assert_eq!(node[2], "node1");
+                }
+                frame => panic!("expected array frame, got {:?}", frame),
+            }
+        }
+        frame => panic!("expected array frame, got {:?}", frame),
+    }
+
+    let nodes = bulk_string(send(&mut conn, &["CLUSTER", "NODES"]).await);
+    assert_eq!(nodes.lines().count(), 3);
+    assert!(nodes.contains(&format!("node1 {}@", addrs[1])));
+    assert!(nodes.contains("myself,master - 0 0 0 connected 5461-10922\n"));
+
+    let mut standalone = connect(start_server().await).await;
+    let msg = error(send(&mut standalone, &["CLUSTER", "SLOTS"]).await);
+    assert!(msg.contains("cluster support disabled"), "{}", msg);
+}
+
+#[tokio::test]
+async fn client_follows_moved_redirects() {
+    let addrs = start_cluster().await;
+    let mut client = Client::connect(addrs[0]).await.unwrap();
+
+    for i in 0..20 {
+        let key = format!("key:{}", i);
+        client.set(&key, Bytes::from(i.to_string())).await.unwrap();
+    }
+    for i in 0..20 {
+        let key = format!("key:{}", i);
+        assert_eq!(
+            client.get(&key).await.unwrap(),
+            Some(Bytes::from(i.to_string()))
+        );
+    }
+
+    // the keys really are spread over the nodes.
+    for addr in addrs {
+        let mut conn = connect(addr).await;
+        let info = bulk_string(send(&mut conn, &["INFO", "keyspace"]).await);
+        assert!(info.contains("db0:keys="), "{}", info);
+    }
+}
+
+#[tokio::test]
+async fn ask_redirects_during_slot_migration() {
+    let addrs = start_cluster().await;
+    let mut a = connect(addrs[0]).await;
+    let mut b = connect(addrs[1]).await;
+    send(&mut a, &["SET", "{bar}old", "1"]).await;
+
+    // move bar's slot from node0 to node1.
+    send(
+        &mut b,
+        &["CLUSTER", "SETSLOT", "5061", "IMPORTING", "node0"],
+    )
+    .await;
+    send(
+        &mut a,
+        &["CLUSTER", "SETSLOT", "5061", "MIGRATING", "node1"],
+    )
+    .await;
+
+    // keys still on node0 are served there, the others are asked for on
+    // node1.
+    assert_eq!(bulk_string(send(&mut a, &["GET", "{bar}old"]).await), "1");
+    assert_eq!(
+        error(send(&mut a, &["SET", "{bar}new", "2"]).await),
+        format!("ASK 5061 {}", addrs[1])
+    );
+    // node1 only takes them after ASKING, for one command.
+    let msg = error(send(&mut b, &["SET", "{bar}new", "2"]).await);
+    assert!(msg.starts_with("MOVED 5061 "), "{}", msg);
+
+    let mut client = Client::connect(addrs[0]).await.unwrap();
+    client.set("{bar}new", Bytes::from("2")).await.unwrap();
+    send(&mut b, &["ASKING"]).await;
+    assert_eq!(bulk_string(send(&mut b, &["GET", "{bar}new"]).await), "2");
+    let msg = error(send(&mut b, &["GET", "{bar}new"]).await);
+    assert!(msg.starts_with("MOVED 5061 "), "{}", msg);
+    assert!(matches!(
+        send(&mut b, &["CLUSTER", "COUNTKEYSINSLOT", "5061"]).await,
+        Frame::Integer(1)
+    ));
+
+    // once the migration is over, the slot is node1's.
+    for conn in [&mut a, &mut b] {
+        send(conn, &["CLUSTER", "SETSLOT", "5061", "NODE", "node1"]).await;
+    }
+    assert_eq!(
+        error(send(&mut a, &["GET", "{bar}new"]).await),
+        format!("MOVED 5061 {}", addrs[1])
+    );
+    assert_eq!(
+        client.get("{bar}new").await.unwrap(),
+        Some(Bytes::from("2"))
+    );
+}
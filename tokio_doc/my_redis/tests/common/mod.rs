@@ -0,0 +1,65 @@
+#![allow(dead_code)]
+
+use bytes::Bytes;
+use mini_redis::Frame;
+use my_redis::{server, Config, Connection};
+use std::net::SocketAddr;
+use tokio::net::{TcpListener, TcpStream};
+
+pub async fn start_server() -> SocketAddr {
+    start_server_with(Config::default()).await
+}
+
+pub async fn start_server_with(config: Config) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move { server::run(listener, config).await });
+
+    addr
+}
+
+pub async fn connect(addr: SocketAddr) -> Connection {
+    Connection::new(TcpStream::connect(addr).await.unwrap())
+}
+
+pub async fn send(conn: &mut Connection, args: &[&str]) -> Frame {
+    let frame = Frame::Array(
+        args.iter()
+            .map(|arg| Frame::Bulk(Bytes::copy_from_slice(arg.as_bytes())))
+            .collect(),
+    );
+    conn.write_frame(&frame).await.unwrap();
+    conn.read_frame().await.unwrap().unwrap()
+}
+
+pub fn bulk_string(frame: Frame) -> String {
+    match frame {
+        Frame::Bulk(bytes) => String::from_utf8(bytes.to_vec()).unwrap(),
+        frame => panic!("expected bulk frame, got {:?}", frame),
+    }
+}
+
+pub fn error(frame: Frame) -> String {
+    match frame {
+        Frame::Error(msg) => msg,
+        frame => panic!("expected error frame, got {:?}", frame),
+    }
+}
+
+// A port nothing listens on, for listeners the server binds itself.
+pub fn free_port() -> u16 {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().port()
+}
+
+// Wait until a listener spawned by the server accepts connections.
+pub async fn wait_for_port(port: u16) {
+    for _ in 0..100 {
+        if TcpStream::connect(("127.0.0.1", port)).await.is_ok() {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    panic!("nothing listening on port {}", port);
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::*;
+use mini_redis::Frame;
+use my_redis::Config;
+
+#[tokio::test]
+async fn databases_are_selected_per_connection() {
+    let addr = start_server().await;
+    let mut a = connect(addr).await;
+    let mut b = connect(addr).await;
+
+    send(&mut a, &["SELECT", "1"]).await;
+    send(&mut a, &["SET", "foo", "one"]).await;
+    assert!(matches!(send(&mut b, &["GET", "foo"]).await, Frame::Null));
+    send(&mut b, &["SET", "foo", "zero"]).await;
+    assert_eq!(bulk_string(send(&mut a, &["GET", "foo"]).await), "one");
+
+    let msg = error(send(&mut a, &["SELECT", "16"]).await);
+    assert_eq!(msg, "ERR DB index is out of range");
+
+    let info = bulk_string(send(&mut a, &["INFO", "keyspace"]).await);
+    assert!(info.contains("db0:keys=1,expires=0\r\n"), "{}", info);
+    assert!(info.contains("db1:keys=1,expires=0\r\n"), "{}", info);
+}
+
+#[tokio::test]
+async fn move_and_swapdb() {
+    let addr = start_server().await;
+    let mut a = connect(addr).await;
+    let mut b = connect(addr).await;
+    send(&mut b, &["SELECT", "2"]).await;
+
+    send(&mut a, &["SET", "foo", "bar", "EX", "100"]).await;
+    assert!(matches!(
+        send(&mut a, &["MOVE", "foo", "2"]).await,
+        Frame::Integer(1)
+    ));
+    assert!(matches!(
+        send(&mut a, &["MOVE", "foo", "2"]).await,
+        Frame::Integer(0)
+    ));
+    assert_eq!(bulk_string(send(&mut b, &["GET", "foo"]).await), "bar");
+    let info = bulk_string(send(&mut a, &["INFO", "keyspace"]).await);
+    assert!(info.contains("db2:keys=1,expires=1\r\n"), "{}", info);
+
+    // b keeps db 2 selected, and sees the keys of db 0 after the swap.
+    send(&mut a, &["SET", "baz", "qux"]).await;
+    send(&mut a, &["SWAPDB", "0", "2"]).await;
+    assert!(matches!(send(&mut b, &["GET", "foo"]).await, Frame::Null));
+    assert_eq!(bulk_string(send(&mut b, &["GET", "baz"]).await), "qux");
+    assert_eq!(bulk_string(send(&mut a, &["GET", "foo"]).await), "bar");
+}
+
+#[tokio::test]
+async fn flushdb_and_flushall() {
+    let mut config = Config::default();
+    config.set("databases", "2").unwrap();
+    let addr = start_server_with(config).await;
+    let mut conn = connect(addr).await;
+
+    send(&mut conn, &["SET", "a", "1"]).await;
+    send(&mut conn, &["SELECT", "1"]).await;
+    send(&mut conn, &["SET", "b", "1"]).await;
+
+    send(&mut conn, &["FLUSHDB"]).await;
+    assert!(matches!(send(&mut conn, &["GET", "b"]).await, Frame::Null));
+    send(&mut conn, &["SELECT", "0"]).await;
+    assert_eq!(bulk_string(send(&mut conn, &["GET", "a"]).await), "1");
+
+    send(&mut conn, &["FLUSHALL", "ASYNC"]).await;
+    assert!(matches!(send(&mut conn, &["GET", "a"]).await, Frame::Null));
+    let info = bulk_string(send(&mut conn, &["INFO", "keyspace"]).await);
+    assert!(!info.contains("db0:"), "{}", info);
+}
@@ -0,0 +1,59 @@
+use mini_redis::Frame;
+use my_redis::{Client, Config, Connection, Server};
+use tokio::net::TcpStream;
+
+#[tokio::test]
+async fn server_on_an_ephemeral_port() {
+    let server = Server::builder().start().await.unwrap();
+    let addr = server.addr().unwrap();
+    assert_ne!(addr.port(), 0);
+
+    let mut client = Client::connect(addr).await.unwrap();
+    client.set("hello", "world".into()).await.unwrap();
+    assert_eq!(client.get("hello").await.unwrap().unwrap(), "world");
+
+    // another server has a keyspace of its own.
+    let other = Server::builder().start().await.unwrap();
+    let mut other_client = Client::connect(other.addr().unwrap()).await.unwrap();
+    assert!(other_client.get("hello").await.unwrap().is_none());
+
+    let mut conn = Connection::new(TcpStream::connect(addr).await.unwrap());
+    server.shutdown().await;
+    // the connections are closed, and nothing listens anymore.
+    assert!(!matches!(conn.read_frame().await, Ok(Some(_))));
+    assert!(TcpStream::connect(addr).await.is_err());
+    assert!(other_client.get("hello").await.is_ok());
+}
+
+#[tokio::test]
+async fn server_in_memory() {
+    let mut config = Config::default();
+    config.set("databases", "2").unwrap();
+    let server = Server::builder()
+        .config(config)
+        .in_memory()
+        .start()
+        .await
+        .unwrap();
+    assert!(server.addr().is_none());
+
+    let mut client = server.client();
+    client.set("hello", "world".into()).await.unwrap();
+
+    let mut conn = Connection::new(server.connect());
+    let get = Frame::Array(vec![Frame::Bulk("GET".into()), Frame::Bulk("hello".into())]);
+    conn.write_frame(&get).await.unwrap();
+    match conn.read_frame().await.unwrap() {
+        Some(Frame::Bulk(value)) => assert_eq!(value, "world"),
+        frame => panic!("expected bulk frame, got {:?}", frame),
+    }
+    let select = Frame::Array(vec![Frame::Bulk("SELECT".into()), Frame::Bulk("1".into())]);
+    conn.write_frame(&select).await.unwrap();
+    assert!(matches!(
+        conn.read_frame().await.unwrap(),
+        Some(Frame::Simple(_))
+    ));
+
+    server.shutdown().await;
+    assert!(conn.read_frame().await.unwrap().is_none());
+}
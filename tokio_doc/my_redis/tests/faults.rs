@@ -0,0 +1,111 @@
+use my_redis::faults::Faults;
+use my_redis::proxy::Proxy;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+// A proxy injecting `faults` in front of an echo server.
+async fn faulty_echo(faults: Faults) -> SocketAddr {
+    let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let proxy = Proxy {
+        upstream: upstream.local_addr().unwrap().to_string(),
+        faults,
+        ..Proxy::default()
+    };
+    let running = proxy.start().await.unwrap();
+    let addr = running.addr();
+
+    tokio::spawn(async move {
+        let _running = running;
+        loop {
+            let (mut socket, _) = upstream.accept().await.unwrap();
+            tokio::spawn(async move {
+                let (mut reader, mut writer) = socket.split();
+                let _ = tokio::io::copy(&mut reader, &mut writer).await;
+            });
+        }
+    });
+    addr
+}
+
+// Send `message`, and read what comes back until the connection is closed.
+async fn echo(addr: SocketAddr, message: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut client = TcpStream::connect(addr).await?;
+    client.write_all(message).await?;
+    client.shutdown().await?;
+    let mut reply = Vec::new();
+    client.read_to_end(&mut reply).await?;
+    Ok(reply)
+}
+
+fn seeded(seed: u64) -> Faults {
+    Faults {
+        seed: Some(seed),
+        ..Faults::default()
+    }
+}
+
+#[tokio::test]
+async fn delayed_writes() {
+    let addr = faulty_echo(Faults {
+        delay: Duration::from_millis(100),
+        ..seeded(1)
+    })
+    .await;
+
+    let start = Instant::now();
+    assert_eq!(echo(addr, b"hello").await.unwrap(), b"hello");
+    assert!(start.elapsed() >= Duration::from_millis(100));
+}
+
+#[tokio::test]
+async fn reset_connections() {
+    let addr = faulty_echo(Faults {
+        reset: 1.0,
+        ..seeded(1)
+    })
+    .await;
+
+    // without shutting down, which could race with the reset.
+    let mut client = TcpStream::connect(addr).await.unwrap();
+    client.write_all(b"hello").await.unwrap();
+    let e = client.read(&mut [0; 16]).await.unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::ConnectionReset);
+}
+
+#[tokio::test]
+async fn truncated_writes() {
+    let addr = faulty_echo(Faults {
+        truncate: 1.0,
+        ..seeded(1)
+    })
+    .await;
+
+    let message = b"a message long enough to be cut short";
+    let reply = echo(addr, message).await.unwrap();
+    assert!(reply.len() < message.len());
+    assert!(message.starts_with(&reply));
+}
+
+#[tokio::test]
+async fn corrupted_writes_are_reproducible() {
+    let message = [0u8; 64];
+    let mut replies = Vec::new();
+    for _ in 0..2 {
+        let addr = faulty_echo(Faults {
+            corrupt: 1.0,
+            ..seeded(42)
+        })
+        .await;
+        let reply = echo(addr, &message).await.unwrap();
+
+        // a single bit was flipped.
+        assert_eq!(reply.len(), message.len());
+        let flipped: u32 = reply.iter().map(|byte| byte.count_ones()).sum();
+        assert_eq!(flipped, 1);
+        replies.push(reply);
+    }
+    assert_eq!(replies[0], replies[1]);
+}
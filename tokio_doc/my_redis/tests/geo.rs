@@ -0,0 +1,321 @@
+mod common;
+
+use common::*;
+use mini_redis::Frame;
+use my_redis::Connection;
+
+// The members of a GEOSEARCH reply, or of its items with WITH* options.
+fn members(frame: Frame) -> Vec<String> {
+    match frame {
+        Frame::Array(items) => items
+            .into_iter()
+            .map(|item| match item {
+                Frame::Array(mut parts) => bulk_string(parts.remove(0)),
+                item => bulk_string(item),
+            })
+            .collect(),
+        frame => panic!("expected array frame, got {:?}", frame),
+    }
+}
+
+async fn sicily(conn: &mut Connection) {
+    let reply = send(
+        conn,
+        &[
+            "GEOADD",
+            "Sicily",
+            "13.361389",
+            "38.115556",
+            "Palermo",
+            "15.087269",
+            "37.502669",
+            "Catania",
+        ],
+    )
+    .await;
+    assert!(matches!(reply, Frame::Integer(2)));
+    let reply = send(
+        conn,
+        &[
+            "GEOADD",
+            "Sicily",
+            "12.758489",
+            "38.788135",
+            "edge1",
+            "17.241510",
+            "38.788135",
+            "edge2",
+        ],
+    )
+    .await;
+    assert!(matches!(reply, Frame::Integer(2)));
+}
+
+#[tokio::test]
+async fn geoadd_geopos_and_geodist() {
+    let addr = start_server().await;
+    let mut conn = connect(addr).await;
+    sicily(&mut conn).await;
+
+    for (unit, distance) in [
+        (None, "166274.1516"),
+        (Some("km"), "166.2742"),
+        (Some("mi"), "103.3182"),
+    ] {
+        let mut args = vec!["GEODIST", "Sicily", "Palermo", "Catania"];
+        args.extend(unit);
+        assert_eq!(bulk_string(send(&mut conn, &args).await), distance);
+    }
+    assert!(matches!(
+        send(&mut conn, &["GEODIST", "Sicily", "Palermo", "Rome"]).await,
+        Frame::Null
+    ));
+
+    match send(&mut conn, &["GEOPOS", "Sicily", "Palermo", "Rome"]).await {
+        Frame::Array(positions) => {
+            assert_eq!(positions.len(), 2);
+            let coordinates = match &positions[0] {
+                Frame::Array(coordinates) => coordinates.clone(),
+                frame => panic!("expected array frame, got {:?}", frame),
+            };
+            let lon: f64 = bulk_string(coordinates[0].clone()).parse().unwrap();
+            let lat: f64 = bulk_string(coordinates[1].clone()).parse().unwrap();
+            assert!((lon - 13.361389).abs() < 1e-5 && (lat - 38.115556).abs() < 1e-5);
+            assert!(matches!(positions[1], Frame::Null));
+        }
+        frame => panic!("expected array frame, got {:?}", frame),
+    }
+
+    // the score is the geohash, and members are updated in place.
+    assert!(matches!(send(&mut conn, &["TYPE", "Sicily"]).await, Frame::Simple(s) if s == "zset"));
+    let reply = send(
+        &mut conn,
+        &["GEOADD", "Sicily", "CH", "13", "38", "Palermo"],
+    )
+    .await;
+    assert!(matches!(reply, Frame::Integer(1)));
+    let reply = send(&mut conn, &["GEOADD", "Sicily", "NX", "0", "0", "Palermo"]).await;
+    assert!(matches!(reply, Frame::Integer(0)));
+    let reply = send(&mut conn, &["GEOADD", "missing", "XX", "0", "0", "Palermo"]).await;
+    assert!(matches!(reply, Frame::Integer(0)));
+    assert!(matches!(
+        send(&mut conn, &["TYPE", "missing"]).await,
+        Frame::Simple(s) if s == "none"
+    ));
+
+    let msg = error(send(&mut conn, &["GEOADD", "Sicily", "181", "10", "x"]).await);
+    assert_eq!(
+        msg,
+        "ERR invalid longitude,latitude pair 181.000000,10.000000"
+    );
+    let msg = error(send(&mut conn, &["GEOADD", "Sicily", "1", "2", "x", "3"]).await);
+    assert_eq!(msg, "ERR syntax error");
+    let msg = error(send(&mut conn, &["GEODIST", "Sicily", "a", "b", "yards"]).await);
+    assert_eq!(
+        msg,
+        "ERR unsupported unit provided. please use M, KM, FT, MI"
+    );
+    send(&mut conn, &["SET", "plain", "x"]).await;
+    let msg = error(send(&mut conn, &["GEOADD", "plain", "1", "2", "x"]).await);
+    assert!(msg.starts_with("WRONGTYPE"), "{}", msg);
+}
+
+#[tokio::test]
+async fn geosearch_by_radius_and_box() {
+    let addr = start_server().await;
+    let mut conn = connect(addr).await;
+    sicily(&mut conn).await;
+
+    let search = ["GEOSEARCH", "Sicily", "FROMLONLAT", "15", "37"];
+    let radius = [&search[..], &["BYRADIUS", "200", "km", "ASC"]].concat();
+    assert_eq!(
+        members(send(&mut conn, &radius).await),
+        ["Catania", "Palermo"]
+    );
+
+    let boxed = [
+        &search[..],
+        &["BYBOX", "400", "400", "km", "ASC", "WITHDIST"],
+    ]
+    .concat();
+    let reply = send(&mut conn, &boxed).await;
+    let distances: Vec<String> = match &reply {
+        Frame::Array(items) => items
+            .iter()
+            .map(|item| match item {
+                Frame::Array(parts) => bulk_string(parts[1].clone()),
+                item => panic!("expected array frame, got {:?}", item),
+            })
+            .collect(),
+        frame => panic!("expected array frame, got {:?}", frame),
+    };
+    assert_eq!(members(reply), ["Catania", "Palermo", "edge2", "edge1"]);
+    assert_eq!(distances, ["56.4413", "190.4424", "279.7403", "279.7405"]);
+
+    // the farthest, then the nearest.
+    let desc = [
+        &search[..],
+        &["BYBOX", "400", "400", "km", "DESC", "COUNT", "1"],
+    ]
+    .concat();
+    assert_eq!(members(send(&mut conn, &desc).await), ["edge1"]);
+    let count = [&search[..], &["BYBOX", "400", "400", "km", "COUNT", "2"]].concat();
+    assert_eq!(
+        members(send(&mut conn, &count).await),
+        ["Catania", "Palermo"]
+    );
+
+    let reply = send(
+        &mut conn,
+        &[
+            "GEOSEARCH",
+            "Sicily",
+            "FROMMEMBER",
+            "Palermo",
+            "BYRADIUS",
+            "50",
+            "km",
+            "WITHCOORD",
+            "WITHHASH",
+        ],
+    )
+    .await;
+    match &reply {
+        Frame::Array(items) => match &items[..] {
+            [Frame::Array(parts)] => {
+                assert!(matches!(parts[1], Frame::Integer(3_479_099_956_230_698)));
+                assert!(matches!(&parts[2], Frame::Array(c) if c.len() == 2));
+            }
+            items => panic!("expected one item, got {:?}", items),
+        },
+        frame => panic!("expected array frame, got {:?}", frame),
+    }
+
+    for (args, msg) in [
+        (
+            &[
+                "GEOSEARCH",
+                "Sicily",
+                "BYRADIUS",
+                "1",
+                "km",
+                "ASC",
+                "WITHDIST",
+            ][..],
+            "ERR exactly one of FROMMEMBER or FROMLONLAT can be specified for geosearch",
+        ),
+        (
+            &[
+                "GEOSEARCH",
+                "Sicily",
+                "FROMLONLAT",
+                "15",
+                "37",
+                "ASC",
+                "WITHDIST",
+            ],
+            "ERR exactly one of BYRADIUS and BYBOX can be specified for geosearch",
+        ),
+        (
+            &[
+                "GEOSEARCH",
+                "Sicily",
+                "FROMMEMBER",
+                "Rome",
+                "BYRADIUS",
+                "1",
+                "km",
+            ],
+            "ERR could not decode requested zset member",
+        ),
+        (
+            &[
+                "GEOSEARCH",
+                "Sicily",
+                "FROMLONLAT",
+                "15",
+                "37",
+                "BYRADIUS",
+                "1",
+                "km",
+                "ANY",
+            ],
+            "ERR the ANY argument requires COUNT argument",
+        ),
+    ] {
+        assert_eq!(error(send(&mut conn, args).await), msg, "{:?}", args);
+    }
+    let empty = send(
+        &mut conn,
+        &[
+            "GEOSEARCH",
+            "missing",
+            "FROMMEMBER",
+            "a",
+            "BYRADIUS",
+            "1",
+            "km",
+        ],
+    )
+    .await;
+    assert!(matches!(empty, Frame::Array(items) if items.is_empty()));
+}
+
+#[tokio::test]
+async fn geosearch_matches_a_full_scan() {
+    let addr = start_server().await;
+    let mut conn = connect(addr).await;
+
+    // a grid of points around the antimeridian and the equator.
+    let mut args = vec!["GEOADD".to_string(), "grid".to_string()];
+    for lon in (-20..20).map(|i| i as f64 * 0.5) {
+        for lat in (-20..20).map(|i| i as f64 * 0.5) {
+            let lon = if lon < 0.0 { lon + 180.0 } else { lon - 180.0 };
+            args.extend([lon.to_string(), lat.to_string(), format!("{},{}", lon, lat)]);
+        }
+    }
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    assert!(matches!(send(&mut conn, &args).await, Frame::Integer(1600)));
+
+    for radius in ["100", "300", "700"] {
+        let all = [
+            "GEOSEARCH",
+            "grid",
+            "FROMLONLAT",
+            "179.9",
+            "0.1",
+            "BYRADIUS",
+            "20000",
+            "km",
+            "ASC",
+            "WITHDIST",
+        ];
+        let near = [
+            "GEOSEARCH",
+            "grid",
+            "FROMLONLAT",
+            "179.9",
+            "0.1",
+            "BYRADIUS",
+            radius,
+            "km",
+            "ASC",
+        ];
+        let within: Vec<String> = match send(&mut conn, &all).await {
+            Frame::Array(items) => items
+                .into_iter()
+                .filter_map(|item| match item {
+                    Frame::Array(parts) => {
+                        let distance: f64 = bulk_string(parts[1].clone()).parse().unwrap();
+                        let radius: f64 = radius.parse().unwrap();
+                        (distance <= radius).then(|| bulk_string(parts[0].clone()))
+                    }
+                    item => panic!("expected array frame, got {:?}", item),
+                })
+                .collect(),
+            frame => panic!("expected array frame, got {:?}", frame),
+        };
+        assert!(!within.is_empty());
+        assert_eq!(members(send(&mut conn, &near).await), within, "{}", radius);
+    }
+}
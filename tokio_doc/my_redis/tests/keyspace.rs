@@ -0,0 +1,191 @@
+mod common;
+
+use bytes::Bytes;
+use common::*;
+use mini_redis::Frame;
+use my_redis::Connection;
+
+#[tokio::test]
+async fn type_rename_and_copy() {
+    let addr = start_server().await;
+    let mut conn = connect(addr).await;
+
+    send(&mut conn, &["SET", "a", "1", "EX", "100"]).await;
+    send(&mut conn, &["XADD", "s", "1-1", "f", "v"]).await;
+    for (key, kind) in [("a", "string"), ("s", "stream"), ("missing", "none")] {
+        assert!(matches!(send(&mut conn, &["TYPE", key]).await, Frame::Simple(s) if s == kind));
+    }
+
+    // the expire goes along with the value.
+    send(&mut conn, &["RENAME", "a", "b"]).await;
+    assert!(matches!(send(&mut conn, &["GET", "a"]).await, Frame::Null));
+    assert_eq!(bulk_string(send(&mut conn, &["GET", "b"]).await), "1");
+    let info = bulk_string(send(&mut conn, &["INFO", "keyspace"]).await);
+    assert!(info.contains("db0:keys=2,expires=1\r\n"), "{}", info);
+    assert_eq!(
+        error(send(&mut conn, &["RENAME", "a", "b"]).await),
+        "ERR no such key"
+    );
+    assert!(matches!(
+        send(&mut conn, &["RENAMENX", "b", "s"]).await,
+        Frame::Integer(0)
+    ));
+    assert!(matches!(
+        send(&mut conn, &["RENAMENX", "b", "a"]).await,
+        Frame::Integer(1)
+    ));
+
+    assert!(matches!(
+        send(&mut conn, &["COPY", "a", "c"]).await,
+        Frame::Integer(1)
+    ));
+    assert!(matches!(
+        send(&mut conn, &["COPY", "s", "c"]).await,
+        Frame::Integer(0)
+    ));
+    assert!(matches!(
+        send(&mut conn, &["COPY", "s", "c", "REPLACE"]).await,
+        Frame::Integer(1)
+    ));
+    assert!(matches!(send(&mut conn, &["TYPE", "c"]).await, Frame::Simple(s) if s == "stream"));
+    let msg = error(send(&mut conn, &["COPY", "a", "a"]).await);
+    assert_eq!(msg, "ERR source and destination objects are the same");
+
+    assert!(matches!(
+        send(&mut conn, &["COPY", "a", "a", "DB", "1"]).await,
+        Frame::Integer(1)
+    ));
+    send(&mut conn, &["SELECT", "1"]).await;
+    assert_eq!(bulk_string(send(&mut conn, &["GET", "a"]).await), "1");
+}
+
+#[tokio::test]
+async fn object_encoding_and_idletime() {
+    let addr = start_server().await;
+    let mut conn = connect(addr).await;
+
+    let long = "x".repeat(45);
+    send(&mut conn, &["SET", "int", "-12345"]).await;
+    send(&mut conn, &["SET", "short", "hello"]).await;
+    send(&mut conn, &["SET", "padded", "012"]).await;
+    send(&mut conn, &["SET", "long", &long]).await;
+    send(&mut conn, &["XADD", "stream", "*", "f", "v"]).await;
+
+    for (key, encoding) in [
+        ("int", "int"),
+        ("short", "embstr"),
+        ("padded", "embstr"),
+        ("long", "raw"),
+        ("stream", "stream"),
+    ] {
+        let reply = send(&mut conn, &["OBJECT", "ENCODING", key]).await;
+        assert_eq!(bulk_string(reply), encoding, "{}", key);
+    }
+
+    assert!(matches!(
+        send(&mut conn, &["OBJECT", "IDLETIME", "int"]).await,
+        Frame::Integer(0)
+    ));
+    assert!(matches!(
+        send(&mut conn, &["OBJECT", "FREQ", "int"]).await,
+        Frame::Integer(_)
+    ));
+    assert!(matches!(
+        send(&mut conn, &["OBJECT", "ENCODING", "missing"]).await,
+        Frame::Null
+    ));
+    let msg = error(send(&mut conn, &["OBJECT", "NOPE", "int"]).await);
+    assert!(msg.starts_with("ERR unknown subcommand"), "{}", msg);
+}
+
+async fn send_bytes(conn: &mut Connection, args: &[Bytes]) -> Frame {
+    let frame = Frame::Array(args.iter().cloned().map(Frame::Bulk).collect());
+    conn.write_frame(&frame).await.unwrap();
+    conn.read_frame().await.unwrap().unwrap()
+}
+
+async fn dump(conn: &mut Connection, key: &str) -> Bytes {
+    match send(conn, &["DUMP", key]).await {
+        Frame::Bulk(payload) => payload,
+        frame => panic!("expected bulk frame, got {:?}", frame),
+    }
+}
+
+async fn restore(
+    conn: &mut Connection,
+    key: &str,
+    ttl: &str,
+    payload: Bytes,
+    options: &[&str],
+) -> Frame {
+    let mut args = vec![
+        Bytes::from("RESTORE"),
+        Bytes::copy_from_slice(key.as_bytes()),
+        Bytes::copy_from_slice(ttl.as_bytes()),
+        payload,
+    ];
+    args.extend(
+        options
+            .iter()
+            .map(|arg| Bytes::copy_from_slice(arg.as_bytes())),
+    );
+    send_bytes(conn, &args).await
+}
+
+#[tokio::test]
+async fn dump_and_restore_between_servers() {
+    let mut source = connect(start_server().await).await;
+    let mut target = connect(start_server().await).await;
+
+    send(&mut source, &["SET", "greeting", "hello"]).await;
+    send(&mut source, &["XADD", "events", "1-1", "kind", "login"]).await;
+    send(&mut source, &["XGROUP", "CREATE", "events", "workers", "0"]).await;
+    let read = [
+        "XREADGROUP",
+        "GROUP",
+        "workers",
+        "alice",
+        "STREAMS",
+        "events",
+        ">",
+    ];
+    send(&mut source, &read).await;
+
+    for key in ["greeting", "events"] {
+        let payload = dump(&mut source, key).await;
+
+        let reply = restore(&mut target, key, "0", payload.clone(), &[]).await;
+        assert!(matches!(reply, Frame::Simple(s) if s == "OK"));
+        let reply = restore(&mut target, key, "0", payload.clone(), &[]).await;
+        assert_eq!(error(reply), "BUSYKEY Target key name already exists.");
+        let reply = restore(&mut target, key, "60000", payload, &["REPLACE"]).await;
+        assert!(matches!(reply, Frame::Simple(s) if s == "OK"));
+    }
+
+    assert_eq!(
+        bulk_string(send(&mut target, &["GET", "greeting"]).await),
+        "hello"
+    );
+    // the group and its pending entry came along.
+    let pending = send(&mut target, &["XPENDING", "events", "workers"]).await;
+    assert!(matches!(&pending, Frame::Array(parts) if matches!(parts[0], Frame::Integer(1))));
+    let info = bulk_string(send(&mut target, &["INFO", "keyspace"]).await);
+    assert!(info.contains("db0:keys=2,expires=2\r\n"), "{}", info);
+
+    // an absolute ttl in the past, nothing to create.
+    let payload = dump(&mut source, "greeting").await;
+    let reply = restore(&mut target, "copy", "1", payload.clone(), &["ABSTTL"]).await;
+    assert!(matches!(reply, Frame::Simple(s) if s == "OK"));
+    assert!(matches!(
+        send(&mut target, &["GET", "copy"]).await,
+        Frame::Null
+    ));
+
+    let mut damaged = payload.to_vec();
+    damaged[1] ^= 0xff;
+    let reply = restore(&mut target, "copy", "0", Bytes::from(damaged), &[]).await;
+    assert_eq!(
+        error(reply),
+        "ERR DUMP payload version or checksum are wrong"
+    );
+}
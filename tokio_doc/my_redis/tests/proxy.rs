@@ -0,0 +1,87 @@
+mod common;
+
+use common::*;
+use my_redis::proxy::Proxy;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+fn proxy_to(upstream: String) -> Proxy {
+    Proxy {
+        upstream,
+        connect_timeout: Duration::from_secs(1),
+        ..Proxy::default()
+    }
+}
+
+#[tokio::test]
+async fn proxy_forwards_commands_and_counts_bytes() {
+    let addr = start_server().await;
+    let proxy = proxy_to(addr.to_string()).start().await.unwrap();
+
+    let mut conn = connect(proxy.addr()).await;
+    assert!(
+        matches!(send(&mut conn, &["SET", "k", "v"]).await, mini_redis::Frame::Simple(s) if s == "OK")
+    );
+    assert_eq!(bulk_string(send(&mut conn, &["GET", "k"]).await), "v");
+
+    let stats = proxy.stats();
+    assert_eq!(stats.connections(), 1);
+    // the two commands, and the "+OK" and "$1 v" replies.
+    let sent = "*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n*2\r\n$3\r\nGET\r\n$1\r\nk\r\n";
+    assert_eq!(stats.upstream_bytes(), sent.len() as u64);
+    assert_eq!(stats.client_bytes(), "+OK\r\n$1\r\nv\r\n".len() as u64);
+
+    proxy.shutdown().await;
+    assert!(conn.read_frame().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn proxy_keeps_half_closed_connections() {
+    // an upstream replying once the client is done writing.
+    let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let proxy = proxy_to(upstream.local_addr().unwrap().to_string())
+        .start()
+        .await
+        .unwrap();
+    tokio::spawn(async move {
+        let (mut socket, _) = upstream.accept().await.unwrap();
+        let mut request = Vec::new();
+        socket.read_to_end(&mut request).await.unwrap();
+        request.reverse();
+        socket.write_all(&request).await.unwrap();
+    });
+
+    let mut client = TcpStream::connect(proxy.addr()).await.unwrap();
+    client.write_all(b"hello").await.unwrap();
+    client.shutdown().await.unwrap();
+    let mut reply = Vec::new();
+    client.read_to_end(&mut reply).await.unwrap();
+    assert_eq!(reply, b"olleh");
+}
+
+#[tokio::test]
+async fn proxy_closes_clients_of_an_unreachable_upstream() {
+    let proxy = proxy_to(format!("127.0.0.1:{}", free_port()))
+        .start()
+        .await
+        .unwrap();
+
+    let mut client = TcpStream::connect(proxy.addr()).await.unwrap();
+    let mut reply = Vec::new();
+    // closed, or reset.
+    let _ = client.read_to_end(&mut reply).await;
+    assert!(reply.is_empty());
+    assert_eq!(proxy.stats().connect_errors(), 1);
+
+    let args = ["--connect-timeout-ms", "250", "--upstream", "127.0.0.1:1"];
+    let proxy = Proxy::from_args(args.iter().map(|s| s.to_string())).unwrap();
+    assert_eq!(proxy.connect_timeout, Duration::from_millis(250));
+    assert_eq!(proxy.upstream, "127.0.0.1:1");
+    assert!(Proxy::from_args(
+        ["--connect-timeout-ms", "soon"]
+            .iter()
+            .map(|s| s.to_string())
+    )
+    .is_err());
+}
@@ -0,0 +1,121 @@
+mod common;
+
+use common::*;
+use mini_redis::Frame;
+use my_redis::{Config, Connection};
+
+// Read the next pub/sub message, as strings.
+async fn message(conn: &mut Connection) -> Vec<String> {
+    match conn.read_frame().await.unwrap().unwrap() {
+        Frame::Array(parts) => parts.into_iter().map(bulk_string).collect(),
+        frame => panic!("expected array frame, got {:?}", frame),
+    }
+}
+
+#[tokio::test]
+async fn publish_to_subscribers() {
+    let addr = start_server().await;
+    let mut subscriber = connect(addr).await;
+    let mut publisher = connect(addr).await;
+
+    let resp = send(&mut subscriber, &["SUBSCRIBE", "news", "sports"]).await;
+    assert!(matches!(resp, Frame::Array(_)), "{:?}", resp);
+    // the confirmation of the second channel.
+    assert!(matches!(
+        subscriber.read_frame().await.unwrap().unwrap(),
+        Frame::Array(parts) if matches!(parts[2], Frame::Integer(2))
+    ));
+    send(&mut subscriber, &["PSUBSCRIBE", "n*"]).await;
+
+    let resp = send(&mut publisher, &["PUBLISH", "news", "hello"]).await;
+    assert!(matches!(resp, Frame::Integer(2)), "{:?}", resp);
+    assert_eq!(message(&mut subscriber).await, ["message", "news", "hello"]);
+    assert_eq!(
+        message(&mut subscriber).await,
+        ["pmessage", "n*", "news", "hello"]
+    );
+
+    // only pub/sub commands while subscribed.
+    let msg = error(send(&mut subscriber, &["GET", "foo"]).await);
+    assert!(msg.starts_with("ERR Can't execute 'get'"), "{}", msg);
+
+    send(&mut subscriber, &["UNSUBSCRIBE"]).await;
+    subscriber.read_frame().await.unwrap();
+    send(&mut subscriber, &["PUNSUBSCRIBE"]).await;
+    assert!(matches!(
+        send(&mut subscriber, &["GET", "foo"]).await,
+        Frame::Null
+    ));
+    let resp = send(&mut publisher, &["PUBLISH", "news", "hello"]).await;
+    assert!(matches!(resp, Frame::Integer(0)), "{:?}", resp);
+}
+
+#[tokio::test]
+async fn disconnected_subscribers_are_dropped() {
+    let addr = start_server().await;
+    let mut subscriber = connect(addr).await;
+    send(&mut subscriber, &["SUBSCRIBE", "news"]).await;
+    drop(subscriber);
+
+    let mut publisher = connect(addr).await;
+    for _ in 0..100 {
+        if let Frame::Integer(0) = send(&mut publisher, &["PUBLISH", "news", "hi"]).await {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    panic!("subscriber still registered");
+}
+
+#[tokio::test]
+async fn keyspace_notifications() {
+    let mut config = Config::default();
+    config.set("notify-keyspace-events", "KEg$x").unwrap();
+    let addr = start_server_with(config).await;
+    let mut subscriber = connect(addr).await;
+    let mut conn = connect(addr).await;
+
+    send(&mut subscriber, &["PSUBSCRIBE", "__key*@0__:*"]).await;
+
+    send(&mut conn, &["SET", "foo", "bar"]).await;
+    assert_eq!(
+        message(&mut subscriber).await,
+        ["pmessage", "__key*@0__:*", "__keyspace@0__:foo", "set"]
+    );
+    assert_eq!(
+        message(&mut subscriber).await,
+        ["pmessage", "__key*@0__:*", "__keyevent@0__:set", "foo"]
+    );
+
+    send(&mut conn, &["DEL", "foo"]).await;
+    assert_eq!(message(&mut subscriber).await[3], "del");
+    assert_eq!(message(&mut subscriber).await[2], "__keyevent@0__:del");
+
+    // expired keys are notified by the background purge.
+    send(&mut conn, &["SET", "tmp", "1", "PX", "10"]).await;
+    let mut events = Vec::new();
+    while events.len() < 6 {
+        events.push(message(&mut subscriber).await[2].clone());
+    }
+    assert!(events.contains(&"__keyevent@0__:expire".to_string()));
+    assert!(events.contains(&"__keyevent@0__:expired".to_string()));
+}
+
+#[tokio::test]
+async fn keyspace_notifications_are_filtered_by_class() {
+    let mut config = Config::default();
+    config.set("notify-keyspace-events", "Eg").unwrap();
+    let addr = start_server_with(config).await;
+    let mut subscriber = connect(addr).await;
+    let mut conn = connect(addr).await;
+
+    send(&mut subscriber, &["PSUBSCRIBE", "__key*"]).await;
+    send(&mut conn, &["SET", "foo", "bar"]).await;
+    send(&mut conn, &["DEL", "foo"]).await;
+
+    // no `set` event, and no keyspace channel.
+    assert_eq!(
+        message(&mut subscriber).await,
+        ["pmessage", "__key*", "__keyevent@0__:del", "foo"]
+    );
+}
@@ -0,0 +1,151 @@
+mod common;
+
+use common::*;
+use mini_redis::Frame;
+use my_redis::Config;
+use std::time::{Duration, Instant};
+
+const CAS: &str = r#"
+    if redis_call("GET", KEYS[0]) == ARGV[0] {
+        redis_call("SET", KEYS[0], ARGV[1]);
+        true
+    } else {
+        false
+    }
+"#;
+
+#[tokio::test]
+async fn eval_runs_commands() {
+    let addr = start_server().await;
+    let mut conn = connect(addr).await;
+    send(&mut conn, &["SET", "foo", "1"]).await;
+
+    let resp = send(&mut conn, &["EVAL", CAS, "1", "foo", "1", "2"]).await;
+    assert!(matches!(resp, Frame::Integer(1)), "{:?}", resp);
+    let resp = send(&mut conn, &["EVAL", CAS, "1", "foo", "1", "3"]).await;
+    assert!(matches!(resp, Frame::Null), "{:?}", resp);
+    assert_eq!(bulk_string(send(&mut conn, &["GET", "foo"]).await), "2");
+
+    let resp = send(
+        &mut conn,
+        &["EVAL", "[KEYS[0], ARGV[0], 42]", "1", "a", "b"],
+    )
+    .await;
+    match resp {
+        Frame::Array(items) => {
+            assert_eq!(items.len(), 3);
+            assert_eq!(items[0], "a");
+            assert_eq!(items[1], "b");
+            assert!(matches!(items[2], Frame::Integer(42)));
+        }
+        resp => panic!("expected array, got {:?}", resp),
+    }
+}
+
+#[tokio::test]
+async fn evalsha_runs_loaded_scripts() {
+    let addr = start_server().await;
+    let mut conn = connect(addr).await;
+
+    let msg = error(send(&mut conn, &["EVALSHA", "0123", "0"]).await);
+    assert!(msg.starts_with("NOSCRIPT "), "{}", msg);
+
+    let sha = bulk_string(send(&mut conn, &["SCRIPT", "LOAD", "ARGV[0] + ARGV[1]"]).await);
+    assert_eq!(sha.len(), 40);
+    let resp = send(&mut conn, &["SCRIPT", "EXISTS", &sha, "0123"]).await;
+    assert!(
+        matches!(&resp, Frame::Array(items) if matches!(items[..], [Frame::Integer(1), Frame::Integer(0)])),
+        "{:?}",
+        resp
+    );
+    let resp = send(&mut conn, &["EVALSHA", &sha, "0", "foo", "bar"]).await;
+    assert_eq!(bulk_string(resp), "foobar");
+
+    send(&mut conn, &["SCRIPT", "FLUSH"]).await;
+    let msg = error(send(&mut conn, &["EVALSHA", &sha, "0"]).await);
+    assert!(msg.starts_with("NOSCRIPT "), "{}", msg);
+}
+
+#[tokio::test]
+async fn script_errors_are_replies() {
+    let mut config = Config::default();
+    config.set("script-time-limit", "50").unwrap();
+    let addr = start_server_with(config).await;
+    let mut conn = connect(addr).await;
+
+    let msg = error(send(&mut conn, &["EVAL", "redis_call(\"SET\", \"foo\")", "0"]).await);
+    assert!(msg.starts_with("ERR wrong number of arguments"), "{}", msg);
+    let msg = error(send(&mut conn, &["EVAL", "redis_call(\"EVAL\", \"1\", 0)", "0"]).await);
+    assert!(msg.contains("not allowed from script"), "{}", msg);
+    let msg = error(send(&mut conn, &["EVAL", "let x = ", "0"]).await);
+    assert!(msg.starts_with("ERR Error compiling script"), "{}", msg);
+    let msg = error(send(&mut conn, &["EVAL", "1", "2", "foo"]).await);
+    assert!(msg.contains("greater than number of args"), "{}", msg);
+    let msg = error(send(&mut conn, &["EVAL", "1", "2147483647"]).await);
+    assert!(msg.contains("greater than number of args"), "{}", msg);
+
+    let msg = error(send(&mut conn, &["EVAL", "loop {}", "0"]).await);
+    assert!(msg.contains("script killed"), "{}", msg);
+    // the server is still serving.
+    assert!(matches!(
+        send(&mut conn, &["GET", "foo"]).await,
+        Frame::Null
+    ));
+}
+
+#[tokio::test]
+async fn scripts_run_with_the_caller_permissions() {
+    let mut config = Config::default();
+    config
+        .set("user", "alice on >pw ~cache:* +@read +@scripting")
+        .unwrap();
+    let addr = start_server_with(config).await;
+    let mut conn = connect(addr).await;
+    send(&mut conn, &["AUTH", "alice", "pw"]).await;
+
+    let script = "redis_call(\"SET\", KEYS[0], \"1\")";
+    let msg = error(send(&mut conn, &["EVAL", script, "1", "cache:1"]).await);
+    assert!(msg.starts_with("NOPERM "), "{}", msg);
+    let msg = error(send(&mut conn, &["EVAL", "1", "1", "other"]).await);
+    assert!(msg.starts_with("NOPERM "), "{}", msg);
+}
+
+#[tokio::test]
+async fn scripts_are_bounded() {
+    let addr = start_server().await;
+    let mut conn = connect(addr).await;
+
+    let script = "let s = \"x\"; loop { s += s; }";
+    let msg = error(send(&mut conn, &["EVAL", script, "0"]).await);
+    assert!(msg.starts_with("ERR Error running script"), "{}", msg);
+    let script = "fn f(n) { f(n + 1) } f(0)";
+    let msg = error(send(&mut conn, &["EVAL", script, "0"]).await);
+    assert!(msg.starts_with("ERR Error running script"), "{}", msg);
+    // printing goes to the log.
+    let reply = send(&mut conn, &["EVAL", "print(\"hi\"); debug(1); 1", "0"]).await;
+    assert!(matches!(reply, Frame::Integer(1)));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn clients_waiting_for_a_script_dont_block_the_server() {
+    let mut config = Config::default();
+    config.set("script-time-limit", "1000").unwrap();
+    let addr = start_server_with(config).await;
+    let mut scripted = connect(addr).await;
+    let mut waiting = connect(addr).await;
+    let mut other = connect(addr).await;
+
+    let start = Instant::now();
+    let script = tokio::spawn(async move { send(&mut scripted, &["EVAL", "loop {}", "0"]).await });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let get = tokio::spawn(async move { send(&mut waiting, &["GET", "foo"]).await });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // the script and the GET waiting for it don't hold both threads, a
+    // command that doesn't need the lock is answered meanwhile.
+    error(send(&mut other, &["NOSUCHCOMMAND"]).await);
+    assert!(start.elapsed() < Duration::from_millis(800));
+
+    assert!(error(script.await.unwrap()).contains("script killed"));
+    assert!(matches!(get.await.unwrap(), Frame::Null));
+}
@@ -0,0 +1,330 @@
+mod common;
+
+use common::*;
+use mini_redis::Frame;
+use my_redis::{Config, Connection};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+#[tokio::test]
+async fn info_reports_clients_keyspace_and_commandstats() {
+    let addr = start_server().await;
+    let mut conn = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    send(&mut conn, &["SET", "hello", "world"]).await;
+    send(&mut conn, &["GET", "hello"]).await;
+    send(&mut conn, &["GET", "missing"]).await;
+
+    let info = bulk_string(send(&mut conn, &["INFO"]).await);
+    assert!(info.contains("# Server\r\n"));
+    assert!(info.contains("connected_clients:1\r\n"));
+    assert!(info.contains("db0:keys=1,expires=0\r\n"));
+    assert!(!info.contains("# Commandstats"));
+
+    let stats = bulk_string(send(&mut conn, &["INFO", "commandstats"]).await);
+    assert!(stats.starts_with("# Commandstats\r\n"));
+    assert!(stats.contains("cmdstat_get:calls=2,"));
+    assert!(stats.contains("cmdstat_set:calls=1,"));
+}
+
+#[tokio::test]
+async fn metrics_are_served_over_http() {
+    let port = free_port();
+    let mut config = Config::default();
+    config.set("metrics-port", &port.to_string()).unwrap();
+    let addr = start_server_with(config).await;
+    wait_for_port(port).await;
+
+    let mut conn = connect(addr).await;
+    send(&mut conn, &["SET", "hello", "world"]).await;
+
+    let mut socket = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    socket
+        .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    socket.read_to_string(&mut response).await.unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+    assert!(head.contains("\r\nContent-Type: text/plain; version=0.0.4\r\n"));
+    assert!(body.contains("my_redis_command_calls_total{cmd=\"set\"} 1\n"));
+}
+
+#[tokio::test]
+async fn unknown_command_is_an_error() {
+    let addr = start_server().await;
+    let mut conn = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    match send(&mut conn, &["NOPE"]).await {
+        Frame::Error(msg) => assert_eq!(msg, "ERR unknown command 'nope'"),
+        frame => panic!("expected error, got {:?}", frame),
+    }
+    // the connection is still usable.
+    match send(&mut conn, &["GET", "foo"]).await {
+        Frame::Null => {}
+        frame => panic!("expected null, got {:?}", frame),
+    }
+}
+
+#[tokio::test]
+async fn inline_commands_as_typed_in_telnet() {
+    let addr = start_server().await;
+    let mut socket = TcpStream::connect(addr).await.unwrap();
+
+    socket
+        .write_all(b"SET greeting \"hello world\"\r\nGET greeting\n")
+        .await
+        .unwrap();
+    let expected = b"+OK\r\n$11\r\nhello world\r\n";
+    let mut reply = vec![0; expected.len()];
+    socket.read_exact(&mut reply).await.unwrap();
+    assert_eq!(reply, expected);
+
+    // a RESP client sees the value set inline.
+    let mut conn = connect(addr).await;
+    assert_eq!(
+        bulk_string(send(&mut conn, &["GET", "greeting"]).await),
+        "hello world"
+    );
+}
+
+#[tokio::test]
+async fn noeviction_rejects_writes_over_maxmemory() {
+    let mut config = Config::default();
+    config.set("maxmemory", "1kb").unwrap();
+    let addr = start_server_with(config).await;
+    let mut conn = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let value = "x".repeat(300);
+    let mut rejected = None;
+    for i in 0..10 {
+        if let Frame::Error(msg) = send(&mut conn, &["SET", &format!("key:{}", i), &value]).await {
+            rejected = Some((i, msg));
+            break;
+        }
+    }
+
+    let (i, msg) = rejected.expect("writes should be rejected");
+    assert!(msg.starts_with("OOM "), "{}", msg);
+    // reads and deletes still work.
+    assert!(matches!(
+        send(&mut conn, &["GET", "key:0"]).await,
+        Frame::Bulk(_)
+    ));
+    assert!(matches!(
+        send(&mut conn, &["DEL", "key:0"]).await,
+        Frame::Integer(1)
+    ));
+    let key = format!("key:{}", i);
+    assert!(matches!(
+        send(&mut conn, &["SET", &key, &value]).await,
+        Frame::Simple(_)
+    ));
+}
+
+#[tokio::test]
+async fn allkeys_lru_evicts_instead_of_failing() {
+    let mut config = Config::default();
+    config.set("maxmemory", "1kb").unwrap();
+    config.set("maxmemory-policy", "allkeys-lru").unwrap();
+    let addr = start_server_with(config).await;
+    let mut conn = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    let value = "x".repeat(300);
+    for i in 0..10 {
+        let resp = send(&mut conn, &["SET", &format!("key:{}", i), &value]).await;
+        assert!(matches!(resp, Frame::Simple(_)), "{:?}", resp);
+    }
+
+    let info = bulk_string(send(&mut conn, &["INFO", "stats"]).await);
+    assert!(!info.contains("evicted_keys:0\r\n"), "{}", info);
+}
+
+#[tokio::test]
+async fn set_with_expire() {
+    let addr = start_server().await;
+    let mut conn = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    send(&mut conn, &["SET", "foo", "bar", "PX", "50"]).await;
+    assert!(matches!(
+        send(&mut conn, &["GET", "foo"]).await,
+        Frame::Bulk(_)
+    ));
+
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert!(matches!(
+        send(&mut conn, &["GET", "foo"]).await,
+        Frame::Null
+    ));
+}
+
+#[tokio::test]
+async fn slowlog_records_commands_over_the_threshold() {
+    let mut config = Config::default();
+    config.set("slowlog-log-slower-than", "0").unwrap();
+    config.set("slowlog-max-len", "2").unwrap();
+    let addr = start_server_with(config).await;
+    let mut conn = connect(addr).await;
+
+    send(&mut conn, &["CLIENT", "SETNAME", "slow"]).await;
+    send(&mut conn, &["SET", "k", "v"]).await;
+    send(&mut conn, &["GET", "k"]).await;
+    assert!(matches!(
+        send(&mut conn, &["SLOWLOG", "LEN"]).await,
+        Frame::Integer(2)
+    ));
+
+    let entries = match send(&mut conn, &["SLOWLOG", "GET", "1"]).await {
+        Frame::Array(entries) => entries,
+        frame => panic!("expected array frame, got {:?}", frame),
+    };
+    assert_eq!(entries.len(), 1);
+    match &entries[0] {
+        Frame::Array(entry) => {
+            // the SLOWLOG LEN before it.
+            assert!(matches!(entry[0], Frame::Integer(3)), "{:?}", entry);
+            match &entry[3] {
+                Frame::Array(args) => {
+                    assert_eq!(args[0], "slowlog");
+                    assert_eq!(args[1], "LEN");
+                }
+                frame => panic!("expected array frame, got {:?}", frame),
+            }
+            assert_eq!(entry[5], "slow");
+        }
+        frame => panic!("expected array frame, got {:?}", frame),
+    }
+
+    send(&mut conn, &["SLOWLOG", "RESET"]).await;
+    // the RESET itself is logged once it ran.
+    assert!(matches!(
+        send(&mut conn, &["SLOWLOG", "LEN"]).await,
+        Frame::Integer(1)
+    ));
+}
+
+#[tokio::test]
+async fn slowlog_redacts_passwords() {
+    let mut config = Config::default();
+    config.set("slowlog-log-slower-than", "0").unwrap();
+    config.set("requirepass", "secret").unwrap();
+    let addr = start_server_with(config).await;
+    let mut conn = connect(addr).await;
+
+    send(&mut conn, &["AUTH", "secret"]).await;
+    match send(&mut conn, &["SLOWLOG", "GET", "1"]).await {
+        Frame::Array(entries) => match &entries[0] {
+            Frame::Array(entry) => match &entry[3] {
+                Frame::Array(args) => {
+                    assert_eq!(args[0], "auth");
+                    assert_eq!(args[1], "(redacted)");
+                }
+                frame => panic!("expected array frame, got {:?}", frame),
+            },
+            frame => panic!("expected array frame, got {:?}", frame),
+        },
+        frame => panic!("expected array frame, got {:?}", frame),
+    }
+}
+
+#[tokio::test]
+async fn slowlog_can_be_disabled() {
+    let mut config = Config::default();
+    config.set("slowlog-log-slower-than", "-1").unwrap();
+    let addr = start_server_with(config).await;
+    let mut conn = connect(addr).await;
+
+    send(&mut conn, &["SET", "k", "v"]).await;
+    assert!(matches!(
+        send(&mut conn, &["SLOWLOG", "LEN"]).await,
+        Frame::Integer(0)
+    ));
+}
+
+#[tokio::test]
+async fn latency_histogram_per_command() {
+    let addr = start_server().await;
+    let mut conn = connect(addr).await;
+
+    send(&mut conn, &["SET", "k", "v"]).await;
+    send(&mut conn, &["GET", "k"]).await;
+    send(&mut conn, &["GET", "k"]).await;
+
+    let reply = match send(&mut conn, &["LATENCY", "HISTOGRAM", "GET"]).await {
+        Frame::Array(reply) => reply,
+        frame => panic!("expected array frame, got {:?}", frame),
+    };
+    assert_eq!(reply.len(), 2);
+    assert_eq!(reply[0], "get");
+    let stats = match &reply[1] {
+        Frame::Array(stats) => stats,
+        frame => panic!("expected array frame, got {:?}", frame),
+    };
+    assert!(matches!(stats[1], Frame::Integer(2)));
+    assert_eq!(stats[2], "histogram_usec");
+    match &stats[3] {
+        // bucket bounds and cumulative counts, ending with every call.
+        Frame::Array(histogram) => {
+            assert!(histogram.len() >= 2 && histogram.len() % 2 == 0);
+            assert!(matches!(histogram.last(), Some(Frame::Integer(2))));
+        }
+        frame => panic!("expected array frame, got {:?}", frame),
+    }
+}
+
+#[tokio::test]
+async fn idle_clients_are_disconnected() {
+    let mut config = Config::default();
+    config.set("timeout", "1").unwrap();
+    let addr = start_server_with(config).await;
+    let mut idle = connect(addr).await;
+    let mut subscriber = connect(addr).await;
+    send(&mut subscriber, &["SUBSCRIBE", "news"]).await;
+
+    let start = std::time::Instant::now();
+    assert!(idle.read_frame().await.unwrap().is_none());
+    assert!(start.elapsed() >= std::time::Duration::from_secs(1));
+
+    // subscribers wait for messages as long as they like.
+    let mut publisher = connect(addr).await;
+    send(&mut publisher, &["PUBLISH", "news", "hi"]).await;
+    assert!(matches!(
+        subscriber.read_frame().await.unwrap(),
+        Some(Frame::Array(_))
+    ));
+}
+
+#[tokio::test]
+async fn connections_over_maxclients_are_refused() {
+    let mut config = Config::default();
+    config.set("maxclients", "1").unwrap();
+    let addr = start_server_with(config).await;
+    let mut first = connect(addr).await;
+    send(&mut first, &["PING"]).await;
+
+    let mut refused = connect(addr).await;
+    match refused.read_frame().await.unwrap() {
+        Some(Frame::Error(msg)) => assert_eq!(msg, "ERR max number of clients reached"),
+        frame => panic!("expected error frame, got {:?}", frame),
+    }
+    assert!(refused.read_frame().await.unwrap().is_none());
+
+    let info = bulk_string(send(&mut first, &["INFO", "stats"]).await);
+    assert!(info.contains("rejected_connections:1\r\n"), "{}", info);
+
+    // the slot is free again once the client leaves.
+    drop(first);
+    for _ in 0..100 {
+        let mut conn = connect(addr).await;
+        let frame = Frame::Array(vec![Frame::Bulk("PING".into())]);
+        conn.write_frame(&frame).await.unwrap();
+        if let Some(Frame::Simple(pong)) = conn.read_frame().await.unwrap() {
+            assert_eq!(pong, "PONG");
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    panic!("connection still refused");
+}
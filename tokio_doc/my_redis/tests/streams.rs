@@ -0,0 +1,189 @@
+mod common;
+
+use common::*;
+use mini_redis::Frame;
+use std::time::{Duration, Instant};
+
+// The IDs of the entries in an XRANGE-like reply.
+fn ids(frame: Frame) -> Vec<String> {
+    match frame {
+        Frame::Array(entries) => entries
+            .into_iter()
+            .map(|entry| match entry {
+                Frame::Array(mut parts) => bulk_string(parts.remove(0)),
+                frame => panic!("expected entry, got {:?}", frame),
+            })
+            .collect(),
+        frame => panic!("expected array frame, got {:?}", frame),
+    }
+}
+
+// The entries of the only stream of an XREAD-like reply.
+fn read_ids(frame: Frame) -> Vec<String> {
+    match frame {
+        Frame::Array(mut streams) if streams.len() == 1 => match streams.remove(0) {
+            Frame::Array(mut parts) => ids(parts.remove(1)),
+            frame => panic!("expected stream, got {:?}", frame),
+        },
+        frame => panic!("expected one stream, got {:?}", frame),
+    }
+}
+
+#[tokio::test]
+async fn xadd_and_xrange() {
+    let addr = start_server().await;
+    let mut conn = connect(addr).await;
+
+    for id in ["1-1", "1-2", "2-0"] {
+        let resp = send(&mut conn, &["XADD", "s", id, "f", "v"]).await;
+        assert_eq!(bulk_string(resp), id);
+    }
+    let msg = error(send(&mut conn, &["XADD", "s", "1-5", "f", "v"]).await);
+    assert!(msg.contains("equal or smaller"), "{}", msg);
+    let auto = bulk_string(send(&mut conn, &["XADD", "s", "*", "f", "v"]).await);
+    assert!(matches!(
+        send(&mut conn, &["XLEN", "s"]).await,
+        Frame::Integer(4)
+    ));
+
+    let resp = send(&mut conn, &["XRANGE", "s", "-", "+", "COUNT", "3"]).await;
+    assert_eq!(ids(resp), ["1-1", "1-2", "2-0"]);
+    let resp = send(&mut conn, &["XRANGE", "s", "(1-1", "2"]).await;
+    assert_eq!(ids(resp), ["1-2", "2-0"]);
+    let resp = send(&mut conn, &["XREVRANGE", "s", "+", "2", "COUNT", "2"]).await;
+    assert_eq!(ids(resp), [auto.as_str(), "2-0"]);
+
+    send(&mut conn, &["XADD", "s", "MAXLEN", "~", "2", "*", "f", "v"]).await;
+    assert!(matches!(
+        send(&mut conn, &["XLEN", "s"]).await,
+        Frame::Integer(2)
+    ));
+
+    let msg = error(send(&mut conn, &["GET", "s"]).await);
+    assert!(msg.starts_with("WRONGTYPE "), "{}", msg);
+    send(&mut conn, &["SET", "str", "v"]).await;
+    let msg = error(send(&mut conn, &["XADD", "str", "*", "f", "v"]).await);
+    assert!(msg.starts_with("WRONGTYPE "), "{}", msg);
+}
+
+#[tokio::test]
+async fn xread_blocks_until_an_entry_is_added() {
+    let addr = start_server().await;
+    let mut reader = connect(addr).await;
+    let mut writer = connect(addr).await;
+    send(&mut writer, &["XADD", "s", "1-0", "f", "old"]).await;
+
+    let resp = send(&mut reader, &["XREAD", "STREAMS", "s", "0"]).await;
+    assert_eq!(read_ids(resp), ["1-0"]);
+
+    let read = tokio::spawn(async move {
+        send(&mut reader, &["XREAD", "BLOCK", "0", "STREAMS", "s", "$"]).await
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    // a write to another key doesn't wake it up for nothing.
+    send(&mut writer, &["XADD", "other", "1-0", "f", "v"]).await;
+    send(&mut writer, &["XADD", "s", "2-0", "f", "new"]).await;
+
+    assert_eq!(read_ids(read.await.unwrap()), ["2-0"]);
+}
+
+#[tokio::test]
+async fn xread_block_times_out() {
+    let addr = start_server().await;
+    let mut conn = connect(addr).await;
+
+    let start = Instant::now();
+    let resp = send(&mut conn, &["XREAD", "BLOCK", "100", "STREAMS", "s", "$"]).await;
+    assert!(matches!(resp, Frame::Null), "{:?}", resp);
+    assert!(start.elapsed() >= Duration::from_millis(100));
+}
+
+#[tokio::test]
+async fn consumer_groups() {
+    let addr = start_server().await;
+    let mut conn = connect(addr).await;
+
+    let msg = error(send(&mut conn, &["XGROUP", "CREATE", "s", "g", "$"]).await);
+    assert!(msg.contains("MKSTREAM"), "{}", msg);
+    send(&mut conn, &["XGROUP", "CREATE", "s", "g", "$", "MKSTREAM"]).await;
+    let msg = error(send(&mut conn, &["XGROUP", "CREATE", "s", "g", "$"]).await);
+    assert!(msg.starts_with("BUSYGROUP "), "{}", msg);
+
+    for id in ["1-0", "2-0", "3-0"] {
+        send(&mut conn, &["XADD", "s", id, "f", "v"]).await;
+    }
+
+    let args = ["XREADGROUP", "GROUP", "g", "alice", "COUNT", "2"];
+    let resp = send(&mut conn, &[&args[..], &["STREAMS", "s", ">"]].concat()).await;
+    assert_eq!(read_ids(resp), ["1-0", "2-0"]);
+    let args = ["XREADGROUP", "GROUP", "g", "bob", "STREAMS", "s", ">"];
+    assert_eq!(read_ids(send(&mut conn, &args).await), ["3-0"]);
+
+    let resp = send(&mut conn, &["XPENDING", "s", "g"]).await;
+    match resp {
+        Frame::Array(parts) => {
+            assert!(matches!(parts[0], Frame::Integer(3)));
+            assert_eq!(parts[1], "1-0");
+            assert_eq!(parts[2], "3-0");
+        }
+        frame => panic!("expected array frame, got {:?}", frame),
+    }
+
+    let resp = send(&mut conn, &["XACK", "s", "g", "1-0", "9-0"]).await;
+    assert!(matches!(resp, Frame::Integer(1)), "{:?}", resp);
+
+    // alice's pending entries, read again.
+    let args = ["XREADGROUP", "GROUP", "g", "alice", "STREAMS", "s", "0"];
+    assert_eq!(read_ids(send(&mut conn, &args).await), ["2-0"]);
+    let resp = send(&mut conn, &["XPENDING", "s", "g", "-", "+", "10", "alice"]).await;
+    match resp {
+        Frame::Array(entries) => match &entries[..] {
+            [Frame::Array(entry)] => {
+                assert_eq!(entry[0], "2-0");
+                assert!(matches!(entry[3], Frame::Integer(2)));
+            }
+            entries => panic!("expected one entry, got {:?}", entries),
+        },
+        frame => panic!("expected array frame, got {:?}", frame),
+    }
+
+    let msg = error(
+        send(
+            &mut conn,
+            &["XREADGROUP", "GROUP", "nope", "c", "STREAMS", "s", ">"],
+        )
+        .await,
+    );
+    assert!(msg.starts_with("NOGROUP "), "{}", msg);
+}
+
+#[tokio::test]
+async fn xreadgroup_blocks_for_new_entries() {
+    let addr = start_server().await;
+    let mut reader = connect(addr).await;
+    let mut writer = connect(addr).await;
+    send(
+        &mut writer,
+        &["XGROUP", "CREATE", "s", "g", "$", "MKSTREAM"],
+    )
+    .await;
+
+    let read = tokio::spawn(async move {
+        let args = [
+            "XREADGROUP",
+            "GROUP",
+            "g",
+            "c",
+            "BLOCK",
+            "5000",
+            "STREAMS",
+            "s",
+            ">",
+        ];
+        send(&mut reader, &args).await
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    send(&mut writer, &["XADD", "s", "1-0", "f", "v"]).await;
+
+    assert_eq!(read_ids(read.await.unwrap()), ["1-0"]);
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use common::*;
+use my_redis::{tls, Client, Config};
+use std::path::PathBuf;
+
+// Write a fresh self-signed certificate for "localhost" and its key to a
+// temporary directory.
+fn self_signed_cert(name: &str) -> (PathBuf, PathBuf) {
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+
+    let dir = std::env::temp_dir().join(format!("my_redis-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let cert_file = dir.join("cert.pem");
+    let key_file = dir.join("key.pem");
+    std::fs::write(&cert_file, cert.cert.pem()).unwrap();
+    std::fs::write(&key_file, cert.key_pair.serialize_pem()).unwrap();
+
+    (cert_file, key_file)
+}
+
+#[tokio::test]
+async fn tls_and_plaintext_listeners_share_the_keyspace() {
+    let (cert_file, key_file) = self_signed_cert("shared");
+    let tls_port = free_port();
+
+    let mut config = Config::default();
+    config.set("tls-port", &tls_port.to_string()).unwrap();
+    config
+        .set("tls-cert-file", cert_file.to_str().unwrap())
+        .unwrap();
+    config
+        .set("tls-key-file", key_file.to_str().unwrap())
+        .unwrap();
+    let addr = start_server_with(config).await;
+    wait_for_port(tls_port).await;
+
+    let client_config = tls::client_config(&cert_file).unwrap();
+    let mut secure = Client::connect_tls(("127.0.0.1", tls_port), "localhost", client_config)
+        .await
+        .unwrap();
+    secure.set("hello", "world".into()).await.unwrap();
+
+    let mut plain = Client::connect(addr).await.unwrap();
+    assert_eq!(plain.get("hello").await.unwrap().unwrap(), "world");
+    assert_eq!(secure.get("hello").await.unwrap().unwrap(), "world");
+}
+
+#[tokio::test]
+async fn tls_client_rejects_unknown_certificate() {
+    let (cert_file, key_file) = self_signed_cert("server");
+    let (other_cert, _) = self_signed_cert("other");
+    let tls_port = free_port();
+
+    let mut config = Config::default();
+    config.set("tls-port", &tls_port.to_string()).unwrap();
+    config
+        .set("tls-cert-file", cert_file.to_str().unwrap())
+        .unwrap();
+    config
+        .set("tls-key-file", key_file.to_str().unwrap())
+        .unwrap();
+    start_server_with(config).await;
+    wait_for_port(tls_port).await;
+
+    let client_config = tls::client_config(&other_cert).unwrap();
+    let result = Client::connect_tls(("127.0.0.1", tls_port), "localhost", client_config).await;
+    assert!(result.is_err());
+}
@@ -0,0 +1,178 @@
+mod common;
+
+use common::*;
+use mini_redis::Frame;
+use my_redis::Connection;
+
+// Read the next invalidation message, its keys or `None` for all keys.
+async fn invalidated(conn: &mut Connection) -> Option<Vec<String>> {
+    let mut parts = match conn.read_frame().await.unwrap().unwrap() {
+        Frame::Array(parts) => parts,
+        frame => panic!("expected array frame, got {:?}", frame),
+    };
+    let keys = parts.pop().unwrap();
+    let parts: Vec<String> = parts.into_iter().map(bulk_string).collect();
+    assert_eq!(parts, ["message", "__redis__:invalidate"]);
+    match keys {
+        Frame::Array(keys) => Some(keys.into_iter().map(bulk_string).collect()),
+        Frame::Null => None,
+        frame => panic!("expected array frame, got {:?}", frame),
+    }
+}
+
+// Nothing was sent to `conn` before the reply to a PING.
+async fn nothing_invalidated(conn: &mut Connection) {
+    assert!(matches!(send(conn, &["PING"]).await, Frame::Simple(s) if s == "PONG"));
+}
+
+#[tokio::test]
+async fn keys_read_are_invalidated_once() {
+    let addr = start_server().await;
+    let mut reader = connect(addr).await;
+    let mut writer = connect(addr).await;
+
+    assert!(matches!(
+        send(&mut reader, &["CLIENT", "TRACKING", "ON"]).await,
+        Frame::Simple(s) if s == "OK"
+    ));
+    send(&mut reader, &["GET", "a"]).await;
+    send(&mut reader, &["GET", "b"]).await;
+
+    send(&mut writer, &["SET", "a", "1"]).await;
+    assert_eq!(invalidated(&mut reader).await.unwrap(), ["a"]);
+    // until it's read again.
+    send(&mut writer, &["SET", "a", "2"]).await;
+    send(&mut writer, &["SET", "c", "1"]).await;
+    nothing_invalidated(&mut reader).await;
+
+    // the client's own writes too, unless NOLOOP.
+    send(&mut reader, &["SET", "b", "1"]).await;
+    assert_eq!(invalidated(&mut reader).await.unwrap(), ["b"]);
+    send(&mut reader, &["CLIENT", "TRACKING", "ON", "NOLOOP"]).await;
+    send(&mut reader, &["GET", "b"]).await;
+    send(&mut reader, &["DEL", "b"]).await;
+    nothing_invalidated(&mut reader).await;
+
+    send(&mut reader, &["GET", "a"]).await;
+    send(&mut writer, &["FLUSHALL"]).await;
+    assert_eq!(invalidated(&mut reader).await, None);
+
+    send(&mut reader, &["CLIENT", "TRACKING", "OFF"]).await;
+    send(&mut reader, &["GET", "a"]).await;
+    send(&mut writer, &["SET", "a", "3"]).await;
+    nothing_invalidated(&mut reader).await;
+}
+
+#[tokio::test]
+async fn broadcast_by_prefix() {
+    let addr = start_server().await;
+    let mut reader = connect(addr).await;
+    let mut writer = connect(addr).await;
+
+    let args = ["CLIENT", "TRACKING", "ON", "BCAST", "PREFIX", "user:"];
+    send(&mut reader, &args).await;
+    // keys it never read.
+    send(&mut writer, &["SET", "order:1", "x"]).await;
+    send(&mut writer, &["SET", "user:1", "x"]).await;
+    assert_eq!(invalidated(&mut reader).await.unwrap(), ["user:1"]);
+    send(&mut writer, &["SET", "user:1", "y"]).await;
+    assert_eq!(invalidated(&mut reader).await.unwrap(), ["user:1"]);
+    // the keys of a command come in one message.
+    send(&mut writer, &["DEL", "user:1", "order:1", "user:2"]).await;
+    assert_eq!(
+        invalidated(&mut reader).await.unwrap(),
+        ["user:1", "user:2"]
+    );
+
+    let msg = error(send(&mut reader, &["CLIENT", "TRACKING", "ON"]).await);
+    assert!(
+        msg.starts_with("ERR You can't switch BCAST mode"),
+        "{}",
+        msg
+    );
+    let msg = error(send(&mut writer, &["CLIENT", "TRACKING", "ON", "PREFIX", "a"]).await);
+    assert_eq!(msg, "ERR PREFIX option requires BCAST mode to be enabled");
+}
+
+#[tokio::test]
+async fn invalidations_redirected_to_a_subscriber() {
+    let addr = start_server().await;
+    let mut subscriber = connect(addr).await;
+    let mut reader = connect(addr).await;
+    let mut writer = connect(addr).await;
+
+    let id = match send(&mut subscriber, &["CLIENT", "ID"]).await {
+        Frame::Integer(id) => id.to_string(),
+        frame => panic!("expected integer frame, got {:?}", frame),
+    };
+    send(&mut subscriber, &["SUBSCRIBE", "__redis__:invalidate"]).await;
+
+    send(&mut reader, &["CLIENT", "TRACKING", "ON", "REDIRECT", &id]).await;
+    send(&mut reader, &["GET", "k"]).await;
+    send(&mut writer, &["SET", "k", "v"]).await;
+    assert_eq!(invalidated(&mut subscriber).await.unwrap(), ["k"]);
+    nothing_invalidated(&mut reader).await;
+
+    let msg = error(
+        send(
+            &mut reader,
+            &["CLIENT", "TRACKING", "ON", "REDIRECT", "9999"],
+        )
+        .await,
+    );
+    assert_eq!(msg, "ERR The client ID you want redirect to does not exist");
+}
+
+fn refresh(cache: &mut Option<String>, frame: Frame) {
+    match frame {
+        Frame::Array(_) => *cache = None,
+        Frame::Bulk(value) => *cache = Some(String::from_utf8(value.to_vec()).unwrap()),
+        _ => {}
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn cached_values_are_invalidated_under_concurrent_writes() {
+    let addr = start_server().await;
+    let mut reader = connect(addr).await;
+    send(&mut reader, &["CLIENT", "TRACKING", "ON"]).await;
+
+    let writer = tokio::spawn(async move {
+        let mut writer = connect(addr).await;
+        for i in 0..5000 {
+            send(&mut writer, &["SET", "k", &i.to_string()]).await;
+        }
+    });
+
+    // a client cache of "k": filled by GET, dropped on invalidation.
+    let mut cache: Option<String> = None;
+    let get = Frame::Array(vec![Frame::Bulk("GET".into()), Frame::Bulk("k".into())]);
+    let ping = Frame::Array(vec![Frame::Bulk("PING".into())]);
+    loop {
+        let done = writer.is_finished();
+        let request = if cache.is_none() { &get } else { &ping };
+        reader.write_frame(request).await.unwrap();
+        // invalidations come before or after the reply.
+        loop {
+            let frame = reader.read_frame().await.unwrap().unwrap();
+            let reply = !matches!(frame, Frame::Array(_));
+            refresh(&mut cache, frame);
+            if reply {
+                break;
+            }
+        }
+        if done {
+            break;
+        }
+    }
+
+    // the last invalidations, if any, are in by the reply to a PING.
+    reader.write_frame(&ping).await.unwrap();
+    while let frame @ Frame::Array(_) = reader.read_frame().await.unwrap().unwrap() {
+        refresh(&mut cache, frame);
+    }
+    if let Some(cached) = cache {
+        let mut conn = connect(addr).await;
+        assert_eq!(cached, bulk_string(send(&mut conn, &["GET", "k"]).await));
+    }
+}
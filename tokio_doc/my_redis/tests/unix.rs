@@ -0,0 +1,38 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::*;
+use my_redis::{Client, Config};
+use std::os::unix::fs::PermissionsExt;
+
+#[tokio::test]
+async fn unix_socket_listener() {
+    let path = std::env::temp_dir().join(format!("my_redis-{}.sock", std::process::id()));
+    // a stale file from an earlier run must not prevent binding.
+    std::fs::write(&path, b"").unwrap();
+
+    let mut config = Config::default();
+    config.set("unixsocket", path.to_str().unwrap()).unwrap();
+    config.set("unixsocketperm", "700").unwrap();
+    let addr = start_server_with(config).await;
+
+    let mut unix = None;
+    for _ in 0..100 {
+        if let Ok(client) = Client::connect_unix(&path).await {
+            unix = Some(client);
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    let mut unix = unix.expect("unix socket should accept connections");
+
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o700);
+
+    unix.set("hello", "world".into()).await.unwrap();
+    let mut tcp = Client::connect(addr).await.unwrap();
+    assert_eq!(tcp.get("hello").await.unwrap().unwrap(), "world");
+
+    std::fs::remove_file(&path).unwrap();
+}